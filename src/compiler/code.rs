@@ -53,7 +53,7 @@ macro_rules! op_build {
 // (类名, [枚举(操作数位数), ...])
 op_build!(
     Opcode,
-    61,
+    101,
     [
         // 常量
         Constant(2),
@@ -65,16 +65,62 @@ op_build!(
         ConstantOne(1), //一字节
         // 数组
         Array(2),
+        // 拼接栈上的多个数组(数组个数), 用于带展开元素的数组和参数
+        Concat(2),
         // Hash
         Hash(2),
+        // 模板字符串: 把栈上的多个值转为字符串后拼接(个数)
+        BuildString(2),
         // 索引操作
         Index(),
+        // 索引赋值(索引层数), 栈上为容器, 各层索引, 值, 执行后栈上留下所赋的值
+        SetIndex(1),
+        // 按索引路径取值但保留容器和索引(索引层数), 用于复合赋值
+        IndexPeek(1),
+        // 成员访问(名称常量索引)
+        GetField(2),
+        // 成员赋值(名称常量索引, 索引层数), 栈上为容器, 各层索引, 值, 执行后栈上留下所赋的值
+        SetField(2, 1),
+        // 方法调用(名称常量索引, 参数个数), 栈上为接收者, 各个参数
+        Invoke(2, 1),
+        // 带展开参数的方法调用(名称常量索引), 栈上为接收者, 参数数组
+        InvokeSpread(2),
+        // 结构体实例的字段(槽位), 用于方法内的this.field
+        GetSlot(1),
+        // 字段赋值(槽位), 栈上为实例, 值, 执行后栈上留下所赋的值
+        SetSlot(1),
+        // 结构体类型(类型常量索引, 方法个数), 栈上为各个方法的闭包
+        Struct(2, 1),
+        // 模块(模块常量索引, 成员个数), 栈上为各个导出的值
+        Module(2, 1),
+        // 模式匹配: 是否为指定长度的数组
+        MatchArray(1),
+        // 模式匹配: 是否为包含各个键的Hash(键的个数), 栈上为值, 各个键
+        MatchHash(1),
+        // 解构: 检查是否为至少有指定长度的数组
+        DestructArray(1),
+        // 解构: 检查是否为包含各个键的Hash或结构体实例(键的个数), 栈上为值, 各个键
+        DestructHash(1),
+        // 解构: 读取成员(名称常量索引), 结构体实例没有该成员时为null
+        DestructField(2),
+        // 区间(是否包含终点)
+        Range(1),
         Pop(),
+        // 复制栈顶的值, 赋值表达式保存变量后栈上仍留有所赋的值
+        Dup(),
         //四则运算符
         Add(),
         Sub(),
         Mul(),
         Div(),
+        Mod(),
+        Pow(),
+        //位运算符
+        BitAnd(),
+        BitOr(),
+        BitXor(),
+        Shl(),
+        Shr(),
         //布尔字面常量
         True(),
         False(),
@@ -88,9 +134,14 @@ op_build!(
         //一元运算符
         Neg(),
         Not(),
+        BitNot(),
         //跳转指令
         JumpIfNotTruthy(2),
+        JumpIfTruthy(2),
         JumpIfNotLess(2),
+        JumpIfNotLessEq(2),
+        // 栈顶不为null时跳转, 为null时弹出, 用于默认值
+        JumpIfNotNull(2),
         // JumpIfNotEq(2),
         JumpAlways(2),
         //全局变量绑定
@@ -114,6 +165,8 @@ op_build!(
         SetLocal2(),
         SetLocal3(),
         SetLocal4(),
+        // 给已声明的局部变量赋值, 变量被闭包捕获时写入共享的Cell
+        AssignLocal(1),
         //
         GetLocal(1),
         GetLocal0(), // get_local = 0..4
@@ -127,13 +180,27 @@ op_build!(
         // GetThis(),
         CurrentClosure(),
         GetFree(1),
+        SetFree(1),
+        // 创建闭包时捕获变量的Cell(而不是值), 使闭包内外共享同一个变量
+        CaptureLocal(1),
+        CaptureFree(1),
         // 赋值操作
         // Assign(2),//用SetGlobal/SetLocal代替
         // 函数调用(arg_len)
         Call(1),
+        // 带展开参数的函数调用, 栈上为函数, 参数数组
+        CallSpread(),
+        // for-in迭代器
+        IterNew(),
+        // 迭代结束跳转位置, 每次产出的值个数
+        IterNext(2, 1),
         // 函数返回值
         ReturnValue(),
         Return(),
+        // 异常处理: 登记catch位置, 撤销最近登记的处理器, 抛出栈顶的值
+        SetupTry(2),
+        PopTry(),
+        Throw(),
         //
         Null(),
         Uninitialize(),
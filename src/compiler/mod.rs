@@ -1,13 +1,17 @@
 use std::cell::RefCell;
+use std::mem;
+use std::path::Path;
 use std::rc::Rc;
 
 use crate::compiler::code::{Instructions, Opcode};
-use crate::compiler::symbol_table::{Symbol, SymbolScope, SymbolTable};
+use crate::compiler::symbol_table::{Binding, Symbol, SymbolScope, SymbolTable};
 use crate::create_rc_ref_cell;
+use crate::module::{self, ModuleError};
 use crate::object::builtins::BUILTINS;
-use crate::object::{CompiledFunction, Object};
+use crate::object::{CompiledFunction, Module, Object, StructType};
 use crate::parser::ast::{
-    BinaryOperator, BlockStatement, Expression, Program, Statement, UnaryOperator,
+    BinaryOperator, BlockStatement, Expression, Import, LetPattern, MatchArm, Parameter, Pattern,
+    Program, Statement, UnaryOperator,
 };
 use std::prelude::v1::Option::Some;
 
@@ -26,11 +30,15 @@ pub struct Compiler {
     symbol_table: RcSymbolTable,
     scopes: Vec<CompilationScope>,
     scope_index: usize,
+    // 编译导入的模块时, 外层各个文件的符号表
+    importers: Vec<RcSymbolTable>,
 }
 #[derive(Debug, Clone)]
 pub struct ByteCode {
     pub instructions: Instructions,
     pub constants: Constants,
+    // 顶层语句块中的局部变量个数
+    pub num_locals: usize,
 }
 #[derive(Debug, Clone)]
 pub struct EmittedInstruction {
@@ -43,19 +51,42 @@ pub struct CompilationScope {
     instructions: Instructions,
     last_instruction: Option<EmittedInstruction>,
     previous_instruction: Option<EmittedInstruction>,
+    // 当前函数内正在编译的循环
+    loops: Vec<LoopContext>,
+    // 当前函数内正在编译的try块及其finally, 内层在后
+    tries: Vec<Option<BlockStatement>>,
+    // 编译结构体方法时为该结构体的字段, this.field按槽位访问
+    struct_fields: Option<Vec<String>>,
+}
+/// 循环上下文, 记录待回填的break/continue跳转指令位置
+#[derive(Debug, Clone, Default)]
+pub struct LoopContext {
+    label: Option<String>,
+    // for-in循环的迭代器在栈上, 跳出时需要弹出
+    has_iterator: bool,
+    // 进入循环时所在的try块层数, 跳出循环时要离开更内层的try块
+    try_depth: usize,
+    break_positions: Vec<usize>,
+    continue_positions: Vec<usize>,
 }
 
 #[derive(Debug)]
 pub enum CompileError {
     UnknownBinOperator(BinaryOperator),
-    UnsupportedBinOperation(BinaryOperator, Expression, Expression),
-    _UnsupportedIndexOperation(Expression, Expression),
+    UnsupportedBinOperation(BinaryOperator, Box<Expression>, Box<Expression>),
+    _UnsupportedIndexOperation(Box<Expression>, Box<Expression>),
 
     _UnknownUnOperator(UnaryOperator),
     UnknownExpression(Expression),
 
     UndefinedIdentifier(String),
     CustomErrMsg(String),
+    /// break/continue不在循环内
+    LoopControlOutsideLoop(Box<Statement>),
+    UndefinedLabel(String),
+    Import(ModuleError),
+    /// 给const变量重新赋值
+    AssignToConstant(String),
 }
 
 impl CompilationScope {
@@ -128,12 +159,16 @@ impl Compiler {
             symbol_table,
             scopes: vec![main_scope],
             scope_index: 1,
+            importers: vec![],
         }
     }
     /// 编译为字节码
     pub fn compile(&mut self, program: &Program) -> CompileResult<ByteCode> {
-        for statement in &program.statements {
-            self.compile_statement(statement)?;
+        let symbol_table = self.symbol_table.clone();
+        if let Err(e) = self.compile_statements(&program.statements) {
+            //丢弃出错时未离开的语句块, 交互模式下符号表还要继续使用
+            symbol_table.borrow_mut().blocks.clear();
+            return Err(e);
         }
         Ok(self.bytecode())
     }
@@ -141,15 +176,12 @@ impl Compiler {
         ByteCode::new(
             self.cur_instruction().clone(),
             Constants::clone(&self.constants),
+            self.symbol_table.borrow().num_main_locals,
         )
     }
     fn enter_scope(&mut self) {
         //当前作用域
-        let scope = CompilationScope {
-            instructions: vec![],
-            last_instruction: None,
-            previous_instruction: None,
-        };
+        let scope = CompilationScope::new();
         //入栈
         if self.scope_index >= self.scopes.len() {
             self.scopes.push(scope);
@@ -175,75 +207,537 @@ impl Compiler {
         match statement {
             Statement::Let(name, expr) => {
                 //先定义函数名，不然递归会找不着当前函数
-                if self.symbol_table.borrow_mut().resolve(name).is_some() {
-                    return Err(CompileError::CustomErrMsg(format!(
-                        "variable {} has been declared!",
-                        name
-                    )));
-                }
-                let symbol = self.symbol_table.borrow_mut().define(name);
+                let symbol = self.declare_symbol(name)?;
                 self.compile_expression(expr)?;
                 self.store_symbol(symbol);
             }
+            Statement::Const(name, expr) => {
+                self.compile_const_statement(name, expr)?;
+            }
+            Statement::Destructure(pattern, expr) => {
+                self.compile_expression(expr)?;
+                self.compile_let_pattern(pattern)?;
+            }
             Statement::Return(ret) => match ret {
                 None => {
+                    self.compile_try_exits(0)?;
                     self.emit(Opcode::Return, vec![]);
                 }
                 Some(expr) => {
                     self.compile_expression(expr)?;
+                    self.compile_try_exits(0)?;
                     self.emit(Opcode::ReturnValue, vec![]);
                 }
             },
             Statement::Expression(expr) => {
                 self.compile_expression(expr)?;
-                if let Expression::Binary(bin_op, _, _) = expr {
-                    if bin_op == &BinaryOperator::Assign {
-                        //赋值表达式末尾不添加Pop指令
-                        return Ok(());
-                    }
-                }
                 self.emit(Opcode::Pop, vec![]);
             }
             Statement::Comment(_comment) => {
                 //todo ignore comment
             }
             Statement::For(init, cond, after, blocks) => {
-                if let Some(init) = init.as_deref() {
-                    self.compile_statement(init)?;
+                self.compile_for_statement(init, cond, after, blocks, None)?;
+            }
+            Statement::ForIn(names, iterable, blocks) => {
+                self.compile_for_in_statement(names, iterable, blocks, None)?;
+            }
+            Statement::While(cond, blocks) => {
+                self.compile_while_statement(cond, blocks, None)?;
+            }
+            Statement::Break(label) | Statement::Continue(label) => {
+                let is_break = matches!(statement, Statement::Break(_));
+                let loop_context = self.find_loop(label).ok_or_else(|| match label {
+                    Some(label) => CompileError::UndefinedLabel(label.clone()),
+                    None => CompileError::LoopControlOutsideLoop(Box::new(statement.clone())),
+                })?;
+                let try_depth = self.scopes[self.scope_index - 1].loops[loop_context].try_depth;
+                self.compile_try_exits(try_depth)?;
+                //跳到外层循环时, 弹出内层for-in循环的迭代器
+                let inner_iterators = self.scopes[self.scope_index - 1].loops[loop_context + 1..]
+                    .iter()
+                    .filter(|l| l.has_iterator)
+                    .count();
+                for _ in 0..inner_iterators {
+                    self.emit(Opcode::Pop, vec![]);
                 }
-                let tag = self.cur_instruction_len();
-
-                let mut jump_if_pos = 0; //当前值随意，如果不跳出循环，永远用不到，如果能跳出循环，一定设置为了正确值
-                if let Some(cond) = cond {
-                    self.compile_expression(cond)?;
-                    jump_if_pos = self.get_jump_if_pos()?;
-                    //
-                    self.enter_scope();
-                    self.compile_block_statement(blocks)?;
-                    let mut insts = self.leave_scope();
-                    self.add_instruction(&mut insts);
-
-                    if let Some(after) = after {
-                        self.compile_expression(after)?;
-                    }
+                let pos = self.emit(Opcode::JumpAlways, vec![9999]);
+                let loop_context = &mut self.scopes[self.scope_index - 1].loops[loop_context];
+                if is_break {
+                    loop_context.break_positions.push(pos);
+                } else {
+                    loop_context.continue_positions.push(pos);
                 }
-                //始终跳转到tag处
-                self.emit(Opcode::JumpAlways, vec![tag]);
-                //if不成立则跳转到此处
-                let after_blocks = self.cur_instruction_len();
-                self.change_operand(jump_if_pos, after_blocks);
-                self.emit(Opcode::Null, vec![]);
             }
+            Statement::Labeled(label, statement) => match statement.as_ref() {
+                Statement::For(init, cond, after, blocks) => {
+                    self.compile_for_statement(init, cond, after, blocks, Some(label.clone()))?;
+                }
+                Statement::ForIn(names, iterable, blocks) => {
+                    self.compile_for_in_statement(names, iterable, blocks, Some(label.clone()))?;
+                }
+                Statement::While(cond, blocks) => {
+                    self.compile_while_statement(cond, blocks, Some(label.clone()))?;
+                }
+                statement => self.compile_statement(statement)?,
+            },
             Statement::Function(name, args, blocks) => {
-                let symbol = self.symbol_table.borrow_mut().define(name);
-                self.compile_function_expression(Some(name.clone()), args, blocks)?;
+                let symbol = self.declare_symbol(name)?;
+                self.compile_function_expression(Some(name.clone()), args, blocks, None)?;
                 self.store_symbol(symbol);
+            }
+            Statement::Struct(name, fields, methods) => {
+                self.compile_struct_statement(name, fields, methods)?;
+            }
+            Statement::Throw(expr) => {
+                self.compile_expression(expr)?;
+                self.emit(Opcode::Throw, vec![]);
+            }
+            Statement::Try(body, catch, finally) => {
+                self.compile_try_statement(body, catch, finally)?;
+            }
+            Statement::Import(path, import) => {
+                self.compile_import_statement(path, import)?;
+            }
+            Statement::Export(statement) => {
+                self.compile_statement(statement)?;
+            }
+            Statement::Block(block) => {
+                self.compile_block_statement(block)?;
             } // _ => unimplemented!(),
         }
         Ok(())
     }
+    /// 在当前块中声明变量, 可以遮蔽外层同名变量, 同一块内不能重复声明
+    fn declare_symbol(&mut self, name: &str) -> CompileResult<Rc<Symbol>> {
+        self.check_declaration(name)?;
+        Ok(self.symbol_table.borrow_mut().define(name))
+    }
+    /// 声明不能重新赋值的变量(const变量和结构体名)
+    fn declare_const_symbol(&mut self, name: &str, binding: Binding) -> CompileResult<Rc<Symbol>> {
+        self.check_declaration(name)?;
+        Ok(self.symbol_table.borrow_mut().define_const(name, binding))
+    }
+    /// 内置函数不属于任何块, 与外层变量一样可以被遮蔽
+    fn check_declaration(&mut self, name: &str) -> CompileResult {
+        if self
+            .symbol_table
+            .borrow()
+            .is_declared_in_current_block(name)
+        {
+            return Err(CompileError::CustomErrMsg(format!(
+                "variable {} has been declared!",
+                name
+            )));
+        }
+        Ok(())
+    }
+    /// 编译const声明
+    ///
+    /// 值为字面量时加入常量池, 读取时直接压入该常量, 不再存入变量
+    fn compile_const_statement(&mut self, name: &str, expr: &Expression) -> CompileResult {
+        let literal = match expr {
+            Expression::IntLiteral(value) => Some(Object::Integer(*value)),
+            Expression::FloatLiteral(value) => Some(Object::Float(*value)),
+            Expression::StringLiteral(string) => Some(Object::String(string.to_string())),
+            _ => None,
+        };
+        if let Some(literal) = literal {
+            let index = self.add_constant(literal);
+            self.declare_const_symbol(name, Binding::Constant(Some(index)))?;
+            return Ok(());
+        }
+        //值为结构体构造调用时, 之后按槽位访问其字段
+        let binding = match self.resolve_struct_call(expr) {
+            Some(fields) => Binding::Instance(fields),
+            None => Binding::Constant(None),
+        };
+        let symbol = self.declare_const_symbol(name, binding)?;
+        if let Expression::FunctionLiteral(args, blocks) = expr {
+            //与fn声明一样, 函数内可以通过函数名递归
+            self.compile_function_expression(Some(name.to_string()), args, blocks, None)?;
+        } else {
+            self.compile_expression(expr)?;
+        }
+        self.store_symbol(symbol);
+        Ok(())
+    }
+    /// 编译结构体声明, 结构体名与const变量一样不能重新赋值
+    ///
+    /// 各方法的闭包; Struct 类型常量 方法个数; 存入结构体名
+    fn compile_struct_statement(
+        &mut self,
+        name: &str,
+        fields: &[String],
+        methods: &[(String, Vec<Parameter>, BlockStatement)],
+    ) -> CompileResult {
+        let binding = Binding::Struct(Rc::new(fields.to_vec()));
+        let symbol = self.declare_const_symbol(name, binding)?;
+        if symbol.scope == SymbolScope::Local {
+            //先初始化局部变量, 方法中捕获的是变量本身, 创建类型后再赋值
+            self.emit(Opcode::Null, vec![]);
+            self.store_symbol(symbol.clone());
+        }
+        for (method, args, blocks) in methods {
+            self.compile_function_expression(Some(method.clone()), args, blocks, Some(fields))?;
+        }
+        //方法在运行时由栈上的闭包填充
+        let template = StructType::new(
+            name.to_string(),
+            fields.to_vec(),
+            methods
+                .iter()
+                .map(|(method, _, _)| (method.clone(), Object::Null))
+                .collect(),
+        );
+        let const_index = self.add_constant(Object::Struct(Rc::new(template)));
+        self.emit(Opcode::Struct, vec![const_index, methods.len()]);
+        self.assign_symbol(symbol)
+    }
+    /// 编译for循环, init中声明的变量只在循环内可见
+    ///
+    /// init; tag: cond; JumpIfNot end; blocks; continue: after; JumpAlways tag; end: Null
+    fn compile_for_statement(
+        &mut self,
+        init: &Option<Box<Statement>>,
+        cond: &Option<Expression>,
+        after: &Option<Expression>,
+        blocks: &BlockStatement,
+        label: Option<String>,
+    ) -> CompileResult {
+        self.symbol_table.borrow_mut().enter_block();
+        if let Some(init) = init.as_deref() {
+            self.compile_statement(init)?;
+        }
+        let tag = self.cur_instruction_len();
+        let mut jump_if_pos = None;
+        if let Some(cond) = cond {
+            jump_if_pos = Some(self.compile_condition(cond)?);
+        }
+        self.enter_loop(label, false);
+        self.compile_block_statement(blocks)?;
+        //continue跳转到after处
+        let continue_pos = self.cur_instruction_len();
+        if let Some(after) = after {
+            self.compile_statement(&Statement::Expression(after.clone()))?;
+        }
+        //始终跳转到tag处
+        self.emit(Opcode::JumpAlways, vec![tag]);
+        //if不成立则跳转到此处
+        let after_blocks = self.cur_instruction_len();
+        if let Some(jump_if_pos) = jump_if_pos {
+            self.change_operand(jump_if_pos, after_blocks);
+        }
+        self.leave_loop(continue_pos, after_blocks);
+        self.symbol_table.borrow_mut().leave_block();
+        // 循环语句的值为null
+        self.emit(Opcode::Null, vec![]);
+        self.emit(Opcode::Pop, vec![]);
+        Ok(())
+    }
+    /// 编译for-in循环
+    ///
+    /// iterable; IterNew; tag: IterNext end; set names; blocks; JumpAlways tag; end: Pop; Null
+    fn compile_for_in_statement(
+        &mut self,
+        names: &[String],
+        iterable: &Expression,
+        blocks: &BlockStatement,
+        label: Option<String>,
+    ) -> CompileResult {
+        self.compile_expression(iterable)?;
+        self.emit(Opcode::IterNew, vec![]);
+        let tag = self.cur_instruction_len();
+        //迭代结束则跳转到end处
+        let iter_next_pos = self.emit(Opcode::IterNext, vec![9999, names.len()]);
+        //栈上依次为(索引/键, 元素/值), 倒序绑定, 循环变量只在循环内可见
+        self.symbol_table.borrow_mut().enter_block();
+        let symbols = names
+            .iter()
+            .map(|name| self.symbol_table.borrow_mut().define(name))
+            .collect::<Vec<_>>();
+        for symbol in symbols.into_iter().rev() {
+            self.store_symbol(symbol);
+        }
+        self.enter_loop(label, true);
+        self.compile_block_statement(blocks)?;
+        self.emit(Opcode::JumpAlways, vec![tag]);
+        let end = self.cur_instruction_len();
+        self.change_operand(iter_next_pos, end);
+        self.leave_loop(tag, end);
+        self.symbol_table.borrow_mut().leave_block();
+        //弹出迭代器
+        self.emit(Opcode::Pop, vec![]);
+        // 循环语句的值为null
+        self.emit(Opcode::Null, vec![]);
+        self.emit(Opcode::Pop, vec![]);
+        Ok(())
+    }
+    /// 编译while循环
+    ///
+    /// tag: cond; JumpIfNot end; blocks; JumpAlways tag; end: Null
+    fn compile_while_statement(
+        &mut self,
+        cond: &Expression,
+        blocks: &BlockStatement,
+        label: Option<String>,
+    ) -> CompileResult {
+        let tag = self.cur_instruction_len();
+        let jump_if_pos = self.compile_condition(cond)?;
+        self.enter_loop(label, false);
+        self.compile_block_statement(blocks)?;
+        self.emit(Opcode::JumpAlways, vec![tag]);
+        let after_blocks = self.cur_instruction_len();
+        self.change_operand(jump_if_pos, after_blocks);
+        self.leave_loop(tag, after_blocks);
+        // 循环语句的值为null
+        self.emit(Opcode::Null, vec![]);
+        self.emit(Opcode::Pop, vec![]);
+        Ok(())
+    }
+    /// 编译import语句
+    ///
+    /// 模块在第一次导入处编译并执行, 模块对象存入隐藏的全局变量, 再次导入时直接读取
+    ///
+    /// 导入的名字不能重新赋值, 模块名.成员和导入的成员直接读取模块的变量
+    fn compile_import_statement(&mut self, path: &str, import: &Import) -> CompileResult {
+        let file = module::resolve(path).map_err(CompileError::Import)?;
+        let key = format!("@module:{}", file.display());
+        let cached = self.main_symbol_table().borrow().store.get(&key).cloned();
+        let module = match cached {
+            Some(module) => module,
+            None => {
+                let program = module::enter(&file).map_err(CompileError::Import)?;
+                let result = self.compile_module(&file, &program);
+                module::leave();
+                let exports = Binding::Module(Rc::new(result?));
+                let symbol = self.symbol_table.borrow_mut().define_const(&key, exports);
+                self.store_symbol(symbol.clone());
+                //记在最外层文件的符号表中, 其他文件和交互模式的后续输入都能找到
+                let main_symbol_table = self.main_symbol_table();
+                main_symbol_table
+                    .borrow_mut()
+                    .store
+                    .insert(key, symbol.clone());
+                symbol
+            }
+        };
+        match import {
+            Import::Module(name) => {
+                let symbol = self.declare_const_symbol(name, module.binding.clone())?;
+                self.load(&module);
+                self.store_symbol(symbol);
+            }
+            Import::Names(names) => {
+                for (name, alias) in names {
+                    match Self::module_member(&module, name) {
+                        Some(member) => {
+                            self.check_declaration(alias)?;
+                            self.symbol_table.borrow_mut().define_alias(alias, &member);
+                        }
+                        //没有导出的成员, 运行时报错
+                        None => {
+                            let symbol = self.declare_symbol(alias)?;
+                            self.load(&module);
+                            let name = self.add_constant(Object::String(name.clone()));
+                            self.emit(Opcode::GetField, vec![name]);
+                            self.store_symbol(symbol);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    /// 编译模块, 模块的变量在自己的符号表中, 全局变量和顶层局部变量的槽位接着外层文件分配
+    ///
+    /// 模块代码; 各个导出的变量; Module 模块常量 成员个数
+    ///
+    /// 返回导出变量的符号
+    fn compile_module(&mut self, file: &Path, program: &Program) -> CompileResult<Vec<Rc<Symbol>>> {
+        let mut module_table = SymbolTable::new();
+        for (i, builtin) in BUILTINS.iter().enumerate() {
+            module_table.define_builtin(i, builtin);
+        }
+        module_table.num_definitions = self.symbol_table.borrow().num_definitions;
+        module_table.num_main_locals = self.symbol_table.borrow().num_main_locals;
+        let importer = mem::replace(&mut self.symbol_table, create_rc_ref_cell(module_table));
+        self.importers.push(importer);
+        let result = self.compile_statements(&program.statements);
+        let importer = self.importers.pop().unwrap();
+        let module_table = mem::replace(&mut self.symbol_table, importer);
+        result?;
+        let mut module_table = module_table.borrow_mut();
+        {
+            let mut symbol_table = self.symbol_table.borrow_mut();
+            symbol_table.num_definitions = module_table.num_definitions;
+            symbol_table.num_main_locals = module_table.num_main_locals;
+        }
+        let exports = program.exports();
+        let symbols: Vec<_> = exports
+            .iter()
+            .map(|name| module_table.resolve(name).unwrap())
+            .collect();
+        for symbol in &symbols {
+            self.load(symbol);
+        }
+        //成员在运行时由栈上的值填充
+        let template = Module::new(
+            module::module_name(file),
+            exports
+                .iter()
+                .map(|name| (name.to_string(), Object::Null))
+                .collect(),
+        );
+        let const_index = self.add_constant(Object::Module(Rc::new(template)));
+        self.emit(Opcode::Module, vec![const_index, exports.len()]);
+        Ok(symbols)
+    }
+    /// 模块导出的变量
+    fn module_member(module: &Symbol, name: &str) -> Option<Rc<Symbol>> {
+        match &module.binding {
+            Binding::Module(exports) => exports.iter().find(|s| s.name == name).cloned(),
+            _ => None,
+        }
+    }
+    /// 编译时可以确定为导入模块的成员的lib.name, 返回模块导出的变量
+    fn resolve_member(&self, obj: &Expression, name: &str) -> Option<Rc<Symbol>> {
+        match obj {
+            Expression::Identifier(lib) => {
+                let module = self.symbol_table.borrow_mut().resolve(lib)?;
+                Self::module_member(&module, name)
+            }
+            _ => None,
+        }
+    }
+    /// 最外层文件的符号表
+    fn main_symbol_table(&self) -> RcSymbolTable {
+        self.importers.first().unwrap_or(&self.symbol_table).clone()
+    }
+    /// 编译try语句
+    ///
+    /// SetupTry catch; try块; PopTry; JumpAlways finally
+    /// catch: [SetupTry 异常finally]; 绑定异常; catch块; [PopTry]; JumpAlways finally
+    /// 异常finally: 暂存异常; finally块; 重新抛出
+    /// finally: finally块
+    fn compile_try_statement(
+        &mut self,
+        body: &BlockStatement,
+        catch: &Option<(Option<String>, BlockStatement)>,
+        finally: &Option<BlockStatement>,
+    ) -> CompileResult {
+        let mut setup_pos = self.emit(Opcode::SetupTry, vec![9999]);
+        self.scopes[self.scope_index - 1]
+            .tries
+            .push(finally.clone());
+        self.compile_block_statement(body)?;
+        self.scopes[self.scope_index - 1].tries.pop();
+        self.emit(Opcode::PopTry, vec![]);
+        let mut finally_positions = vec![self.emit(Opcode::JumpAlways, vec![9999])];
+        if let Some((name, handler)) = catch {
+            let catch_pos = self.cur_instruction_len();
+            self.change_operand(setup_pos, catch_pos);
+            // 有finally时, catch块中抛出的异常也要先执行finally
+            if finally.is_some() {
+                setup_pos = self.emit(Opcode::SetupTry, vec![9999]);
+                self.scopes[self.scope_index - 1]
+                    .tries
+                    .push(finally.clone());
+            }
+            self.symbol_table.borrow_mut().enter_block();
+            match name {
+                Some(name) => {
+                    let symbol = self.declare_symbol(name)?;
+                    self.store_symbol(symbol);
+                }
+                None => {
+                    self.emit(Opcode::Pop, vec![]);
+                }
+            }
+            self.compile_statements(&handler.statements)?;
+            self.symbol_table.borrow_mut().leave_block();
+            if finally.is_some() {
+                self.scopes[self.scope_index - 1].tries.pop();
+                self.emit(Opcode::PopTry, vec![]);
+            }
+            finally_positions.push(self.emit(Opcode::JumpAlways, vec![9999]));
+        }
+        if let Some(finally) = finally {
+            let rethrow_pos = self.cur_instruction_len();
+            self.change_operand(setup_pos, rethrow_pos);
+            self.symbol_table.borrow_mut().enter_block();
+            let error = self.define_temp();
+            self.compile_block_statement(finally)?;
+            self.load(&error);
+            self.emit(Opcode::Throw, vec![]);
+            self.symbol_table.borrow_mut().leave_block();
+        }
+        let finally_pos = self.cur_instruction_len();
+        for pos in finally_positions {
+            self.change_operand(pos, finally_pos);
+        }
+        if let Some(finally) = finally {
+            self.compile_block_statement(finally)?;
+        }
+        // try语句的值为null
+        self.emit(Opcode::Null, vec![]);
+        self.emit(Opcode::Pop, vec![]);
+        Ok(())
+    }
+    /// break/continue/return提前离开try块时, 由内向外撤销异常处理器并执行finally
+    fn compile_try_exits(&mut self, try_depth: usize) -> CompileResult {
+        let tries = self.scopes[self.scope_index - 1].tries.clone();
+        for (depth, finally) in tries.iter().enumerate().skip(try_depth).rev() {
+            self.emit(Opcode::PopTry, vec![]);
+            if let Some(finally) = finally {
+                // finally中的break/continue/return只需离开更外层的try块
+                self.scopes[self.scope_index - 1].tries.truncate(depth);
+                self.compile_block_statement(finally)?;
+            }
+        }
+        self.scopes[self.scope_index - 1].tries = tries;
+        Ok(())
+    }
+    /// 进入循环
+    fn enter_loop(&mut self, label: Option<String>, has_iterator: bool) {
+        let try_depth = self.scopes[self.scope_index - 1].tries.len();
+        self.scopes[self.scope_index - 1].loops.push(LoopContext {
+            label,
+            has_iterator,
+            try_depth,
+            ..Default::default()
+        });
+    }
+    /// 离开循环, 回填break和continue的跳转位置
+    fn leave_loop(&mut self, continue_pos: usize, break_pos: usize) {
+        let loop_context = self.scopes[self.scope_index - 1].loops.pop().unwrap();
+        for pos in loop_context.break_positions {
+            self.change_operand(pos, break_pos);
+        }
+        for pos in loop_context.continue_positions {
+            self.change_operand(pos, continue_pos);
+        }
+    }
+    /// 查找break/continue的目标循环, 没有标签时为最内层循环
+    fn find_loop(&self, label: &Option<String>) -> Option<usize> {
+        let loops = &self.scopes[self.scope_index - 1].loops;
+        match label {
+            None => loops.len().checked_sub(1),
+            Some(_) => loops.iter().rposition(|l| &l.label == label),
+        }
+    }
+    fn in_loop(&self) -> bool {
+        !self.scopes[self.scope_index - 1].loops.is_empty()
+    }
+    /// 编译语句块, 块内声明的变量离开块后不可见
     fn compile_block_statement(&mut self, block_statement: &BlockStatement) -> CompileResult {
-        for statement in &block_statement.statements {
+        self.symbol_table.borrow_mut().enter_block();
+        self.compile_statements(&block_statement.statements)?;
+        self.symbol_table.borrow_mut().leave_block();
+        Ok(())
+    }
+    fn compile_statements(&mut self, statements: &[Statement]) -> CompileResult {
+        for statement in statements {
             self.compile_statement(statement)?;
         }
         Ok(())
@@ -254,6 +748,9 @@ impl Compiler {
             Expression::IntLiteral(value) => {
                 self.add_constant_one_and_emit(Object::Integer(*value));
             }
+            Expression::FloatLiteral(value) => {
+                self.add_constant_one_and_emit(Object::Float(*value));
+            }
             Expression::BoolLiteral(bool) => {
                 if *bool {
                     self.emit(Opcode::True, vec![]);
@@ -264,6 +761,15 @@ impl Compiler {
             Expression::StringLiteral(string) => {
                 self.add_constant_one_and_emit(Object::String(string.to_string()));
             }
+            Expression::Template(parts) => {
+                for part in parts {
+                    self.compile_expression(part)?;
+                }
+                self.emit(Opcode::BuildString, vec![parts.len()]);
+            }
+            Expression::ArrayLiteral(items) if items.iter().any(Expression::is_spread) => {
+                self.compile_spread_items(items)?;
+            }
             Expression::ArrayLiteral(items) => {
                 for item in items {
                     self.compile_expression(item)?;
@@ -282,6 +788,61 @@ impl Compiler {
                 self.compile_expression(index_expr)?;
                 self.emit(Opcode::Index, vec![]);
             }
+            Expression::Field(obj, field) => {
+                if let Some(member) = self.resolve_member(obj, field) {
+                    self.load(&member);
+                    return Ok(());
+                }
+                self.compile_expression(obj)?;
+                if let Some(slot) = self.resolve_slot(obj, field) {
+                    self.emit(Opcode::GetSlot, vec![slot]);
+                } else {
+                    let name = self.add_constant(Object::String(field.clone()));
+                    self.emit(Opcode::GetField, vec![name]);
+                }
+            }
+            Expression::MethodCall(receiver, method, args)
+                if args.iter().any(Expression::is_spread) =>
+            {
+                if let Some(member) = self.resolve_member(receiver, method) {
+                    self.load(&member);
+                    self.compile_spread_items(args)?;
+                    self.emit(Opcode::CallSpread, vec![]);
+                    return Ok(());
+                }
+                self.compile_expression(receiver)?;
+                self.compile_spread_items(args)?;
+                let name = self.add_constant(Object::String(method.clone()));
+                self.emit(Opcode::InvokeSpread, vec![name]);
+            }
+            Expression::MethodCall(receiver, method, args) => {
+                if let Some(member) = self.resolve_member(receiver, method) {
+                    self.load(&member);
+                    for arg in args {
+                        self.compile_expression(arg)?;
+                    }
+                    self.emit(Opcode::Call, vec![args.len()]);
+                    return Ok(());
+                }
+                self.compile_expression(receiver)?;
+                for arg in args {
+                    self.compile_expression(arg)?;
+                }
+                let name = self.add_constant(Object::String(method.clone()));
+                self.emit(Opcode::Invoke, vec![name, args.len()]);
+            }
+            Expression::Range(start, end, inclusive) => {
+                //省略的端点为null
+                for bound in [start, end] {
+                    match bound {
+                        Some(bound) => self.compile_expression(bound)?,
+                        None => {
+                            self.emit(Opcode::Null, vec![]);
+                        }
+                    }
+                }
+                self.emit(Opcode::Range, vec![*inclusive as usize]);
+            }
             Expression::Identifier(name) => {
                 self.load_symbol(name)?;
             }
@@ -290,35 +851,10 @@ impl Compiler {
                 self.compile_unary_expression(op)?;
             }
             Expression::Binary(op, left, right) => {
-                if op == &BinaryOperator::Assign {
-                    //赋值
-                    match left.as_ref() {
-                        Expression::Identifier(name) => {
-                            self.compile_expression(right)?;
-                            self.compile_assign(name)?;
-                        }
-                        Expression::Index(left_expr, index_expr) => match left_expr.as_ref() {
-                            Expression::Identifier(obj_name) => {
-                                self.compile_expression(index_expr)?;
-                                self.compile_expression(right)?;
-                                self.compile_assign(obj_name)?;
-                            }
-                            _ => {
-                                return Err(CompileError::UnsupportedBinOperation(
-                                    op.clone(),
-                                    *left.clone(),
-                                    *right.clone(),
-                                ));
-                            }
-                        },
-                        _ => {
-                            return Err(CompileError::UnsupportedBinOperation(
-                                op.clone(),
-                                *left.clone(),
-                                *right.clone(),
-                            ));
-                        }
-                    }
+                if op.is_assign() {
+                    self.compile_assign_expression(op, left, right)?;
+                } else if op == &BinaryOperator::And || op == &BinaryOperator::Or {
+                    self.compile_logical_expression(op, left, right)?;
                 } else {
                     self.compile_expression(left)?;
                     self.compile_expression(right)?;
@@ -326,8 +862,7 @@ impl Compiler {
                 }
             }
             Expression::If(cond, cons, alt) => {
-                self.compile_expression(cond)?;
-                let jump_if_pos = self.get_jump_if_pos()?;
+                let jump_if_pos = self.compile_condition(cond)?;
                 //条件不成立跳转的位置
                 // let jump_if_not_truthy_pos = self.emit(Opcode::JumpIfNotTruthy, vec![9999]);
                 self.compile_block_statement(cons)?;
@@ -337,6 +872,9 @@ impl Compiler {
                 if self.last_instruction_is(Opcode::Pop) {
                     // 移除pop指令
                     self.remove_last_instruction()?;
+                } else {
+                    // 语句块为空或以声明、赋值结尾时没有值, 以null作为返回值
+                    self.emit(Opcode::Null, vec![]);
                 }
                 //如果if语句块正常执行到这，就不能继续后面的else块，应该跳转到整个语句末尾
                 let jump_always_pos = self.emit(Opcode::JumpAlways, vec![9999]);
@@ -348,6 +886,8 @@ impl Compiler {
                     self.compile_block_statement(alt)?;
                     if self.last_instruction_is(Opcode::Pop) {
                         self.remove_last_instruction()?;
+                    } else {
+                        self.emit(Opcode::Null, vec![]);
                     }
                 } else {
                     self.emit(Opcode::Null, vec![]);
@@ -356,8 +896,16 @@ impl Compiler {
                 let final_pos = self.cur_instruction_len();
                 self.change_operand(jump_always_pos, final_pos);
             }
+            Expression::Match(subject, arms) => {
+                self.compile_match_expression(subject, arms)?;
+            }
             Expression::FunctionLiteral(args, blocks) => {
-                self.compile_function_expression(None, args, blocks)?;
+                self.compile_function_expression(None, args, blocks, None)?;
+            }
+            Expression::Call(fun, args) if args.iter().any(Expression::is_spread) => {
+                self.compile_expression(fun)?;
+                self.compile_spread_items(args)?;
+                self.emit(Opcode::CallSpread, vec![]);
             }
             Expression::Call(fun, args) => {
                 self.compile_expression(fun)?;
@@ -366,25 +914,269 @@ impl Compiler {
                 }
                 self.emit(Opcode::Call, vec![args.len()]);
             }
+            Expression::Spread(_) => {
+                return Err(CompileError::CustomErrMsg(format!(
+                    "`{}` is only allowed in call arguments and array literals",
+                    expression
+                )))
+            }
             _ => return Err(CompileError::UnknownExpression(expression.clone())),
         }
         Ok(())
     }
+    /// 编译带展开元素的列表, 相邻的普通元素组成数组, 最后拼接为一个数组
+    ///
+    /// [a, ...b, c] => a; Array 1; b; c; Array 1; Concat 3
+    fn compile_spread_items(&mut self, items: &[Expression]) -> CompileResult {
+        let mut parts = 0;
+        let mut plain = 0;
+        for item in items {
+            if let Expression::Spread(spread) = item {
+                if plain > 0 {
+                    self.emit(Opcode::Array, vec![plain]);
+                    parts += 1;
+                    plain = 0;
+                }
+                self.compile_expression(spread)?;
+                parts += 1;
+            } else {
+                self.compile_expression(item)?;
+                plain += 1;
+            }
+        }
+        if plain > 0 {
+            self.emit(Opcode::Array, vec![plain]);
+            parts += 1;
+        }
+        self.emit(Opcode::Concat, vec![parts]);
+        Ok(())
+    }
+    /// 编译match表达式, 被匹配的值存入临时变量, 依次尝试各分支, 都不匹配时为null
+    ///
+    /// value; Set tmp; 模式测试(失败跳到下一分支); guard; JumpIfNot 下一分支; body; JumpAlways end; ...; Null; end:
+    fn compile_match_expression(
+        &mut self,
+        subject: &Expression,
+        arms: &[MatchArm],
+    ) -> CompileResult {
+        self.symbol_table.borrow_mut().enter_block();
+        self.compile_expression(subject)?;
+        let value = self.define_temp();
+        let mut end_positions = vec![];
+        for arm in arms {
+            //每个分支绑定的变量只在该分支内可见
+            self.symbol_table.borrow_mut().enter_block();
+            let mut fail_positions = self.compile_pattern(&arm.pattern, &value)?;
+            if let Some(guard) = &arm.guard {
+                fail_positions.push(self.compile_condition(guard)?);
+            }
+            self.compile_block_statement(&arm.body)?;
+            if self.last_instruction_is(Opcode::Pop) {
+                self.remove_last_instruction()?;
+            } else {
+                self.emit(Opcode::Null, vec![]);
+            }
+            end_positions.push(self.emit(Opcode::JumpAlways, vec![9999]));
+            let next_arm_pos = self.cur_instruction_len();
+            for pos in fail_positions {
+                self.change_operand(pos, next_arm_pos);
+            }
+            self.symbol_table.borrow_mut().leave_block();
+        }
+        self.emit(Opcode::Null, vec![]);
+        let end_pos = self.cur_instruction_len();
+        for pos in end_positions {
+            self.change_operand(pos, end_pos);
+        }
+        self.symbol_table.borrow_mut().leave_block();
+        Ok(())
+    }
+    /// 编译模式测试, 被测试的值存放在变量value中, 返回匹配失败时待回填的跳转指令位置
+    fn compile_pattern(&mut self, pattern: &Pattern, value: &Symbol) -> CompileResult<Vec<usize>> {
+        let mut fail_positions = vec![];
+        match pattern {
+            Pattern::Wildcard => {}
+            Pattern::Binding(name) => {
+                self.load(value);
+                self.bind_pattern_variable(name)?;
+            }
+            Pattern::Literal(literal) => {
+                self.load(value);
+                self.compile_expression(literal)?;
+                self.emit(Opcode::Equal, vec![]);
+                fail_positions.push(self.emit(Opcode::JumpIfNotTruthy, vec![9999]));
+            }
+            Pattern::Array(items) => {
+                self.load(value);
+                self.emit(Opcode::MatchArray, vec![items.len()]);
+                fail_positions.push(self.emit(Opcode::JumpIfNotTruthy, vec![9999]));
+                for (i, item) in items.iter().enumerate() {
+                    if item == &Pattern::Wildcard {
+                        continue;
+                    }
+                    self.load(value);
+                    self.add_constant_one_and_emit(Object::Integer(i as i64));
+                    self.emit(Opcode::Index, vec![]);
+                    fail_positions.extend(self.compile_pattern_on_stack(item)?);
+                }
+            }
+            Pattern::Hash(pairs) => {
+                self.load(value);
+                for (key, _) in pairs {
+                    self.compile_expression(key)?;
+                }
+                self.emit(Opcode::MatchHash, vec![pairs.len()]);
+                fail_positions.push(self.emit(Opcode::JumpIfNotTruthy, vec![9999]));
+                for (key, item) in pairs {
+                    if item == &Pattern::Wildcard {
+                        continue;
+                    }
+                    self.load(value);
+                    self.compile_expression(key)?;
+                    self.emit(Opcode::Index, vec![]);
+                    fail_positions.extend(self.compile_pattern_on_stack(item)?);
+                }
+            }
+            Pattern::Or(alternatives) => {
+                //前面的分支匹配成功时跳到末尾, 失败时尝试下一个分支
+                let mut matched_positions = vec![];
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    let positions = self.compile_pattern(alternative, value)?;
+                    if i == alternatives.len() - 1 {
+                        fail_positions = positions;
+                        break;
+                    }
+                    matched_positions.push(self.emit(Opcode::JumpAlways, vec![9999]));
+                    let next_pos = self.cur_instruction_len();
+                    for pos in positions {
+                        self.change_operand(pos, next_pos);
+                    }
+                }
+                let matched_pos = self.cur_instruction_len();
+                for pos in matched_positions {
+                    self.change_operand(pos, matched_pos);
+                }
+            }
+        }
+        Ok(fail_positions)
+    }
+    /// 对栈顶的值进行模式测试, 变量模式直接绑定, 其余存入临时变量
+    fn compile_pattern_on_stack(&mut self, pattern: &Pattern) -> CompileResult<Vec<usize>> {
+        if let Pattern::Binding(name) = pattern {
+            self.bind_pattern_variable(name)?;
+            return Ok(vec![]);
+        }
+        let value = self.define_temp();
+        self.compile_pattern(pattern, &value)
+    }
+    /// 栈顶的值绑定到模式中的变量, 多选模式的各分支共用同一个变量
+    fn bind_pattern_variable(&mut self, name: &str) -> CompileResult {
+        let declared = self
+            .symbol_table
+            .borrow()
+            .is_declared_in_current_block(name);
+        let symbol = if declared {
+            self.symbol_table.borrow_mut().resolve(name).unwrap()
+        } else {
+            self.declare_symbol(name)?
+        };
+        self.store_symbol(symbol);
+        Ok(())
+    }
+    /// 栈顶的值存入当前块中的临时变量, 临时变量名不是合法的标识符, 不会与用户变量冲突
+    fn define_temp(&mut self) -> Rc<Symbol> {
+        let symbol = self.symbol_table.borrow_mut().define("@temp");
+        self.store_symbol(symbol.clone());
+        symbol
+    }
+    /// 解构栈顶的值, 依次声明模式中的变量
+    fn compile_let_pattern(&mut self, pattern: &LetPattern) -> CompileResult {
+        match pattern {
+            LetPattern::Name(name) => {
+                let symbol = self.declare_symbol(name)?;
+                self.store_symbol(symbol);
+            }
+            LetPattern::Array(items, rest) => {
+                let value = self.define_temp();
+                self.load(&value);
+                self.emit(Opcode::DestructArray, vec![LetPattern::required_len(items)]);
+                for (i, (item, default)) in items.iter().enumerate() {
+                    self.load(&value);
+                    self.add_constant_one_and_emit(Object::Integer(i as i64));
+                    self.emit(Opcode::Index, vec![]);
+                    self.compile_pattern_default(default)?;
+                    self.compile_let_pattern(item)?;
+                }
+                if let Some(rest) = rest {
+                    // value[n..]
+                    self.load(&value);
+                    self.add_constant_one_and_emit(Object::Integer(items.len() as i64));
+                    self.emit(Opcode::Null, vec![]);
+                    self.emit(Opcode::Range, vec![0]);
+                    self.emit(Opcode::Index, vec![]);
+                    self.compile_let_pattern(&LetPattern::Name(rest.clone()))?;
+                }
+            }
+            LetPattern::Hash(pairs) => {
+                let value = self.define_temp();
+                self.load(&value);
+                let required = pairs.iter().filter(|(_, _, default)| default.is_none());
+                for (key, _, _) in required.clone() {
+                    self.add_constant_one_and_emit(Object::String(key.clone()));
+                }
+                self.emit(Opcode::DestructHash, vec![required.count()]);
+                for (key, item, default) in pairs {
+                    self.load(&value);
+                    let name = self.add_constant(Object::String(key.clone()));
+                    self.emit(Opcode::DestructField, vec![name]);
+                    self.compile_pattern_default(default)?;
+                    self.compile_let_pattern(item)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// 栈顶的值为null时替换为默认值
+    fn compile_pattern_default(&mut self, default: &Option<Expression>) -> CompileResult {
+        if let Some(default) = default {
+            let pos = self.emit(Opcode::JumpIfNotNull, vec![9999]);
+            self.compile_expression(default)?;
+            let after_default = self.cur_instruction_len();
+            self.change_operand(pos, after_default);
+        }
+        Ok(())
+    }
+    /// 编译函数, 结构体方法(struct_fields不为None)的第一个参数是实例本身this
     fn compile_function_expression(
         &mut self,
         fun_name: Option<String>,
-        args: &[String],
+        args: &[Parameter],
         blocks: &BlockStatement,
+        struct_fields: Option<&[String]>,
     ) -> CompileResult {
         self.enter_scope();
-        //当前函数
-        self.symbol_table.borrow_mut().define_self(fun_name.clone());
+        if let Some(fields) = struct_fields {
+            self.symbol_table.borrow_mut().define("this");
+            self.scopes[self.scope_index - 1].struct_fields = Some(fields.to_vec());
+        } else if let Some(name) = &fun_name {
+            //当前函数
+            self.symbol_table.borrow_mut().define_self(name);
+        }
         //参数列表
-        for arg in args {
-            self.symbol_table.borrow_mut().define(arg);
+        let params = args
+            .iter()
+            .map(|arg| self.symbol_table.borrow_mut().define(&arg.name))
+            .collect::<Vec<_>>();
+        //缺省的参数为null, 在函数开头换成默认值
+        for (arg, symbol) in args.iter().zip(params) {
+            if arg.default.is_some() {
+                self.load(&symbol);
+                self.compile_pattern_default(&arg.default)?;
+                self.store_symbol(symbol);
+            }
         }
-        //编译语句块
-        self.compile_block_statement(blocks)?;
+        //编译语句块, 函数体与参数同属一个块
+        self.compile_statements(&blocks.statements)?;
         //如果最后一条指令是pop，说明有返回值，改为return_value
         if self.last_instruction_is(Opcode::Pop) {
             self.remove_last_instruction()?;
@@ -408,16 +1200,19 @@ impl Compiler {
         let num_locals = self.symbol_table_len();
         //编译后的函数常量
         let compiled_fn = self.leave_scope();
-        //自由变量
+        //自由变量, 捕获的是变量本身而非当前值
         for name in frees {
-            self.load_symbol(name)?; // emit free
+            self.capture_symbol(name)?;
         }
-        let constant = Object::CompiledFunction(CompiledFunction::with_name(
+        let mut compiled_fn = CompiledFunction::with_name(
             fun_name,
             Rc::new(compiled_fn),
             num_locals,
-            args.len(),
-        ));
+            args.len() + struct_fields.map_or(0, |_| 1),
+        );
+        compiled_fn.num_defaults = args.iter().filter(|arg| arg.default.is_some()).count();
+        compiled_fn.rest = args.last().is_some_and(|arg| arg.rest);
+        let constant = Object::CompiledFunction(Rc::new(compiled_fn));
         let const_index = self.add_constant(constant);
         //函数常量索引
         self.emit(Opcode::Closure, vec![const_index, free_count]);
@@ -431,6 +1226,9 @@ impl Compiler {
             }
             UnaryOperator::Neg => {
                 self.emit(Opcode::Neg, vec![]);
+            }
+            UnaryOperator::BitNot => {
+                self.emit(Opcode::BitNot, vec![]);
             } // _ => return Err(CompileError::UnknownUnOperator(op.clone())),
         }
         Ok(())
@@ -450,12 +1248,39 @@ impl Compiler {
             BinaryOperator::Div => {
                 self.emit(Opcode::Div, vec![]);
             }
+            BinaryOperator::Mod => {
+                self.emit(Opcode::Mod, vec![]);
+            }
+            BinaryOperator::Pow => {
+                self.emit(Opcode::Pow, vec![]);
+            }
+            BinaryOperator::BitAnd => {
+                self.emit(Opcode::BitAnd, vec![]);
+            }
+            BinaryOperator::BitOr => {
+                self.emit(Opcode::BitOr, vec![]);
+            }
+            BinaryOperator::BitXor => {
+                self.emit(Opcode::BitXor, vec![]);
+            }
+            BinaryOperator::Shl => {
+                self.emit(Opcode::Shl, vec![]);
+            }
+            BinaryOperator::Shr => {
+                self.emit(Opcode::Shr, vec![]);
+            }
             BinaryOperator::Gt => {
                 self.emit(Opcode::GreaterThan, vec![]);
             }
+            BinaryOperator::Ge => {
+                self.emit(Opcode::GreaterEq, vec![]);
+            }
             BinaryOperator::Lt => {
                 self.emit(Opcode::LessThan, vec![]);
             }
+            BinaryOperator::Le => {
+                self.emit(Opcode::LessEq, vec![]);
+            }
             BinaryOperator::Eq => {
                 self.emit(Opcode::Equal, vec![]);
             }
@@ -466,25 +1291,253 @@ impl Compiler {
         }
         Ok(())
     }
+    /// 编译赋值表达式
+    ///
+    /// a += b: a; b; Add; Dup; SetX a (栈上留下所赋的值作为表达式的值)
+    ///
+    /// obj[i][j] = b: obj; i; j; b; SetIndex 2
+    ///
+    /// obj[i] += b: obj; i; IndexPeek 1; b; Add; SetIndex 1 (索引只求值一次)
+    fn compile_assign_expression(
+        &mut self,
+        op: &BinaryOperator,
+        left: &Expression,
+        right: &Expression,
+    ) -> CompileResult {
+        let compound = op.compound_operator();
+        match (left, &compound) {
+            (Expression::Identifier(name), _) => {
+                if let Some(compound) = &compound {
+                    self.load_symbol(name)?;
+                    self.compile_expression(right)?;
+                    self.compile_binary_expression(compound)?;
+                } else {
+                    self.compile_expression(right)?;
+                }
+                self.emit(Opcode::Dup, vec![]);
+                self.compile_assign(name)?;
+            }
+            (Expression::Index(_, _), _) => {
+                let depth = self.compile_index_path(left)?;
+                if let Some(compound) = &compound {
+                    //保留容器和索引, 取出旧值
+                    self.emit(Opcode::IndexPeek, vec![depth]);
+                    self.compile_expression(right)?;
+                    self.compile_binary_expression(compound)?;
+                } else {
+                    self.compile_expression(right)?;
+                }
+                self.emit(Opcode::SetIndex, vec![depth]);
+            }
+            (Expression::Field(container, field), _) => {
+                if let Some(slot) = self.resolve_slot(container, field) {
+                    //this.x += b: this; IndexPeek 0; GetSlot x; b; Add; SetSlot x
+                    self.compile_expression(container)?;
+                    if let Some(compound) = &compound {
+                        self.emit(Opcode::IndexPeek, vec![0]);
+                        self.emit(Opcode::GetSlot, vec![slot]);
+                        self.compile_expression(right)?;
+                        self.compile_binary_expression(compound)?;
+                    } else {
+                        self.compile_expression(right)?;
+                    }
+                    self.emit(Opcode::SetSlot, vec![slot]);
+                    return Ok(());
+                }
+                let depth = self.compile_index_path(container)?;
+                let name = self.add_constant(Object::String(field.clone()));
+                if let Some(compound) = &compound {
+                    //保留容器和索引, 取出旧值
+                    self.emit(Opcode::IndexPeek, vec![depth]);
+                    self.emit(Opcode::GetField, vec![name]);
+                    self.compile_expression(right)?;
+                    self.compile_binary_expression(compound)?;
+                } else {
+                    self.compile_expression(right)?;
+                }
+                self.emit(Opcode::SetField, vec![name, depth]);
+            }
+            _ => {
+                return Err(CompileError::UnsupportedBinOperation(
+                    op.clone(),
+                    Box::new(left.clone()),
+                    Box::new(right.clone()),
+                ));
+            }
+        }
+        Ok(())
+    }
+    /// 编译索引路径 a[i].j: 依次压入最外层容器和各层索引(成员名作为字符串索引), 返回索引层数
+    fn compile_index_path(&mut self, expr: &Expression) -> CompileResult<usize> {
+        match expr {
+            Expression::Index(container, index) => {
+                let depth = self.compile_index_path(container)?;
+                self.compile_expression(index)?;
+                Ok(depth + 1)
+            }
+            Expression::Field(container, field) => {
+                let depth = self.compile_index_path(container)?;
+                self.add_constant_one_and_emit(Object::String(field.clone()));
+                Ok(depth + 1)
+            }
+            _ => {
+                self.compile_expression(expr)?;
+                Ok(0)
+            }
+        }
+    }
+    /// 编译时可以确定为结构体实例的obj.field, 返回字段的槽位
+    ///
+    /// 实例为方法内的this, 结构体构造调用P(..), 或const p = P(..)声明的变量
+    fn resolve_slot(&self, obj: &Expression, field: &str) -> Option<usize> {
+        let fields = match obj {
+            Expression::Identifier(name) => {
+                let symbol = self.symbol_table.borrow_mut().resolve(name)?;
+                match &symbol.binding {
+                    Binding::Instance(fields) => fields.clone(),
+                    _ if self.is_receiver(&symbol) => {
+                        Rc::new(self.scopes[self.scope_index - 1].struct_fields.clone()?)
+                    }
+                    _ => return None,
+                }
+            }
+            _ => self.resolve_struct_call(obj)?,
+        };
+        fields.iter().position(|f| f == field)
+    }
+    /// 表达式为结构体构造调用P(..)时, 返回该结构体的字段
+    fn resolve_struct_call(&self, expr: &Expression) -> Option<Rc<Vec<String>>> {
+        let name = match expr {
+            Expression::Call(fun, _) => match fun.as_ref() {
+                Expression::Identifier(name) => name,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        match &self.symbol_table.borrow_mut().resolve(name)?.binding {
+            Binding::Struct(fields) => Some(fields.clone()),
+            _ => None,
+        }
+    }
+    /// 是否为结构体方法的接收者this(未被块内的同名变量遮蔽)
+    fn is_receiver(&self, symbol: &Symbol) -> bool {
+        self.scopes[self.scope_index - 1].struct_fields.is_some()
+            && symbol.name == "this"
+            && symbol.scope == SymbolScope::Local
+            && symbol.index == 0
+    }
+    /// 编译逻辑运算(短路求值)
+    ///
+    /// a && b: 任一操作数为假即跳转到false, 否则为true
+    ///
+    /// a || b: 任一操作数为真即跳转到true, 否则为false
+    fn compile_logical_expression(
+        &mut self,
+        op: &BinaryOperator,
+        left: &Expression,
+        right: &Expression,
+    ) -> CompileResult {
+        let is_and = op == &BinaryOperator::And;
+        let mut jump_positions = vec![];
+        for operand in [left, right] {
+            self.compile_expression(operand)?;
+            //操作数末尾可能是if分支中的比较指令, 不能与跳转合并
+            let pos = if is_and {
+                self.emit(Opcode::JumpIfNotTruthy, vec![9999])
+            } else {
+                self.emit(Opcode::JumpIfTruthy, vec![9999])
+            };
+            jump_positions.push(pos);
+        }
+        //两个操作数都未跳转
+        self.emit(if is_and { Opcode::True } else { Opcode::False }, vec![]);
+        let jump_always_pos = self.emit(Opcode::JumpAlways, vec![9999]);
+        //短路跳转的位置
+        let short_circuit_pos = self.cur_instruction_len();
+        for pos in jump_positions {
+            self.change_operand(pos, short_circuit_pos);
+        }
+        self.emit(if is_and { Opcode::False } else { Opcode::True }, vec![]);
+        let final_pos = self.cur_instruction_len();
+        self.change_operand(jump_always_pos, final_pos);
+        Ok(())
+    }
+    /// 给已声明的变量赋值, 与声明(store_symbol)不同, 局部变量和自由变量会写入闭包共享的Cell
     fn compile_assign(&mut self, name: &str) -> CompileResult<()> {
         let option = self.symbol_table.borrow_mut().resolve(name);
         let symbol = match option {
             None => return Err(CompileError::UndefinedIdentifier(name.to_string())),
             Some(symbol) => symbol,
         };
-        self.store_symbol(symbol);
+        if symbol.binding != Binding::Variable {
+            return Err(CompileError::AssignToConstant(name.to_string()));
+        }
+        self.assign_symbol(symbol)
+    }
+    /// 给变量赋值, 不检查能否重新赋值
+    fn assign_symbol(&mut self, symbol: Rc<Symbol>) -> CompileResult {
+        match symbol.scope {
+            SymbolScope::Global => self.store_symbol(symbol),
+            SymbolScope::Local if self.is_receiver(&symbol) => {
+                return Err(CompileError::CustomErrMsg(format!(
+                    "can't assign to {}",
+                    symbol.name
+                )));
+            }
+            SymbolScope::Local => {
+                self.emit(Opcode::AssignLocal, vec![symbol.index]);
+            }
+            SymbolScope::Free => {
+                self.emit(Opcode::SetFree, vec![symbol.index]);
+            }
+            SymbolScope::Builtin | SymbolScope::Function => {
+                return Err(CompileError::CustomErrMsg(format!(
+                    "can't assign to {}",
+                    symbol.name
+                )));
+            }
+        }
         Ok(())
     }
-    fn get_jump_if_pos(&mut self) -> CompileResult<usize> {
-        let jump_if_pos = if self.last_instruction_is(Opcode::LessThan) {
-            //如果是小于比较运算
-            self.remove_last_instruction()?; //移除小于指令
-            self.emit(Opcode::JumpIfNotLess, vec![9999]) //替换指令
-        } else {
-            //不变
-            self.emit(Opcode::JumpIfNotTruthy, vec![9999])
+    /// 创建闭包时捕获自由变量(生成一条压入该变量Cell的指令)
+    fn capture_symbol(&mut self, name: &str) -> CompileResult {
+        let option = self.symbol_table.borrow_mut().resolve(name);
+        let symbol = match option {
+            None => return Err(CompileError::UndefinedIdentifier(name.to_string())),
+            Some(symbol) => symbol,
+        };
+        match symbol.scope {
+            SymbolScope::Local => {
+                self.emit(Opcode::CaptureLocal, vec![symbol.index]);
+            }
+            SymbolScope::Free => {
+                self.emit(Opcode::CaptureFree, vec![symbol.index]);
+            }
+            _ => self.load_symbol(name)?,
+        }
+        Ok(())
+    }
+    /// 编译条件并生成条件不成立时的跳转指令, 返回待回填的跳转指令位置
+    ///
+    /// 条件本身是 < 或 <= 比较时, 比较与跳转合并为一条指令
+    fn compile_condition(&mut self, cond: &Expression) -> CompileResult<usize> {
+        let jump_op = match cond {
+            Expression::Binary(BinaryOperator::Lt, left, right) => {
+                self.compile_expression(left)?;
+                self.compile_expression(right)?;
+                Opcode::JumpIfNotLess
+            }
+            Expression::Binary(BinaryOperator::Le, left, right) => {
+                self.compile_expression(left)?;
+                self.compile_expression(right)?;
+                Opcode::JumpIfNotLessEq
+            }
+            _ => {
+                self.compile_expression(cond)?;
+                Opcode::JumpIfNotTruthy
+            }
         };
-        Ok(jump_if_pos)
+        Ok(self.emit(jump_op, vec![9999]))
     }
     /// 常量池添加常量，返回常量索引
     fn add_constant(&mut self, constant: Object) -> usize {
@@ -493,8 +1546,12 @@ impl Compiler {
     }
     //在一字节范围的常量
     fn add_constant_one_and_emit(&mut self, constant: Object) {
-        self.constants.push(Rc::new(constant));
-        let i = self.constants.len();
+        let index = self.add_constant(constant);
+        self.emit_constant(index);
+    }
+    /// 生成压入常量的指令
+    fn emit_constant(&mut self, index: usize) {
+        let i = index + 1;
         if i <= 0xF {
             let mut o = vec![];
             let op = match i {
@@ -512,7 +1569,6 @@ impl Compiler {
         } else {
             self.emit(Opcode::Constant, vec![i - 1]);
         }
-        // self.constants.len() - 1
     }
     /// 指令表添加指令，返回指令开始位置
     fn add_instruction(&mut self, instruction: &mut Instructions) -> usize {
@@ -568,6 +1624,15 @@ impl Compiler {
             None => return Err(CompileError::UndefinedIdentifier(name.to_string())),
             Some(symbol) => symbol,
         };
+        self.load(&symbol);
+        Ok(())
+    }
+    /// 生成一条获取该符号数据的指令
+    fn load(&mut self, symbol: &Symbol) {
+        if let Binding::Constant(Some(index)) = symbol.binding {
+            self.emit_constant(index);
+            return;
+        }
         let op = match symbol.scope {
             SymbolScope::Global => {
                 let i = symbol.index;
@@ -584,7 +1649,7 @@ impl Compiler {
                     }
                 };
                 self.emit(op, o);
-                return Ok(());
+                return;
             }
             SymbolScope::Local => {
                 let i = symbol.index;
@@ -601,17 +1666,16 @@ impl Compiler {
                     }
                 };
                 self.emit(op, o);
-                return Ok(());
+                return;
             }
             SymbolScope::Builtin => Opcode::GetBuiltin,
             SymbolScope::Free => Opcode::GetFree,
             SymbolScope::Function => {
                 self.emit(Opcode::CurrentClosure, vec![]);
-                return Ok(());
+                return;
             }
         };
         self.emit(op, vec![symbol.index]);
-        Ok(())
     }
     //
     fn store_symbol(&mut self, symbol: Rc<Symbol>) {
@@ -625,7 +1689,7 @@ impl Compiler {
                     3 => Opcode::SetGlobal3,
                     4 => Opcode::SetGlobal4,
                     _ => {
-                        self.emit(Opcode::SetLocal, vec![i]);
+                        self.emit(Opcode::SetGlobal, vec![i]);
                         return;
                     }
                 };
@@ -656,10 +1720,11 @@ impl Compiler {
 }
 
 impl ByteCode {
-    pub fn new(instructions: Instructions, constants: Constants) -> Self {
+    pub fn new(instructions: Instructions, constants: Constants, num_locals: usize) -> Self {
         Self {
             instructions,
             constants,
+            num_locals,
         }
     }
 }
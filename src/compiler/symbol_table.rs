@@ -14,11 +14,26 @@ pub enum SymbolScope {
     Function,
 }
 
+/// 变量能否重新赋值
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Binding {
+    Variable,
+    /// const声明的变量, 值为字面量时记录其常量索引, 读取时直接压入该常量
+    Constant(Option<usize>),
+    /// 结构体名, 不能重新赋值, 记录字段以便按槽位访问构造出的实例
+    Struct(Rc<Vec<String>>),
+    /// 值为结构体实例的const变量(const p = P(..)), 记录实例的字段
+    Instance(Rc<Vec<String>>),
+    /// 导入的模块, 记录导出的变量, 模块名.成员直接读取该变量
+    Module(Rc<Vec<Rc<Symbol>>>),
+}
+
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct Symbol {
     pub name: String,
     pub scope: SymbolScope,
     pub index: usize,
+    pub binding: Binding,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -26,7 +41,11 @@ pub struct SymbolTable {
     pub outer: Option<Rc<RefCell<SymbolTable>>>,
     pub store: HashMap<String, Rc<Symbol>>,
     pub num_definitions: usize,
+    // 顶层语句块中的变量作为主帧的局部变量, 与全局变量分开计数
+    pub num_main_locals: usize,
     pub free_symbols: Vec<Rc<Symbol>>,
+    /// 当前函数内嵌套的语句块, 块内变量仍分配在当前帧, 离开块后不可见
+    pub blocks: Vec<HashMap<String, Rc<Symbol>>>,
 }
 
 impl SymbolTable {
@@ -35,33 +54,84 @@ impl SymbolTable {
             outer: None,
             store: Default::default(),
             num_definitions: 0,
+            num_main_locals: 0,
             free_symbols: vec![],
+            blocks: vec![],
         }
     }
     pub fn define(&mut self, name: &str) -> Rc<Symbol> {
-        let scope = if self.outer.is_none() {
-            SymbolScope::Global
+        let (scope, index) = if self.outer.is_some() {
+            self.num_definitions += 1;
+            (SymbolScope::Local, self.num_definitions - 1)
+        } else if self.blocks.is_empty() {
+            self.num_definitions += 1;
+            (SymbolScope::Global, self.num_definitions - 1)
         } else {
-            SymbolScope::Local
+            self.num_main_locals += 1;
+            (SymbolScope::Local, self.num_main_locals - 1)
         };
         let symbol = Symbol {
             name: String::from(name),
             scope,
-            index: self.num_definitions,
+            index,
+            binding: Binding::Variable,
         };
         let symbol = Rc::new(symbol);
-        self.store.insert(name.to_string(), symbol.clone());
-        self.num_definitions += 1;
+        //块内定义只加入最内层块, 槽位不复用
+        let store = self.blocks.last_mut().unwrap_or(&mut self.store);
+        store.insert(name.to_string(), symbol.clone());
         symbol
     }
+    /// 定义不能重新赋值的变量
+    pub fn define_const(&mut self, name: &str, binding: Binding) -> Rc<Symbol> {
+        let symbol = Rc::new(Symbol {
+            binding,
+            ..Symbol::clone(&self.define(name))
+        });
+        let store = self.blocks.last_mut().unwrap_or(&mut self.store);
+        store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+    /// 导入的成员作为原变量的别名, 共用槽位, 不能重新赋值
+    pub fn define_alias(&mut self, name: &str, original: &Symbol) -> Rc<Symbol> {
+        let binding = match original.binding {
+            Binding::Variable => Binding::Constant(None),
+            ref binding => binding.clone(),
+        };
+        let symbol = Rc::new(Symbol {
+            name: name.to_string(),
+            binding,
+            ..original.clone()
+        });
+        let store = self.blocks.last_mut().unwrap_or(&mut self.store);
+        store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+    /// 进入语句块
+    pub fn enter_block(&mut self) {
+        self.blocks.push(HashMap::new());
+    }
+    /// 离开语句块
+    pub fn leave_block(&mut self) {
+        self.blocks.pop();
+    }
+    /// 当前块(或函数顶层)是否已声明该变量, 外层同名变量可以被遮蔽
+    pub fn is_declared_in_current_block(&self, name: &str) -> bool {
+        let store = self.blocks.last().unwrap_or(&self.store);
+        store
+            .get(name)
+            .is_some_and(|s| matches!(s.scope, SymbolScope::Global | SymbolScope::Local))
+    }
     fn define_free(&mut self, original: Rc<Symbol>) -> Rc<Symbol> {
         let name = original.name.clone();
+        let original_binding = original.binding.clone();
         //加入自由变量表
         self.free_symbols.push(original);
         let symbol = Symbol {
             name: name.clone(),
             scope: SymbolScope::Free,
             index: self.free_symbols.len() - 1,
+            binding: original_binding,
         };
         let symbol = Rc::new(symbol);
         //作用域改为free，加入符号表
@@ -74,24 +144,29 @@ impl SymbolTable {
             name: name.clone(),
             scope: SymbolScope::Builtin,
             index,
+            binding: Binding::Variable,
         };
         let symbol = Rc::new(symbol);
         self.store.insert(name, symbol);
     }
-    pub fn define_self(&mut self, fun_name: Option<String>){
-        let name = fun_name.unwrap_or_else(|| "this".to_string());
+    /// 函数名指向函数自身, 用于递归调用
+    pub fn define_self(&mut self, name: &str) {
         let self_symbol = Rc::new(Symbol {
-            name: name.clone(),
+            name: name.to_string(),
             scope: SymbolScope::Function,
             index: 0,
+            binding: Binding::Variable,
         });
-        self.store.insert(name, self_symbol);
+        self.store.insert(name.to_string(), self_symbol);
     }
-    /// 先从当前符号表查找
+    /// 先从当前符号表由内向外的语句块查找
     /// 没有则解析父符号表
     pub fn resolve(&mut self, name: &str) -> Option<Rc<Symbol>> {
-        self.store
-            .get(name)
+        self.blocks
+            .iter()
+            .rev()
+            .find_map(|block| block.get(name))
+            .or_else(|| self.store.get(name))
             .cloned()
             .or_else(|| self.resolve_outer(name))
     }
@@ -102,7 +177,11 @@ impl SymbolTable {
             .map(|s| self.resolve_free(s))
     }
     fn resolve_free(&mut self, s: Rc<Symbol>) -> Rc<Symbol> {
-        if s.scope == SymbolScope::Global || s.scope == SymbolScope::Builtin {
+        //内联的常量不需要捕获
+        if s.scope == SymbolScope::Global
+            || s.scope == SymbolScope::Builtin
+            || matches!(s.binding, Binding::Constant(Some(_)))
+        {
             s
         } else {
             self.define_free(s)
@@ -118,7 +197,9 @@ impl SymbolTable {
             outer,
             store,
             num_definitions,
+            num_main_locals: 0,
             free_symbols,
+            blocks: vec![],
         }))
     }
 }
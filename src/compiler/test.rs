@@ -1,4 +1,5 @@
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
     use std::collections::HashMap;
     use std::rc::Rc;
@@ -6,10 +7,11 @@ mod tests {
     use crate::compiler::code::{
         self, _make, _make_closure, _make_const, _make_noop, make, print_instructions, Opcode,
     };
-    use crate::compiler::symbol_table::{Symbol, SymbolScope, SymbolTable};
-    use crate::compiler::{Compiler, Instructions};
+    use crate::compiler::symbol_table::{Binding, Symbol, SymbolScope, SymbolTable};
+    use crate::compiler::{CompileError, Compiler, Instructions};
     use crate::create_rc_ref_cell;
-    use crate::object::{CompiledFunction, Object};
+    use crate::module::ModuleError;
+    use crate::object::{CompiledFunction, Object, StructType};
     use crate::Opcode::*;
     use crate::parser::ast::Program;
 
@@ -40,29 +42,764 @@ mod tests {
             vec![
                 //init
                 _make_const(0),// 0
-                _make_noop(Opcode::SetGlobal0),// 1
+                _make_noop(Opcode::SetLocal0),// 1
                 //cond
-                _make_noop(Opcode::GetGlobal0),// 2
+                _make_noop(Opcode::GetLocal0),// 2
                 _make_const(1),// 3
-                _make(Opcode::JumpIfNotLess, 17+3),// 4
+                _make(Opcode::JumpIfNotLess, 23),// 4
                 //loop blocks
                 _make(Opcode::GetBuiltin, 5),//7
-                _make_noop(Opcode::GetGlobal0),//9
+                _make_noop(Opcode::GetLocal0),//9
                 _make(Opcode::Call, 1),//10
                 _make_noop(Opcode::Pop),//12
                 //after
-                _make_noop(Opcode::GetGlobal0),// 13
+                _make_noop(Opcode::GetLocal0),// 13
                 _make_const(2),//14
                 _make_noop(Opcode::Add),//15
-                _make_noop(Opcode::SetGlobal0),//16
+                _make_noop(Opcode::Dup),//16
+                _make(Opcode::AssignLocal, 0),//17
+                _make_noop(Opcode::Pop),//19
                 //always jump to start
-                _make(Opcode::JumpAlways, 2),//17
-                _make_noop(Opcode::Null)
+                _make(Opcode::JumpAlways, 2),//20
+                _make_noop(Opcode::Null),//23
+                _make_noop(Opcode::Pop),//24
             ],
         )];
         run_compile_test(inputs);
     }
     #[test]
+    fn less_or_greater_eq() {
+        let inputs = vec![
+            (
+                "1 >= 2; 1 <= 2",
+                vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                    Object::Integer(1),
+                    Object::Integer(2),
+                ],
+                vec![
+                    _make_const(0),
+                    _make_const(1),
+                    _make_noop(Opcode::GreaterEq),
+                    _make_noop(Opcode::Pop),
+                    _make_const(2),
+                    _make_const(3),
+                    _make_noop(Opcode::LessEq),
+                    _make_noop(Opcode::Pop),
+                ],
+            ),
+            (
+                "let i = 0; while (i <= 3) { i += 1 }",
+                vec![Object::Integer(0), Object::Integer(3), Object::Integer(1)],
+                vec![
+                    _make_const(0),                     // 0
+                    _make_noop(Opcode::SetGlobal0),     // 1
+                    _make_noop(Opcode::GetGlobal0),     // 2
+                    _make_const(1),                     // 3
+                    _make(Opcode::JumpIfNotLessEq, 16), // 4
+                    _make_noop(Opcode::GetGlobal0),     // 7
+                    _make_const(2),                     // 8
+                    _make_noop(Opcode::Add),            // 9
+                    _make_noop(Opcode::Dup),            // 10
+                    _make_noop(Opcode::SetGlobal0),     // 11
+                    _make_noop(Opcode::Pop),            // 12
+                    _make(Opcode::JumpAlways, 2),       // 13
+                    _make_noop(Opcode::Null),           // 16
+                    _make_noop(Opcode::Pop),            // 17
+                ],
+            ),
+        ];
+        run_compile_test(inputs);
+    }
+    #[test]
+    fn while_statement() {
+        let inputs = vec![(
+            r"
+            while (true) {
+                break
+            }
+            ",
+            vec![],
+            vec![
+                _make_noop(Opcode::True),               // 0
+                _make(Opcode::JumpIfNotTruthy, 10),     // 1
+                _make(Opcode::JumpAlways, 10),          // 4 break
+                _make(Opcode::JumpAlways, 0),           // 7
+                _make_noop(Opcode::Null),               // 10
+                _make_noop(Opcode::Pop),                // 11
+            ],
+        )];
+        run_compile_test(inputs);
+    }
+    #[test]
+    fn for_in_statement() {
+        let inputs = vec![
+            (
+                "for x in [1] { break }",
+                vec![Object::Integer(1)],
+                vec![
+                    _make_const(0),                      // 0
+                    _make(Opcode::Array, 1),             // 1
+                    _make_noop(Opcode::IterNew),         // 4
+                    make(Opcode::IterNext, vec![16, 1]), // 5
+                    _make_noop(Opcode::SetLocal0),       // 9
+                    _make(Opcode::JumpAlways, 16),       // 10 break
+                    _make(Opcode::JumpAlways, 5),        // 13
+                    _make_noop(Opcode::Pop),             // 16 pop iterator
+                    _make_noop(Opcode::Null),            // 17
+                    _make_noop(Opcode::Pop),             // 18
+                ],
+            ),
+            (
+                "outer: for (k, v) in [1] { for y in [2] { break outer } }",
+                vec![Object::Integer(1), Object::Integer(2)],
+                vec![
+                    _make_const(0),                      // 0
+                    _make(Opcode::Array, 1),             // 1
+                    _make_noop(Opcode::IterNew),         // 4
+                    make(Opcode::IterNext, vec![34, 2]), // 5
+                    _make_noop(Opcode::SetLocal1),       // 9 v
+                    _make_noop(Opcode::SetLocal0),       // 10 k
+                    _make_const(1),                      // 11
+                    _make(Opcode::Array, 1),             // 12
+                    _make_noop(Opcode::IterNew),         // 15
+                    make(Opcode::IterNext, vec![28, 1]), // 16
+                    _make_noop(Opcode::SetLocal2),       // 20
+                    _make_noop(Opcode::Pop),             // 21 pop inner iterator
+                    _make(Opcode::JumpAlways, 34),       // 22 break outer
+                    _make(Opcode::JumpAlways, 16),       // 25
+                    _make_noop(Opcode::Pop),             // 28
+                    _make_noop(Opcode::Null),            // 29
+                    _make_noop(Opcode::Pop),             // 30
+                    _make(Opcode::JumpAlways, 5),        // 31
+                    _make_noop(Opcode::Pop),             // 34
+                    _make_noop(Opcode::Null),            // 35
+                    _make_noop(Opcode::Pop),             // 36
+                ],
+            ),
+        ];
+        run_compile_test(inputs);
+    }
+    #[test]
+    fn nested_assign() {
+        let inputs = vec![(
+            "let g = [[1]]; g[0][0] = 2",
+            vec![
+                Object::Integer(1),
+                Object::Integer(0),
+                Object::Integer(0),
+                Object::Integer(2),
+            ],
+            vec![
+                _make_const(0),
+                _make(Opcode::Array, 1),
+                _make(Opcode::Array, 1),
+                _make_noop(Opcode::SetGlobal0),
+                _make_noop(Opcode::GetGlobal0),
+                _make_const(1),
+                _make_const(2),
+                _make_const(3),
+                _make(Opcode::SetIndex, 2),
+                _make_noop(Opcode::Pop),
+            ],
+        )];
+        run_compile_test(inputs);
+    }
+    #[test]
+    fn field_and_method() {
+        let inputs = vec![
+            (
+                "let p = {}; p.a; p.a = 1",
+                vec![
+                    Object::String("a".to_string()),
+                    Object::String("a".to_string()),
+                    Object::Integer(1),
+                ],
+                vec![
+                    _make(Opcode::Hash, 0),
+                    _make_noop(Opcode::SetGlobal0),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make(Opcode::GetField, 0),
+                    _make_noop(Opcode::Pop),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make_const(2),
+                    make(Opcode::SetField, vec![1, 0]),
+                    _make_noop(Opcode::Pop),
+                ],
+            ),
+            (
+                "let p = {}; p.q.a -= 1",
+                vec![
+                    Object::String("q".to_string()),
+                    Object::String("a".to_string()),
+                    Object::Integer(1),
+                ],
+                vec![
+                    _make(Opcode::Hash, 0),
+                    _make_noop(Opcode::SetGlobal0),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make_const(0),
+                    _make(Opcode::IndexPeek, 1),
+                    _make(Opcode::GetField, 1),
+                    _make_const(2),
+                    _make_noop(Opcode::Sub),
+                    make(Opcode::SetField, vec![1, 1]),
+                    _make_noop(Opcode::Pop),
+                ],
+            ),
+            (
+                "[].push(1)",
+                vec![Object::Integer(1), Object::String("push".to_string())],
+                vec![
+                    _make(Opcode::Array, 0),
+                    _make_const(0),
+                    make(Opcode::Invoke, vec![1, 1]),
+                    _make_noop(Opcode::Pop),
+                ],
+            ),
+        ];
+        run_compile_test(inputs);
+    }
+    #[test]
+    fn match_expression() {
+        let inputs = vec![(
+            "match 1 { 1 => 2, _ => 3 }",
+            vec![
+                Object::Integer(1),
+                Object::Integer(1),
+                Object::Integer(2),
+                Object::Integer(3),
+            ],
+            vec![
+                _make_const(0),
+                _make_noop(Opcode::SetLocal0),
+                _make_noop(Opcode::GetLocal0),
+                _make_const(1),
+                _make_noop(Opcode::Equal),
+                _make(Opcode::JumpIfNotTruthy, 12),
+                _make_const(2),
+                _make(Opcode::JumpAlways, 17),
+                _make_const(3),
+                _make(Opcode::JumpAlways, 17),
+                _make_noop(Opcode::Null),
+                _make_noop(Opcode::Pop),
+            ],
+        )];
+        run_compile_test(inputs);
+    }
+    #[test]
+    fn try_statement() {
+        let inputs = vec![(
+            "try { 1 } catch e { e } finally { 2 }",
+            vec![Object::Integer(1), Object::Integer(2), Object::Integer(2)],
+            vec![
+                _make(Opcode::SetupTry, 9),
+                _make_const(0),
+                _make_noop(Opcode::Pop),
+                _make_noop(Opcode::PopTry),
+                _make(Opcode::JumpAlways, 24),
+                _make(Opcode::SetupTry, 19),
+                _make_noop(Opcode::SetLocal0),
+                _make_noop(Opcode::GetLocal0),
+                _make_noop(Opcode::Pop),
+                _make_noop(Opcode::PopTry),
+                _make(Opcode::JumpAlways, 24),
+                _make_noop(Opcode::SetLocal1),
+                _make_const(1),
+                _make_noop(Opcode::Pop),
+                _make_noop(Opcode::GetLocal1),
+                _make_noop(Opcode::Throw),
+                _make_const(2),
+                _make_noop(Opcode::Pop),
+                _make_noop(Opcode::Null),
+                _make_noop(Opcode::Pop),
+            ],
+        )];
+        run_compile_test(inputs);
+    }
+    #[test]
+    fn destructure_statement() {
+        let inputs = vec![
+            (
+                "let [a, b = 2] = [1]",
+                vec![
+                    Object::Integer(1),
+                    Object::Integer(0),
+                    Object::Integer(1),
+                    Object::Integer(2),
+                ],
+                vec![
+                    _make_const(0),
+                    _make(Opcode::Array, 1),
+                    _make_noop(Opcode::SetGlobal0),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make(Opcode::DestructArray, 1),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make_const(1),
+                    _make_noop(Opcode::Index),
+                    _make_noop(Opcode::SetGlobal1),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make_const(2),
+                    _make_noop(Opcode::Index),
+                    _make(Opcode::JumpIfNotNull, 19),
+                    _make_const(3),
+                    _make_noop(Opcode::SetGlobal2),
+                ],
+            ),
+            (
+                "let {a, b = 2} = {}",
+                vec![
+                    Object::String("a".to_string()),
+                    Object::String("a".to_string()),
+                    Object::String("b".to_string()),
+                    Object::Integer(2),
+                ],
+                vec![
+                    _make(Opcode::Hash, 0),
+                    _make_noop(Opcode::SetGlobal0),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make_const(0),
+                    _make(Opcode::DestructHash, 1),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make(Opcode::DestructField, 1),
+                    _make_noop(Opcode::SetGlobal1),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make(Opcode::DestructField, 2),
+                    _make(Opcode::JumpIfNotNull, 21),
+                    _make_const(3),
+                    _make_noop(Opcode::SetGlobal2),
+                ],
+            ),
+        ];
+        run_compile_test(inputs);
+    }
+    #[test]
+    fn function_parameters() {
+        let mut f = CompiledFunction::with_name(
+            Some("f".to_string()),
+            Rc::new(
+                vec![
+                    _make_noop(Opcode::GetLocal1),
+                    _make(Opcode::JumpIfNotNull, 5),
+                    _make_const(0),
+                    _make_noop(Opcode::SetLocal1),
+                    _make_noop(Opcode::GetLocal0),
+                    _make_noop(Opcode::GetLocal1),
+                    _make_noop(Opcode::Add),
+                    _make_noop(Opcode::ReturnValue),
+                ]
+                .concat(),
+            ),
+            2,
+            2,
+        );
+        f.num_defaults = 1;
+        let inputs = vec![(
+            "let f = fn(a, b = 1) { a + b }; f(0, ...[2])",
+            vec![
+                Object::Integer(1),
+                Object::CompiledFunction(Rc::new(f)),
+                Object::Integer(0),
+                Object::Integer(2),
+            ],
+            vec![
+                _make_closure(1, 0),
+                _make_noop(Opcode::SetGlobal0),
+                _make_noop(Opcode::GetGlobal0),
+                _make_const(2),
+                _make(Opcode::Array, 1),
+                _make_const(3),
+                _make(Opcode::Array, 1),
+                _make(Opcode::Concat, 2),
+                _make_noop(Opcode::CallSpread),
+                _make_noop(Opcode::Pop),
+            ],
+        )];
+        run_compile_test(inputs);
+    }
+    #[test]
+    fn struct_statement() {
+        let inputs = vec![
+            (
+                "struct P { x; fn get() { this.x } }; P(1).get()",
+                vec![
+                    make_fun_object_with_name(
+                        "get",
+                        vec![
+                            _make_noop(Opcode::GetLocal0),
+                            _make(Opcode::GetSlot, 0),
+                            _make_noop(Opcode::ReturnValue),
+                        ]
+                        .concat(),
+                        1,
+                        1,
+                    ),
+                    Object::Struct(Rc::new(StructType::new(
+                        "P".to_string(),
+                        vec!["x".to_string()],
+                        vec![("get".to_string(), Object::Null)],
+                    ))),
+                    Object::Integer(1),
+                    Object::String("get".to_string()),
+                ],
+                vec![
+                    _make_closure(0, 0),
+                    make(Opcode::Struct, vec![1, 1]),
+                    _make_noop(Opcode::SetGlobal0),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make_const(2),
+                    _make(Opcode::Call, 1),
+                    make(Opcode::Invoke, vec![3, 0]),
+                    _make_noop(Opcode::Pop),
+                ],
+            ),
+            (
+                "struct P { x, y; fn inc(n) { this.y += n; this.z } }",
+                vec![
+                    Object::String("z".to_string()),
+                    make_fun_object_with_name(
+                        "inc",
+                        vec![
+                            _make_noop(Opcode::GetLocal0),
+                            _make(Opcode::IndexPeek, 0),
+                            _make(Opcode::GetSlot, 1),
+                            _make_noop(Opcode::GetLocal1),
+                            _make_noop(Opcode::Add),
+                            _make(Opcode::SetSlot, 1),
+                            _make_noop(Opcode::Pop),
+                            _make_noop(Opcode::GetLocal0),
+                            _make(Opcode::GetField, 0),
+                            _make_noop(Opcode::ReturnValue),
+                        ]
+                        .concat(),
+                        2,
+                        2,
+                    ),
+                    Object::Struct(Rc::new(StructType::new(
+                        "P".to_string(),
+                        vec!["x".to_string(), "y".to_string()],
+                        vec![("inc".to_string(), Object::Null)],
+                    ))),
+                ],
+                vec![
+                    _make_closure(1, 0),
+                    make(Opcode::Struct, vec![2, 1]),
+                    _make_noop(Opcode::SetGlobal0),
+                ],
+            ),
+            (
+                "struct P { x, y }; const p = P(1, 2); p.y = p.x",
+                vec![
+                    Object::Struct(Rc::new(StructType::new(
+                        "P".to_string(),
+                        vec!["x".to_string(), "y".to_string()],
+                        vec![],
+                    ))),
+                    Object::Integer(1),
+                    Object::Integer(2),
+                ],
+                vec![
+                    make(Opcode::Struct, vec![0, 0]),
+                    _make_noop(Opcode::SetGlobal0),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make_const(1),
+                    _make_const(2),
+                    _make(Opcode::Call, 2),
+                    _make_noop(Opcode::SetGlobal1),
+                    _make_noop(Opcode::GetGlobal1),
+                    _make_noop(Opcode::GetGlobal1),
+                    _make(Opcode::GetSlot, 0),
+                    _make(Opcode::SetSlot, 1),
+                    _make_noop(Opcode::Pop),
+                ],
+            ),
+            (
+                //let变量可能被重新赋值, 按名称访问
+                "struct P { x }; P(1).x; let q = P(2); q.x",
+                vec![
+                    Object::Struct(Rc::new(StructType::new(
+                        "P".to_string(),
+                        vec!["x".to_string()],
+                        vec![],
+                    ))),
+                    Object::Integer(1),
+                    Object::Integer(2),
+                    Object::String("x".to_string()),
+                ],
+                vec![
+                    make(Opcode::Struct, vec![0, 0]),
+                    _make_noop(Opcode::SetGlobal0),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make_const(1),
+                    _make(Opcode::Call, 1),
+                    _make(Opcode::GetSlot, 0),
+                    _make_noop(Opcode::Pop),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make_const(2),
+                    _make(Opcode::Call, 1),
+                    _make_noop(Opcode::SetGlobal1),
+                    _make_noop(Opcode::GetGlobal1),
+                    _make(Opcode::GetField, 3),
+                    _make_noop(Opcode::Pop),
+                ],
+            ),
+        ];
+        run_compile_test(inputs);
+        let errors = [
+            "struct P { x; fn m() { this = 1 } }",
+            "struct P { x; fn m() { this += 1 } }",
+            "struct P { x }; let P = 1",
+        ];
+        for input in errors {
+            let program = Program::_new(input);
+            let result = Compiler::new().compile(&program);
+            assert!(
+                matches!(result, Err(CompileError::CustomErrMsg(_))),
+                "input: {}",
+                input
+            );
+        }
+        let program = Program::_new("struct P { x }; P = 1");
+        let result = Compiler::new().compile(&program);
+        assert!(matches!(result, Err(CompileError::AssignToConstant(_))));
+    }
+    #[test]
+    fn compound_assign() {
+        let inputs = vec![
+            (
+                "let a = 1; a += 2",
+                vec![Object::Integer(1), Object::Integer(2)],
+                vec![
+                    _make_const(0),
+                    _make_noop(Opcode::SetGlobal0),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make_const(1),
+                    _make_noop(Opcode::Add),
+                    _make_noop(Opcode::Dup),
+                    _make_noop(Opcode::SetGlobal0),
+                    _make_noop(Opcode::Pop),
+                ],
+            ),
+            (
+                "let a = [1]; a[0] %= 2",
+                vec![Object::Integer(1), Object::Integer(0), Object::Integer(2)],
+                vec![
+                    _make_const(0),
+                    _make(Opcode::Array, 1),
+                    _make_noop(Opcode::SetGlobal0),
+                    _make_noop(Opcode::GetGlobal0),
+                    _make_const(1),
+                    _make(Opcode::IndexPeek, 1),
+                    _make_const(2),
+                    _make_noop(Opcode::Mod),
+                    _make(Opcode::SetIndex, 1),
+                    _make_noop(Opcode::Pop),
+                ],
+            ),
+        ];
+        run_compile_test(inputs);
+    }
+    #[test]
+    fn range_expression() {
+        let inputs = vec![
+            (
+                "1..=2",
+                vec![Object::Integer(1), Object::Integer(2)],
+                vec![
+                    _make_const(0),
+                    _make_const(1),
+                    _make(Opcode::Range, 1),
+                    _make_noop(Opcode::Pop),
+                ],
+            ),
+            (
+                "..2",
+                vec![Object::Integer(2)],
+                vec![
+                    _make_noop(Opcode::Null),
+                    _make_const(0),
+                    _make(Opcode::Range, 0),
+                    _make_noop(Opcode::Pop),
+                ],
+            ),
+        ];
+        run_compile_test(inputs);
+    }
+    #[test]
+    fn loop_control_outside_loop() {
+        let inputs = ["break", "fn() { continue }", "while (true) { fn() { break } }"];
+        for input in inputs {
+            let program = Program::_new(input);
+            let result = Compiler::new().compile(&program);
+            assert!(
+                matches!(result, Err(CompileError::LoopControlOutsideLoop(_))),
+                "input: {}",
+                input
+            );
+        }
+    }
+    #[test]
+    fn undefined_label() {
+        let inputs = [
+            "break typo",
+            "while (true) { continue typo }",
+            "outer: while (true) { fn() { break outer } }",
+        ];
+        for input in inputs {
+            let program = Program::_new(input);
+            let result = Compiler::new().compile(&program);
+            assert!(
+                matches!(result, Err(CompileError::UndefinedLabel(_))),
+                "input: {}",
+                input
+            );
+        }
+    }
+    #[test]
+    fn import_errors() {
+        let cycle_a = std::path::Path::new("res/modules/cycle_a.my")
+            .canonicalize()
+            .unwrap();
+        let cycle_b = std::path::Path::new("res/modules/cycle_b.my")
+            .canonicalize()
+            .unwrap();
+        let program = Program::_new("import \"res/modules/cycle_a.my\" as a");
+        match Compiler::new().compile(&program) {
+            Err(CompileError::Import(ModuleError::Cycle(files))) => assert_eq!(
+                files,
+                [&cycle_a, &cycle_b, &cycle_a].map(|f| f.display().to_string())
+            ),
+            result => panic!("expected import cycle, got {:?}", result),
+        }
+        let program = Program::_new("import \"res/modules/none.my\" as none");
+        assert!(matches!(
+            Compiler::new().compile(&program),
+            Err(CompileError::Import(ModuleError::NotFound(_)))
+        ));
+        for input in [
+            "import \"res/modules/counter.my\" as c; c = 1",
+            "import { n } from \"res/modules/counter.my\"; n += 1",
+        ] {
+            let program = Program::_new(input);
+            let result = Compiler::new().compile(&program);
+            assert!(
+                matches!(result, Err(CompileError::AssignToConstant(_))),
+                "input: {}",
+                input
+            );
+        }
+    }
+    #[test]
+    fn template_literal() {
+        let inputs = vec![(
+            "`a${1}`",
+            vec![Object::String("a".to_string()), Object::Integer(1)],
+            vec![
+                _make_const(0),                // 0
+                _make_const(1),                // 1
+                _make(Opcode::BuildString, 2), // 2
+                _make_noop(Opcode::Pop),       // 5
+            ],
+        )];
+        run_compile_test(inputs);
+    }
+    #[test]
+    fn const_statement() {
+        let inputs = vec![(
+            "const a = 1; const b = [a]; b; a",
+            vec![Object::Integer(1)],
+            vec![
+                _make_const(0),                 // 0
+                _make(Opcode::Array, 1),        // 1
+                _make_noop(Opcode::SetGlobal1), // 4
+                _make_noop(Opcode::GetGlobal1), // 5
+                _make_noop(Opcode::Pop),        // 6
+                _make_const(0),                 // 7
+                _make_noop(Opcode::Pop),        // 8
+            ],
+        )];
+        run_compile_test(inputs);
+        let errors = [
+            "const a = 1; a = 2",
+            "const a = [1]; a += [2]",
+            "const a = 1; fn() { a = 2 }",
+            "let f = fn() { const a = []; fn() { a = 1 } }",
+        ];
+        for input in errors {
+            let program = Program::_new(input);
+            let result = Compiler::new().compile(&program);
+            assert!(
+                matches!(result, Err(CompileError::AssignToConstant(_))),
+                "input: {}",
+                input
+            );
+        }
+    }
+    #[test]
+    fn block_scopes() {
+        let inputs = vec![
+            (
+                "let a = 1; if (true) { let a = 2; a }; a",
+                vec![Object::Integer(1), Object::Integer(2)],
+                vec![
+                    _make_const(0),                     // 0
+                    _make_noop(Opcode::SetGlobal0),     // 1
+                    _make_noop(Opcode::True),           // 2
+                    _make(Opcode::JumpIfNotTruthy, 12), // 3
+                    _make_const(1),                     // 6
+                    _make_noop(Opcode::SetLocal0),      // 7 块内的a为主帧的局部变量
+                    _make_noop(Opcode::GetLocal0),      // 8
+                    _make(Opcode::JumpAlways, 13),      // 9
+                    _make_noop(Opcode::Null),           // 12
+                    _make_noop(Opcode::Pop),            // 13
+                    _make_noop(Opcode::GetGlobal0),     // 14 离开块后为外层的a
+                    _make_noop(Opcode::Pop),            // 15
+                ],
+            ),
+            (
+                "let a = 1; fn(a) { a }",
+                vec![
+                    Object::Integer(1),
+                    make_fun_object(
+                        vec![
+                            _make_noop(Opcode::GetLocal0),
+                            _make_noop(Opcode::ReturnValue),
+                        ]
+                        .concat(),
+                        1,
+                        1,
+                    ),
+                ],
+                vec![
+                    _make_const(0),
+                    _make_noop(Opcode::SetGlobal0),
+                    _make_closure(1, 0),
+                    _make_noop(Opcode::Pop),
+                ],
+            ),
+        ];
+        run_compile_test(inputs);
+    }
+    #[test]
+    fn redeclare_in_same_block() {
+        let inputs = [
+            "let a = 1; let a = 2;",
+            "fn(a) { let a = 1 }",
+            "if (true) { let b = 1; let b = 2 }",
+            "fn f() {}; let f = 1;",
+        ];
+        for input in inputs {
+            let program = Program::_new(input);
+            let result = Compiler::new().compile(&program);
+            assert!(
+                matches!(result, Err(CompileError::CustomErrMsg(_))),
+                "input: {}",
+                input
+            );
+        }
+    }
+    #[test]
     fn recursive_function() {
         let count_down_const = make_fun_object_with_name(
             "countDown",
@@ -167,7 +904,7 @@ mod tests {
                     ),
                     make_fun_object(
                         vec![
-                            _make_noop(Opcode::GetLocal0),
+                            _make(Opcode::CaptureLocal, 0),
                             _make_closure(0, 1),
                             _make_noop(Opcode::ReturnValue),
                         ]
@@ -204,8 +941,8 @@ mod tests {
                     ),
                     make_fun_object(
                         vec![
-                            _make(Opcode::GetFree, 0),
-                            _make_noop(Opcode::GetLocal0),
+                            _make(Opcode::CaptureFree, 0),
+                            _make(Opcode::CaptureLocal, 0),
                             _make_closure(0, 2),
                             _make_noop(Opcode::ReturnValue),
                         ]
@@ -215,7 +952,7 @@ mod tests {
                     ),
                     make_fun_object(
                         vec![
-                            _make_noop(Opcode::GetLocal0),
+                            _make(Opcode::CaptureLocal, 0),
                             _make_closure(1, 1),
                             _make_noop(Opcode::ReturnValue),
                         ]
@@ -266,8 +1003,8 @@ mod tests {
                         vec![
                             _make_const(2),
                             _make_noop(Opcode::SetLocal0), // declare b
-                            _make(Opcode::GetFree, 0),     // free a
-                            _make_noop(Opcode::GetLocal0),
+                            _make(Opcode::CaptureFree, 0), // free a
+                            _make(Opcode::CaptureLocal, 0),
                             _make_closure(4, 2), // closure
                             _make_noop(Opcode::ReturnValue),
                         ]
@@ -279,7 +1016,7 @@ mod tests {
                         vec![
                             _make_const(1),
                             _make_noop(Opcode::SetLocal0), // declare a
-                            _make_noop(Opcode::GetLocal0),
+                            _make(Opcode::CaptureLocal, 0),
                             _make_closure(5, 1),
                             _make_noop(Opcode::ReturnValue),
                         ]
@@ -299,6 +1036,66 @@ mod tests {
         run_compile_test(tests);
     }
     #[test]
+    fn mutable_closures() {
+        let tests = vec![
+            (
+                "fn() { let a = 1; a = 2; a }",
+                vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                    make_fun_object(
+                        vec![
+                            _make_const(0),
+                            _make_noop(Opcode::SetLocal0), // declare a
+                            _make_const(1),
+                            _make_noop(Opcode::Dup),
+                            _make(Opcode::AssignLocal, 0), // a = 2
+                            _make_noop(Opcode::Pop),
+                            _make_noop(Opcode::GetLocal0),
+                            _make_noop(Opcode::ReturnValue),
+                        ]
+                        .concat(),
+                        1,
+                        0,
+                    ),
+                ],
+                vec![_make_closure(2, 0), _make_noop(Opcode::Pop)],
+            ),
+            (
+                "fn() { let a = 1; fn() { a = 2 } }",
+                vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                    make_fun_object(
+                        vec![
+                            _make_const(1),
+                            _make_noop(Opcode::Dup),
+                            _make(Opcode::SetFree, 0),
+                            _make_noop(Opcode::ReturnValue),
+                        ]
+                        .concat(),
+                        0,
+                        0,
+                    ),
+                    make_fun_object(
+                        vec![
+                            _make_const(0),
+                            _make_noop(Opcode::SetLocal0),
+                            _make(Opcode::CaptureLocal, 0),
+                            _make_closure(2, 1),
+                            _make_noop(Opcode::ReturnValue),
+                        ]
+                        .concat(),
+                        1,
+                        0,
+                    ),
+                ],
+                vec![_make_closure(3, 0), _make_noop(Opcode::Pop)],
+            ),
+        ];
+        run_compile_test(tests);
+    }
+    #[test]
     fn builtins() {
         let tests = vec![(
             r"
@@ -416,16 +1213,14 @@ mod tests {
                 vec![
                     Object::Integer(1),
                     make_fun_object_with_name("f",
-                        vec![
-                            _make_const( 0),
+                        [_make_const( 0),
                             _make_noop(SetLocal2),
                             _make_noop(GetLocal0),
                             _make_noop(GetLocal1),
                             _make_noop(Add),
                             _make_noop(GetLocal2),
                             _make_noop(Add),
-                            _make_noop(ReturnValue),
-                        ].concat(),
+                            _make_noop(ReturnValue)].concat(),
                         3,2
                     ),
                     Object::Integer(2),
@@ -626,7 +1421,7 @@ mod tests {
                 vec![
                     make_fun_object_with_name(
                         "one_arg",
-                        vec![_make_noop(Opcode::Return)].concat(),
+                        [_make_noop(Opcode::Return)].concat(),
                         1,
                         1,
                     ),
@@ -680,14 +1475,14 @@ mod tests {
                     Object::Integer(1),
                     make_fun_object_with_name(
                         "one",
-                        vec![_make_const(0), _make_noop(Opcode::ReturnValue)].concat(),
+                        [_make_const(0), _make_noop(Opcode::ReturnValue)].concat(),
                         0,
                         0,
                     ),
                     Object::Integer(2),
                     make_fun_object_with_name(
                         "two",
-                        vec![_make_const(2), _make_noop(Opcode::ReturnValue)].concat(),
+                        [_make_const(2), _make_noop(Opcode::ReturnValue)].concat(),
                         0,
                         0,
                     ),
@@ -788,7 +1583,9 @@ mod tests {
                     _make_const(0),
                     _make_noop(Opcode::SetGlobal0),
                     _make_const(1),
+                    _make_noop(Opcode::Dup),
                     _make_noop(Opcode::SetGlobal0),
+                    _make_noop(Opcode::Pop),
                     _make_noop(Opcode::GetGlobal0),
                     _make_noop(Opcode::Pop),
                 ],
@@ -809,9 +1606,11 @@ mod tests {
                     _make_const(2),
                     _make(Opcode::Array, 3),     //声明赋值数组
                     _make_noop(Opcode::SetGlobal0), //存arr
+                    _make_noop(Opcode::GetGlobal0),   //取arr
                     _make_const(3),                   //index
                     _make_const(4),                   //value
-                    _make_noop(Opcode::SetGlobal0),         //arr[index] = value
+                    _make(Opcode::SetIndex, 1),       //arr[index] = value
+                    _make_noop(Opcode::Pop),
                     _make_noop(Opcode::GetGlobal0),   //取arr
                     // make(Opcode::Constant, vec![5]),  //index
                     // make(Opcode::Index, vec![]),      //arr[index]
@@ -848,9 +1647,11 @@ mod tests {
                     _make_const(8),
                     _make(Opcode::Hash, 3),
                     _make_noop(Opcode::SetGlobal0),    //声明初始化Map
+                    _make_noop(Opcode::GetGlobal0), //取map
                     _make_const(9),                 //index
                     _make_const(10),                //value
-                    _make_noop(Opcode::SetGlobal0),       //map[index] = value
+                    _make(Opcode::SetIndex, 1),     //map[index] = value
+                    _make_noop(Opcode::Pop),
                     _make_noop(Opcode::GetGlobal0), //取map
                     _make_noop(Opcode::Pop),
                 ],
@@ -1059,6 +1860,7 @@ mod tests {
                     name: "a".to_string(),
                     scope: SymbolScope::Global,
                     index: 0,
+                    binding: Binding::Variable,
                 },
             );
             map.insert(
@@ -1067,6 +1869,7 @@ mod tests {
                     name: "b".to_string(),
                     scope: SymbolScope::Global,
                     index: 1,
+                    binding: Binding::Variable,
                 },
             );
             map
@@ -1091,6 +1894,7 @@ mod tests {
                     name: "a".to_string(),
                     scope: SymbolScope::Global,
                     index: 0,
+                    binding: Binding::Variable,
                 },
             );
             map.insert(
@@ -1099,6 +1903,7 @@ mod tests {
                     name: "b".to_string(),
                     scope: SymbolScope::Global,
                     index: 1,
+                    binding: Binding::Variable,
                 },
             );
             map
@@ -1106,7 +1911,7 @@ mod tests {
         for (k, v) in expected {
             let x = global
                 .resolve(k)
-                .expect(&format!("name {} not resolvable", k));
+                .unwrap_or_else(|| panic!("name {} not resolvable", k));
             assert_eq!(*x, v.clone());
         }
     }
@@ -1126,58 +1931,66 @@ mod tests {
                 "a"=> Symbol {
                 name: "a".to_string(),
                 scope: SymbolScope::Global,
-                index: 0
+                index: 0,
+                binding: Binding::Variable
                 },
                 "b"=> Symbol {
                 name: "b".to_string(),
                 scope: SymbolScope::Global,
-                index: 1
+                index: 1,
+                binding: Binding::Variable
                 },
                 "c"=> Symbol {
                     name: "c".to_string(),
                     scope: SymbolScope::Local,
-                    index: 0
+                    index: 0,
+                    binding: Binding::Variable
                 },
                 "d"=> Symbol {
                     name: "d".to_string(),
                     scope: SymbolScope::Local,
-                    index: 1
+                    index: 1,
+                    binding: Binding::Variable
                 },
         };
         for (k, v) in expected {
             let x = first
                 .borrow_mut()
                 .resolve(k)
-                .expect(&format!("name {} not resolvable", k));
+                .unwrap_or_else(|| panic!("name {} not resolvable", k));
             assert_eq!(*x, v.clone());
         }
         let expected = hash! {
                 "a"=> Symbol {
                 name: "a".to_string(),
                 scope: SymbolScope::Global,
-                index: 0
+                index: 0,
+                binding: Binding::Variable
                 },
                  "b"=> Symbol {
                 name: "b".to_string(),
                 scope: SymbolScope::Global,
-                index: 1
+                index: 1,
+                binding: Binding::Variable
                 },
                 "e"=> Symbol {
                     name: "e".to_string(),
                     scope: SymbolScope::Local,
-                    index: 0
+                    index: 0,
+                    binding: Binding::Variable
                 },
                 "f"=> Symbol {
                     name: "f".to_string(),
                     scope: SymbolScope::Local,
-                    index: 1
+                    index: 1,
+                    binding: Binding::Variable
                 },
         };
         for (k, v) in expected {
             let x = second
                 .borrow_mut()
                 .resolve(k)
-                .expect(&format!("name {} not resolvable", k));
+                .unwrap_or_else(|| panic!("name {} not resolvable", k));
             assert_eq!(*x, v.clone());
         }
     }
@@ -1435,6 +2248,56 @@ mod tests {
         run_compile_test(tests);
     }
 
+    #[test]
+    fn test_float_arithmetic() {
+        let tests = vec![(
+            "1.5 * 2",
+            vec![Object::Float(1.5), Object::Integer(2)],
+            vec![
+                code::_make_const(0),
+                code::_make_const(1),
+                code::make(Opcode::Mul, vec![]),
+                code::make(Opcode::Pop, vec![]),
+            ],
+        )];
+        run_compile_test(tests);
+    }
+
+    #[test]
+    fn test_logical_expression() {
+        let tests = vec![
+            (
+                "true && false",
+                vec![],
+                vec![
+                    _make_noop(True),              // 0
+                    _make(JumpIfNotTruthy, 12),    // 1
+                    _make_noop(False),             // 4
+                    _make(JumpIfNotTruthy, 12),    // 5
+                    _make_noop(True),              // 8
+                    _make(JumpAlways, 13),         // 9
+                    _make_noop(False),             // 12
+                    _make_noop(Pop),               // 13
+                ],
+            ),
+            (
+                "true || false",
+                vec![],
+                vec![
+                    _make_noop(True),           // 0
+                    _make(JumpIfTruthy, 12),    // 1
+                    _make_noop(False),          // 4
+                    _make(JumpIfTruthy, 12),    // 5
+                    _make_noop(False),          // 8
+                    _make(JumpAlways, 13),      // 9
+                    _make_noop(True),           // 12
+                    _make_noop(Pop),            // 13
+                ],
+            ),
+        ];
+        run_compile_test(tests);
+    }
+
     #[test]
     fn test_make() {
         let tests = vec![
@@ -1468,7 +2331,7 @@ mod tests {
                     assert_eq!(
                         constants
                             .iter()
-                            .map(|o| Object::clone(&o))
+                            .map(|o| Object::clone(o))
                             .collect::<Vec<Object>>(),
                         expected_constants,
                         "\nconstant:\n{}",
@@ -1488,11 +2351,11 @@ mod tests {
         }
     }
     fn make_fun_object(insts: Vec<u8>, num_locals: usize, num_parameters: usize) -> Object {
-        Object::CompiledFunction(CompiledFunction::new(
+        Object::CompiledFunction(Rc::new(CompiledFunction::new(
             Rc::new(insts),
             num_locals,
             num_parameters,
-        ))
+        )))
     }
     fn make_fun_object_with_name(
         name: &str,
@@ -1500,11 +2363,11 @@ mod tests {
         num_locals: usize,
         num_parameters: usize,
     ) -> Object {
-        Object::CompiledFunction(CompiledFunction::with_name(
+        Object::CompiledFunction(Rc::new(CompiledFunction::with_name(
             Some(name.to_string()),
             Rc::new(insts),
             num_locals,
             num_parameters,
-        ))
+        )))
     }
 }
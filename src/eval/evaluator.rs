@@ -3,11 +3,16 @@ use std::collections::HashMap;
 use std::rc::Rc;
 
 use crate::eval::Environment;
-use crate::object::builtins::lookup;
+use crate::module;
+use crate::object::builtins::{lookup, lookup_method};
 use crate::object::Object::Boolean;
-use crate::object::{HashKey, Object, RuntimeError};
+use crate::object::{
+    check_argument_count, Container, Function, HashKey, Iter, Object, Range, RuntimeError,
+    StructType,
+};
 use crate::parser::ast::{
-    BinaryOperator, BlockStatement, Expression, Program, Statement, UnaryOperator,
+    BinaryOperator, BlockStatement, Expression, Import, LetPattern, MatchArm, Parameter, Pattern,
+    Program, Statement, UnaryOperator,
 };
 use std::ops::Deref;
 
@@ -24,7 +29,27 @@ fn eval_statement(statement: &Statement, env: Env) -> EvalResult {
     match statement {
         Statement::Let(name, expr) => {
             let val = eval_expression(expr, Rc::clone(&env))?;
-            env.borrow_mut().set(name, val)?;
+            env.borrow_mut().declare(name, val)?;
+            Ok(Object::Null)
+        }
+        Statement::Const(name, expr) => {
+            let val = match expr {
+                Expression::FunctionLiteral(args, blocks) => {
+                    Object::Function(Rc::new(Function::new(
+                        Some(name.clone()),
+                        args.clone(),
+                        blocks.clone(),
+                        env.clone(),
+                    )))
+                }
+                expr => eval_expression(expr, Rc::clone(&env))?,
+            };
+            env.borrow_mut().declare_const(name, val)?;
+            Ok(Object::Null)
+        }
+        Statement::Destructure(pattern, expr) => {
+            let val = eval_expression(expr, Rc::clone(&env))?;
+            destructure(pattern, val, env)?;
             Ok(Object::Null)
         }
         Statement::Return(option) => {
@@ -38,66 +63,210 @@ fn eval_statement(statement: &Statement, env: Env) -> EvalResult {
         Statement::Expression(expr) => eval_expression(expr, Rc::clone(&env)),
         Statement::Comment(comment) => Ok(Object::String(comment.to_string())),
         Statement::For(init, cond, after, blocks) => {
-            eval_for_statement(init, cond, after, blocks, Rc::clone(&env))
+            eval_for_statement(init, cond, after, blocks, None, Rc::clone(&env))
+        }
+        Statement::ForIn(names, iterable, blocks) => {
+            eval_for_in_statement(names, iterable, blocks, None, Rc::clone(&env))
+        }
+        Statement::While(cond, blocks) => eval_while_statement(cond, blocks, None, Rc::clone(&env)),
+        Statement::Throw(value) => {
+            let value = eval_expression(value, Rc::clone(&env))?;
+            Err(RuntimeError::Thrown(value))
+        }
+        Statement::Try(body, catch, finally) => {
+            eval_try_statement(body, catch, finally, Rc::clone(&env))
         }
+        Statement::Import(path, import) => eval_import_statement(path, import, Rc::clone(&env)),
+        Statement::Export(statement) => eval_statement(statement, Rc::clone(&env)),
+        Statement::Block(block) => eval_block_statements(block, Rc::clone(&env)),
+        Statement::Break(label) => Ok(Object::Break(label.clone())),
+        Statement::Continue(label) => Ok(Object::Continue(label.clone())),
+        Statement::Labeled(label, statement) => match statement.as_ref() {
+            Statement::For(init, cond, after, blocks) => {
+                eval_for_statement(init, cond, after, blocks, Some(label), Rc::clone(&env))
+            }
+            Statement::ForIn(names, iterable, blocks) => {
+                eval_for_in_statement(names, iterable, blocks, Some(label), Rc::clone(&env))
+            }
+            Statement::While(cond, blocks) => {
+                eval_while_statement(cond, blocks, Some(label), Rc::clone(&env))
+            }
+            statement => eval_statement(statement, Rc::clone(&env)),
+        },
         Statement::Function(name, args, blocks) => {
-            let val = Object::Function(
+            let val = Object::Function(Rc::new(Function::new(
                 Some(name.clone()),
                 args.clone(),
                 blocks.clone(),
                 env.clone(),
-            );
-            env.borrow_mut().set(name, val)?;
+            )));
+            env.borrow_mut().declare(name, val)?;
+            Ok(Object::Null)
+        }
+        Statement::Struct(name, fields, methods) => {
+            //方法的第一个参数是实例本身this
+            let methods = methods
+                .iter()
+                .map(|(method, args, blocks)| {
+                    let params = std::iter::once(Parameter::new("this".to_string()))
+                        .chain(args.iter().cloned())
+                        .collect();
+                    let fun = Function {
+                        method: true,
+                        ..Function::new(Some(method.clone()), params, blocks.clone(), env.clone())
+                    };
+                    let fun = Object::Function(Rc::new(fun));
+                    (method.clone(), fun)
+                })
+                .collect();
+            let struct_type = StructType::new(name.clone(), fields.clone(), methods);
+            env.borrow_mut()
+                .declare_const(name, Object::Struct(Rc::new(struct_type)))?;
             Ok(Object::Null)
         } // _ => unimplemented!(),
     }
 }
-/// for循环求值
+/// 循环体执行后的去向
+enum LoopFlow {
+    /// 继续下一次循环
+    Next,
+    /// 跳出当前循环
+    Break,
+    /// 跳出当前循环并向外传递(return或外层标签的break/continue)
+    Exit(Object),
+}
+/// 循环体求值
+fn eval_loop_body(blocks: &BlockStatement, label: Option<&str>, env: Env) -> EvalResult<LoopFlow> {
+    let result = eval_block_statements(blocks, env)?;
+    let flow = match &result {
+        Object::Break(target) if is_current_loop(target, label) => LoopFlow::Break,
+        Object::Continue(target) if is_current_loop(target, label) => LoopFlow::Next,
+        Object::Return(_) | Object::Break(_) | Object::Continue(_) => LoopFlow::Exit(result),
+        _ => LoopFlow::Next,
+    };
+    Ok(flow)
+}
+/// 没有标签或标签与当前循环一致
+fn is_current_loop(target: &Option<String>, label: Option<&str>) -> bool {
+    match target {
+        None => true,
+        Some(target) => Some(target.as_str()) == label,
+    }
+}
+/// for循环求值, init中声明的变量只在循环内可见
 fn eval_for_statement(
     init: &Option<Box<Statement>>,
     cond: &Option<Expression>,
     after: &Option<Expression>,
     blocks: &BlockStatement,
+    label: Option<&str>,
     env: Env,
 ) -> EvalResult {
+    let env = Rc::new(RefCell::new(Environment::extend(env)));
     if let Some(init) = init.as_deref() {
         eval_statement(init, Rc::clone(&env))?;
     }
     loop {
         if let Some(cond) = cond {
-            let cond_obj = eval_expression(cond, Rc::clone(&env))?;
-            if let Object::Boolean(b) = cond_obj {
-                if b {
-                    eval_block_statements(blocks, Rc::clone(&env))?;
-                } else {
-                    break;
-                }
+            if !eval_expression(cond, Rc::clone(&env))?.is_truthy() {
+                break;
             }
         }
+        match eval_loop_body(blocks, label, Rc::clone(&env))? {
+            LoopFlow::Next => {}
+            LoopFlow::Break => break,
+            LoopFlow::Exit(result) => return Ok(result),
+        }
         if let Some(after) = after {
             eval_expression(after, Rc::clone(&env))?;
         }
     }
     Ok(Object::Null)
 }
+/// for-in循环求值
+fn eval_for_in_statement(
+    names: &[String],
+    iterable: &Expression,
+    blocks: &BlockStatement,
+    label: Option<&str>,
+    env: Env,
+) -> EvalResult {
+    let iterable = eval_expression(iterable, Rc::clone(&env))?;
+    let mut iter = Iter::new(Rc::new(iterable))?;
+    loop {
+        let values = if names.len() == 1 {
+            iter.next_value().map(|value| vec![value])
+        } else {
+            iter.next_pair().map(|(key, value)| vec![key, value])
+        };
+        let values = match values {
+            Some(values) => values,
+            None => break,
+        };
+        //每次迭代重新绑定循环变量
+        let iter_env = Rc::new(RefCell::new(Environment::extend(Rc::clone(&env))));
+        for (name, value) in names.iter().zip(values) {
+            iter_env.borrow_mut().set(name, value)?;
+        }
+        match eval_loop_body(blocks, label, iter_env)? {
+            LoopFlow::Next => {}
+            LoopFlow::Break => break,
+            LoopFlow::Exit(result) => return Ok(result),
+        }
+    }
+    Ok(Object::Null)
+}
+/// while循环求值
+fn eval_while_statement(
+    cond: &Expression,
+    blocks: &BlockStatement,
+    label: Option<&str>,
+    env: Env,
+) -> EvalResult {
+    while eval_expression(cond, Rc::clone(&env))?.is_truthy() {
+        match eval_loop_body(blocks, label, Rc::clone(&env))? {
+            LoopFlow::Next => {}
+            LoopFlow::Break => break,
+            LoopFlow::Exit(result) => return Ok(result),
+        }
+    }
+    Ok(Object::Null)
+}
 /// ## 多条语句求值
 fn eval_statements(statements: &[Statement], env: Env) -> EvalResult {
     let mut result = Object::Null;
     for statement in statements {
         result = eval_statement(statement, Rc::clone(&env))?;
-        if let Object::Return(obj) = result {
-            return Ok(*obj);
+        match result {
+            Object::Return(obj) => return Ok(*obj),
+            Object::Break(_) | Object::Continue(_) => return Err(loop_control_error(result)),
+            _ => {}
         }
     }
     Ok(result)
 }
 
-/// ## 语句块求值
+/// 离开函数或程序的break/continue: 带标签时为标签不存在, 否则为不在循环内
+fn loop_control_error(control: Object) -> RuntimeError {
+    match control {
+        Object::Break(Some(label)) | Object::Continue(Some(label)) => {
+            RuntimeError::UndefinedLabel(label)
+        }
+        control => RuntimeError::LoopControlOutsideLoop(control),
+    }
+}
+
+/// ## 语句块求值, 块内声明的变量离开块后不可见
 fn eval_block_statements(block: &BlockStatement, env: Env) -> EvalResult {
+    eval_block_in_env(block, Rc::new(RefCell::new(Environment::extend(env))))
+}
+
+/// ## 在给定环境中对语句块求值
+fn eval_block_in_env(block: &BlockStatement, env: Env) -> EvalResult {
     let mut result = Object::Null;
     for statement in &block.statements {
         result = eval_statement(statement, Rc::clone(&env))?;
-        if let Object::Return(_) = result {
+        if let Object::Return(_) | Object::Break(_) | Object::Continue(_) = result {
             return Ok(result);
         }
     }
@@ -108,8 +277,16 @@ fn eval_block_statements(block: &BlockStatement, env: Env) -> EvalResult {
 fn eval_expression(expr: &Expression, env: Env) -> EvalResult {
     match expr {
         Expression::IntLiteral(int) => Ok(Object::Integer(*int)),
+        Expression::FloatLiteral(float) => Ok(Object::Float(*float)),
         Expression::BoolLiteral(bool) => Ok(Object::Boolean(*bool)),
         Expression::StringLiteral(str) => Ok(Object::String(str.clone())),
+        Expression::Template(parts) => {
+            let mut string = String::new();
+            for part in parts {
+                string.push_str(&eval_expression(part, Rc::clone(&env))?.to_string());
+            }
+            Ok(Object::String(string))
+        }
 
         Expression::Unary(op, expr) => {
             eval_unary_expression(op, eval_expression(expr, Rc::clone(&env))?)
@@ -120,8 +297,7 @@ fn eval_expression(expr: &Expression, env: Env) -> EvalResult {
 
         Expression::If(cond, block, else_block) => {
             let bool_object = eval_expression(cond, Rc::clone(&env))?;
-            let bool = eval_object_to_bool(bool_object);
-            if bool {
+            if bool_object.is_truthy() {
                 eval_block_statements(block, Rc::clone(&env))
             } else {
                 else_block.as_ref().map_or(Ok(Object::Null), |else_block| {
@@ -131,22 +307,210 @@ fn eval_expression(expr: &Expression, env: Env) -> EvalResult {
         }
 
         Expression::Identifier(id) => eval_identifier_expression(Rc::clone(&env), id),
-        Expression::FunctionLiteral(params, block) => Ok(Object::Function(
+        Expression::FunctionLiteral(params, block) => Ok(Object::Function(Rc::new(Function::new(
             // todo 待定是否新增object类型区分函数语句和表达式
             None,
             params.clone(),
             block.clone(),
             Rc::clone(&env),
-        )),
+        )))),
         Expression::Call(fun, params) => eval_call_expression(Rc::clone(&env), fun, params),
+        Expression::Field(obj, field) => eval_expression(obj, Rc::clone(&env))?.get_field(field),
+        Expression::MethodCall(receiver, method, params) => {
+            eval_method_call_expression(Rc::clone(&env), receiver, method, params)
+        }
         Expression::ArrayLiteral(elements) => eval_array_literal(Rc::clone(&env), elements),
         Expression::Index(arr_expr, idx_expr) => {
             eval_array_index(Rc::clone(&env), arr_expr, idx_expr)
         }
+        Expression::Range(start, end, inclusive) => {
+            let start = eval_range_bound(start, Rc::clone(&env))?;
+            let end = eval_range_bound(end, Rc::clone(&env))?;
+            Ok(Object::Range(Range::new(&start, &end, *inclusive)?))
+        }
         Expression::HashLiteral(pairs) => eval_hash_expression(Rc::clone(&env), pairs),
+        Expression::Match(subject, arms) => eval_match_expression(subject, arms, Rc::clone(&env)),
         _ => Err(RuntimeError::UnsupportedExpression(expr.clone())),
     }
 }
+/// ## import语句求值
+///
+/// 模块在独立的环境中执行, 每个文件只执行一次
+///
+/// 模块的成员和导入的名字与模块的变量共用存储, 导入的名字不能重新赋值
+fn eval_import_statement(path: &str, import: &Import, env: Env) -> EvalResult {
+    let module = module::import(path, |program| {
+        let module_env = Rc::new(RefCell::new(Environment::new()));
+        eval(program, Rc::clone(&module_env))?;
+        let module_env = module_env.borrow();
+        Ok(program
+            .exports()
+            .into_iter()
+            .filter_map(|name| Some((name.to_string(), module_env.get(name)?)))
+            .collect())
+    })?;
+    match import {
+        Import::Module(name) => env.borrow_mut().declare_const(name, module)?,
+        Import::Names(names) => {
+            for (name, alias) in names {
+                let cell = match &module {
+                    Object::Module(m) => m.cell(name),
+                    _ => None,
+                }
+                .ok_or_else(|| RuntimeError::NoSuchMember(module.clone(), name.clone()))?;
+                env.borrow_mut().declare_shared(alias, cell)?;
+            }
+        }
+    }
+    Ok(Object::Null)
+}
+/// ## try语句求值
+///
+/// 运行时错误和throw抛出的值都可以被catch捕获, finally总会执行,
+/// finally中的return/break/continue会取代try或catch的结果
+fn eval_try_statement(
+    body: &BlockStatement,
+    catch: &Option<(Option<String>, BlockStatement)>,
+    finally: &Option<BlockStatement>,
+    env: Env,
+) -> EvalResult {
+    let mut result = eval_block_statements(body, Rc::clone(&env));
+    if let Some((name, handler)) = catch {
+        if let Err(err) = result {
+            let catch_env = Rc::new(RefCell::new(Environment::extend(Rc::clone(&env))));
+            if let Some(name) = name {
+                catch_env.borrow_mut().declare(name, err.into_object())?;
+            }
+            result = eval_block_in_env(handler, catch_env);
+        }
+    }
+    if let Some(finally) = finally {
+        let finished = eval_block_statements(finally, Rc::clone(&env))?;
+        if let Object::Return(_) | Object::Break(_) | Object::Continue(_) = finished {
+            return Ok(finished);
+        }
+    }
+    //try语句本身没有值
+    match result? {
+        result @ (Object::Return(_) | Object::Break(_) | Object::Continue(_)) => Ok(result),
+        _ => Ok(Object::Null),
+    }
+}
+/// ## 解构赋值, 依次声明模式中的变量
+fn destructure(pattern: &LetPattern, value: Object, env: Env) -> EvalResult<()> {
+    //值为null时使用默认值
+    let or_default = |value: Object, default: &Option<Expression>| match default {
+        Some(default) if value == Object::Null => eval_expression(default, Rc::clone(&env)),
+        _ => Ok(value),
+    };
+    match pattern {
+        LetPattern::Name(name) => env.borrow_mut().declare(name, value),
+        LetPattern::Array(items, rest) => {
+            value.expect_array(LetPattern::required_len(items))?;
+            let values = match &value {
+                Object::Array(values) => values.borrow().clone(),
+                _ => vec![],
+            };
+            for (i, (item, default)) in items.iter().enumerate() {
+                let item_value = values.get(i).cloned().unwrap_or(Object::Null);
+                destructure(item, or_default(item_value, default)?, Rc::clone(&env))?;
+            }
+            if let Some(rest) = rest {
+                let rest_values = values.get(items.len()..).unwrap_or_default().to_vec();
+                let rest_value = Object::Array(Rc::new(Container::new(rest_values)));
+                env.borrow_mut().declare(rest, rest_value)?;
+            }
+            Ok(())
+        }
+        LetPattern::Hash(pairs) => {
+            let required = pairs
+                .iter()
+                .filter(|(_, _, default)| default.is_none())
+                .map(|(key, _, _)| Object::String(key.clone()))
+                .collect::<Vec<Object>>();
+            value.expect_members(&required)?;
+            for (key, item, default) in pairs {
+                let item_value = value.destructure_field(key)?;
+                destructure(item, or_default(item_value, default)?, Rc::clone(&env))?;
+            }
+            Ok(())
+        }
+    }
+}
+/// ## match表达式求值, 依次尝试各分支, 都不匹配时为null
+fn eval_match_expression(subject: &Expression, arms: &[MatchArm], env: Env) -> EvalResult {
+    let value = eval_expression(subject, Rc::clone(&env))?;
+    for arm in arms {
+        //每个分支绑定的变量只在该分支内可见
+        let arm_env = Rc::new(RefCell::new(Environment::extend(Rc::clone(&env))));
+        if !match_pattern(&arm.pattern, &value, Rc::clone(&arm_env))? {
+            continue;
+        }
+        if let Some(guard) = &arm.guard {
+            if !eval_expression(guard, Rc::clone(&arm_env))?.is_truthy() {
+                continue;
+            }
+        }
+        return eval_block_statements(&arm.body, arm_env);
+    }
+    Ok(Object::Null)
+}
+/// ## 模式匹配, 匹配成功时变量绑定到env中
+fn match_pattern(pattern: &Pattern, value: &Object, env: Env) -> EvalResult<bool> {
+    match pattern {
+        Pattern::Wildcard => Ok(true),
+        Pattern::Binding(name) => {
+            env.borrow_mut().set(name, value.clone())?;
+            Ok(true)
+        }
+        Pattern::Literal(literal) => {
+            let literal = eval_expression(literal, Rc::clone(&env))?;
+            let equal = eval_binary_operation(&BinaryOperator::Eq, value.clone(), literal)?;
+            Ok(equal.is_truthy())
+        }
+        Pattern::Or(alternatives) => {
+            for alternative in alternatives {
+                if match_pattern(alternative, value, Rc::clone(&env))? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        Pattern::Array(patterns) => {
+            let items = match value {
+                Object::Array(items) => items.borrow().clone(),
+                _ => return Ok(false),
+            };
+            if items.len() != patterns.len() {
+                return Ok(false);
+            }
+            for (pattern, item) in patterns.iter().zip(items.iter()) {
+                if !match_pattern(pattern, item, Rc::clone(&env))? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        Pattern::Hash(pairs) => {
+            let hash = match value {
+                Object::Hash(hash) => hash,
+                _ => return Ok(false),
+            };
+            for (key, pattern) in pairs {
+                let key = eval_expression(key, Rc::clone(&env))?;
+                let item = match HashKey::from_object(&key) {
+                    Ok(key) => hash.borrow().get(&key).cloned(),
+                    Err(_) => None,
+                };
+                match item {
+                    Some(item) if match_pattern(pattern, &item, Rc::clone(&env))? => {}
+                    _ => return Ok(false),
+                }
+            }
+            Ok(true)
+        }
+    }
+}
 /// ## map表达式求值
 fn eval_hash_expression(env: Env, pairs_expr: &[(Expression, Expression)]) -> EvalResult<Object> {
     let mut pairs = HashMap::new();
@@ -155,7 +519,7 @@ fn eval_hash_expression(env: Env, pairs_expr: &[(Expression, Expression)]) -> Ev
         let val = eval_expression(val_expr, Rc::clone(&env))?;
         pairs.insert(HashKey::from_object(&key)?, val);
     }
-    Ok(Object::Hash(RefCell::new(pairs)))
+    Ok(Object::Hash(Rc::new(Container::new(pairs))))
 }
 
 /// ## 数组/hash索引求值
@@ -164,8 +528,20 @@ fn eval_array_index(env: Env, obj_expr: &Expression, idx_expr: &Expression) -> E
     let index = eval_expression(idx_expr, Rc::clone(&env))?;
     eval_index_expression(&obj, &index)
 }
+/// ## 区间端点求值, 省略时为null
+fn eval_range_bound(bound: &Option<Box<Expression>>, env: Env) -> EvalResult {
+    bound
+        .as_ref()
+        .map_or(Ok(Object::Null), |bound| eval_expression(bound, env))
+}
 /// ## 索引表达式求值
 fn eval_index_expression(obj: &Object, idx: &Object) -> EvalResult {
+    //区间索引为切片
+    if let Object::Range(range) = idx {
+        return range
+            .slice(obj)
+            .ok_or_else(|| RuntimeError::IndexUnsupported(obj.clone()));
+    }
     match obj {
         Object::Array(items) => {
             if let Object::Integer(i) = *idx {
@@ -185,24 +561,31 @@ fn eval_index_expression(obj: &Object, idx: &Object) -> EvalResult {
             let r = pairs.get(key).unwrap_or(&Object::Null);
             Ok(r.clone())
         }
+        Object::Instance(_) => match idx {
+            Object::String(field) => obj.get_field(field),
+            _ => Err(RuntimeError::IndexUnsupported(obj.clone())),
+        },
         _ => Err(RuntimeError::IndexUnsupported(obj.clone())),
     }
 }
 
 /// ## 数组字面量求值
 fn eval_array_literal(env: Env, elements: &[Expression]) -> EvalResult {
-    let mut array = vec![];
-    for element in elements {
-        let object = eval_expression(element, Rc::clone(&env))?;
-        array.push(object);
-    }
-    Ok(Object::Array(RefCell::new(array)))
+    let array = eval_expressions(elements, env)?;
+    Ok(Object::Array(Rc::new(Container::new(array))))
 }
 
-/// ##多条表达式求值
+/// ##多条表达式求值, ...expr展开数组中的各个元素
 fn eval_expressions(exprs: &[Expression], env: Env) -> EvalResult<Vec<Object>> {
     let mut result = vec![];
     for expr in exprs {
+        if let Expression::Spread(spread) = expr {
+            match eval_expression(spread, Rc::clone(&env))? {
+                Object::Array(items) => result.extend(items.borrow().iter().cloned()),
+                obj => return Err(RuntimeError::CannotSpread(obj)),
+            }
+            continue;
+        }
         let obj = eval_expression(expr, Rc::clone(&env))?;
         result.push(obj);
     }
@@ -212,19 +595,45 @@ fn eval_expressions(exprs: &[Expression], env: Env) -> EvalResult<Vec<Object>> {
 /// ## 函数表达式求值
 fn apply_function(fun: Object, param_values: Vec<Object>) -> EvalResult {
     match fun {
-        Object::Function(_name, param_names, block, parent_env) => {
-            let env = Rc::new(RefCell::new(Environment::extend(parent_env)));
-            for (i, param) in param_names.iter().enumerate() {
-                env.borrow_mut()
-                    .set(param, param_values.get(i).unwrap_or(&Object::Null).clone())?;
+        Object::Function(fun) => {
+            let params = &fun.params;
+            let rest = params.last().is_some_and(|param| param.rest);
+            let positional = params.len() - rest as usize;
+            let required = params
+                .iter()
+                .filter(|p| p.default.is_none() && !p.rest)
+                .count();
+            check_argument_count(required, positional, rest, param_values.len())?;
+            let env = Rc::new(RefCell::new(Environment::extend(Rc::clone(&fun.env))));
+            let mut values = param_values.into_iter();
+            for param in params {
+                let value = if param.rest {
+                    Object::Array(Rc::new(Container::new(values.by_ref().collect())))
+                } else {
+                    values.next().unwrap_or(Object::Null)
+                };
+                //参数缺省或为null时使用默认值, 默认值可以引用前面的参数
+                let value = match &param.default {
+                    Some(default) if value == Object::Null => {
+                        eval_expression(default, Rc::clone(&env))?
+                    }
+                    _ => value,
+                };
+                env.borrow_mut().set(&param.name, value)?;
             }
-            let evaluated = eval_block_statements(&block, Rc::clone(&env))?;
+            if fun.method {
+                env.borrow_mut().receiver = true;
+            }
+            //函数体与参数同属一个环境
+            let evaluated = eval_block_in_env(&fun.body, Rc::clone(&env))?;
             match evaluated {
                 Object::Return(ret) => Ok(*ret),
+                Object::Break(_) | Object::Continue(_) => Err(loop_control_error(evaluated)),
                 _ => Ok(evaluated),
             }
         }
         Object::Builtin(builtin_fun) => builtin_fun(param_values),
+        Object::Struct(struct_type) => struct_type.instantiate(param_values),
         _ => Err(RuntimeError::NotCallable(fun)),
     }
 }
@@ -254,6 +663,37 @@ fn eval_call_expression(
     let args = eval_expressions(params, Rc::clone(&env))?;
     apply_function(fun, args)
 }
+/// ## 方法调用求值
+///
+/// 优先按接收者类型查找内置方法, 其次是结构体的方法, 最后调用Hash或实例中的同名函数成员
+fn eval_method_call_expression(
+    env: Env,
+    receiver: &Expression,
+    method: &str,
+    params: &[Expression],
+) -> EvalResult {
+    let receiver = eval_expression(receiver, Rc::clone(&env))?;
+    let mut args = eval_expressions(params, Rc::clone(&env))?;
+    if let Some(builtin) = lookup_method(&receiver, method) {
+        args.insert(0, receiver);
+        return apply_function(builtin, args);
+    }
+    if let Object::Instance(instance) = &receiver {
+        if let Some(fun) = instance.struct_type.method(method) {
+            args.insert(0, receiver);
+            return apply_function(fun, args);
+        }
+    }
+    match receiver.get_field(method) {
+        Ok(member) if member != Object::Null => apply_function(member, args),
+        _ => Err(RuntimeError::NoSuchMember(receiver, method.to_string())),
+    }
+}
+/// 赋值目标路径中的一层: a[key] 或 a.field
+enum PathKey<'a> {
+    Index(&'a Expression),
+    Field(&'a str),
+}
 /// # 二元表达式求值
 fn eval_binary_expression(
     operator: &BinaryOperator,
@@ -263,78 +703,122 @@ fn eval_binary_expression(
 ) -> EvalResult {
     match left {
         //变量赋值
-        Expression::Identifier(id) if operator == &BinaryOperator::Assign => {
+        Expression::Identifier(id) if operator.is_assign() => {
             if env.borrow().contains(id) {
-                let new_val = eval_expression(right, Rc::clone(&env))?;
-                env.borrow_mut().set(id, new_val.clone())?;
+                let mut new_val = eval_expression(right, Rc::clone(&env))?;
+                //复合赋值
+                if let Some(compound) = operator.compound_operator() {
+                    let old_val = eval_identifier_expression(Rc::clone(&env), id)?;
+                    new_val = eval_binary_operation(&compound, old_val, new_val)?;
+                }
+                env.borrow().assign(id, new_val.clone())?;
                 Ok(new_val)
             } else {
                 Err(RuntimeError::IdentifierNotFound(id.clone()))
             }
         }
-        //数组/hash索引赋值
-        Expression::Index(arr, key) if operator == &BinaryOperator::Assign => {
-            //只能通过数组名索引来修改
-            if let Expression::Identifier(obj_name) = arr.deref() {
-                let option = env.borrow().get(obj_name);
-                if let Some(obj) = option {
-                    //是否存在
-                    let ref_obj = obj.borrow();
-                    match ref_obj.deref() {
-                        Object::Array(items) => {
-                            let index = eval_expression(key, Rc::clone(&env))?;
-                            //索引是否为整数
-                            if let Object::Integer(i) = index {
-                                let i = i as usize;
-                                if i >= items.borrow().len() {
-                                    return Ok(Object::Null);
-                                }
-                                let val = eval_expression(right, Rc::clone(&env))?;
-                                items.borrow_mut()[i] = val.clone();
-                                return Ok(val);
-                            }
-                        }
-                        Object::Hash(pairs) => {
-                            let index = eval_expression(key, Rc::clone(&env))?;
-                            let hash_key = &HashKey::from_object(&index)?;
-                            let val = eval_expression(right, Rc::clone(&env))?;
-                            let old_val = pairs
-                                .borrow_mut()
-                                .insert(hash_key.clone(), val)
-                                .unwrap_or(Object::Null);
-                            return Ok(old_val);
-                        }
-                        _ => {}
+        //数组/hash索引和成员赋值, 支持嵌套 a[i].j = v 和计算出的容器 f()[k] = v
+        Expression::Index(_, _) | Expression::Field(_, _) if operator.is_assign() => {
+            //展开索引路径, 容器和各层索引从左到右只求值一次
+            let mut keys = vec![];
+            let mut base = left;
+            loop {
+                match base {
+                    Expression::Index(container, key) => {
+                        keys.push(PathKey::Index(key));
+                        base = container;
+                    }
+                    Expression::Field(container, field) => {
+                        keys.push(PathKey::Field(field));
+                        base = container;
                     }
+                    _ => break,
+                }
+            }
+            let container = match base {
+                Expression::Identifier(id) => env
+                    .borrow()
+                    .get(id)
+                    .ok_or_else(|| RuntimeError::IdentifierNotFound(id.clone()))?,
+                _ => Rc::new(RefCell::new(eval_expression(base, Rc::clone(&env))?)),
+            };
+            let keys = keys
+                .into_iter()
+                .rev()
+                .map(|key| match key {
+                    PathKey::Index(key) => eval_expression(key, Rc::clone(&env)),
+                    PathKey::Field(field) => Ok(Object::String(field.to_string())),
+                })
+                .collect::<EvalResult<Vec<_>>>()?;
+            let mut val = eval_expression(right, Rc::clone(&env))?;
+            if let Some(compound) = operator.compound_operator() {
+                let mut old_val = eval_index_expression(&container.borrow(), &keys[0])?;
+                for key in &keys[1..] {
+                    old_val = eval_index_expression(&old_val, key)?;
                 }
+                val = eval_binary_operation(&compound, old_val, val)?;
             }
-            Err(RuntimeError::AssignUnsupported(left.clone(), right.clone()))
+            container.borrow().set_index_path(&keys, val.clone())?;
+            Ok(val)
+        }
+        //逻辑运算, 短路求值
+        _ if operator == &BinaryOperator::And || operator == &BinaryOperator::Or => {
+            let left = eval_expression(left, Rc::clone(&env))?.is_truthy();
+            let result = match operator {
+                BinaryOperator::And if !left => false,
+                BinaryOperator::Or if left => true,
+                _ => eval_expression(right, Rc::clone(&env))?.is_truthy(),
+            };
+            Ok(Object::Boolean(result))
         }
         //普通二元运算
         _ => {
             let left = eval_expression(left, Rc::clone(&env))?;
             let right = eval_expression(right, Rc::clone(&env))?;
-            if let Object::Integer(left) = left {
-                //整数运算
-                if let Object::Integer(right) = right {
-                    return eval_integer_binary_expression(operator, left, right);
-                }
-            } else if let Object::Boolean(left) = left {
-                //布尔运算
-                if let Object::Boolean(right) = right {
-                    return eval_boolean_binary_expression(operator, left, right);
-                }
-            } else if let Object::String(left) = &left {
-                //字符串运算
-                if let Object::String(right) = &right {
-                    return eval_string_binary_expression(operator, left, right);
-                }
-            }
-            Err(RuntimeError::TypeMismatch(operator.clone(), left, right))
+            eval_binary_operation(operator, left, right)
         }
     }
 }
 
+/// ## 对两个值进行二元运算
+fn eval_binary_operation(operator: &BinaryOperator, left: Object, right: Object) -> EvalResult {
+    match (&left, &right) {
+        //整数运算
+        (Object::Integer(l), Object::Integer(r)) => {
+            eval_integer_binary_expression(operator, *l, *r)
+        }
+        //浮点数运算, 整数与浮点数混合时整数提升为浮点数
+        (Object::Float(l), Object::Float(r)) => eval_float_binary_expression(operator, *l, *r),
+        (Object::Integer(l), Object::Float(r)) => {
+            eval_float_binary_expression(operator, *l as f64, *r)
+        }
+        (Object::Float(l), Object::Integer(r)) => {
+            eval_float_binary_expression(operator, *l, *r as f64)
+        }
+        //布尔运算
+        (Object::Boolean(l), Object::Boolean(r)) => {
+            eval_boolean_binary_expression(operator, *l, *r)
+        }
+        //字符串运算
+        (Object::String(l), Object::String(r)) => eval_string_binary_expression(operator, l, r),
+        //字符串与数字拼接, 数字按显示格式转换为字符串
+        (Object::String(l), Object::Integer(_) | Object::Float(_))
+            if operator == &BinaryOperator::Plus =>
+        {
+            Ok(Object::String(format!("{}{}", l, right)))
+        }
+        (Object::Integer(_) | Object::Float(_), Object::String(r))
+            if operator == &BinaryOperator::Plus =>
+        {
+            Ok(Object::String(format!("{}{}", left, r)))
+        }
+        //其他类型只支持按值比较相等
+        _ if operator == &BinaryOperator::Eq => Ok(Object::Boolean(left == right)),
+        _ if operator == &BinaryOperator::NotEq => Ok(Object::Boolean(left != right)),
+        _ => Err(RuntimeError::TypeMismatch(operator.clone(), left, right)),
+    }
+}
+
 fn eval_string_binary_expression(operator: &BinaryOperator, left: &str, right: &str) -> EvalResult {
     match operator {
         BinaryOperator::Plus => {
@@ -358,7 +842,42 @@ fn eval_integer_binary_expression(operator: &BinaryOperator, left: i64, right: i
         BinaryOperator::Plus => Ok(Object::Integer(left + right)),
         BinaryOperator::Minus => Ok(Object::Integer(left - right)),
         BinaryOperator::Mul => Ok(Object::Integer(left * right)),
+        BinaryOperator::Div | BinaryOperator::Mod if right == 0 => Err(RuntimeError::ByZero(
+            operator.clone(),
+            Object::Integer(left),
+            Object::Integer(right),
+        )),
         BinaryOperator::Div => Ok(Object::Integer(left / right)),
+        BinaryOperator::Mod => Ok(Object::Integer(left.wrapping_rem(right))),
+        BinaryOperator::Pow => match u32::try_from(right) {
+            Ok(exp) => left.checked_pow(exp).map(Object::Integer).ok_or_else(|| {
+                RuntimeError::Overflow(
+                    operator.clone(),
+                    Object::Integer(left),
+                    Object::Integer(right),
+                )
+            }),
+            //负指数的结果为浮点数
+            Err(_) => eval_float_binary_expression(operator, left as f64, right as f64),
+        },
+        BinaryOperator::BitAnd => Ok(Object::Integer(left & right)),
+        BinaryOperator::BitOr => Ok(Object::Integer(left | right)),
+        BinaryOperator::BitXor => Ok(Object::Integer(left ^ right)),
+        BinaryOperator::Shl | BinaryOperator::Shr => {
+            let shifted = u32::try_from(right).ok().and_then(|rhs| {
+                if operator == &BinaryOperator::Shl {
+                    left.checked_shl(rhs)
+                } else {
+                    left.checked_shr(rhs)
+                }
+            });
+            shifted
+                .map(Object::Integer)
+                .ok_or(RuntimeError::ShiftOverflow(
+                    Object::Integer(left),
+                    Object::Integer(right),
+                ))
+        }
         BinaryOperator::Gt => Ok(Object::Boolean(left > right)),
         BinaryOperator::Ge => Ok(Object::Boolean(left >= right)),
         BinaryOperator::Lt => Ok(Object::Boolean(left < right)),
@@ -373,6 +892,29 @@ fn eval_integer_binary_expression(operator: &BinaryOperator, left: i64, right: i
     }
 }
 
+/// ## 浮点数二元表达式求值
+fn eval_float_binary_expression(operator: &BinaryOperator, left: f64, right: f64) -> EvalResult {
+    match operator {
+        BinaryOperator::Plus => Ok(Object::Float(left + right)),
+        BinaryOperator::Minus => Ok(Object::Float(left - right)),
+        BinaryOperator::Mul => Ok(Object::Float(left * right)),
+        BinaryOperator::Div => Ok(Object::Float(left / right)),
+        BinaryOperator::Mod => Ok(Object::Float(left % right)),
+        BinaryOperator::Pow => Ok(Object::Float(left.powf(right))),
+        BinaryOperator::Gt => Ok(Object::Boolean(left > right)),
+        BinaryOperator::Ge => Ok(Object::Boolean(left >= right)),
+        BinaryOperator::Lt => Ok(Object::Boolean(left < right)),
+        BinaryOperator::Le => Ok(Object::Boolean(left <= right)),
+        BinaryOperator::Eq => Ok(Object::Boolean(left == right)),
+        BinaryOperator::NotEq => Ok(Object::Boolean(left != right)),
+        _ => Err(RuntimeError::UnknownBinaryOperator(
+            operator.clone(),
+            Object::Float(left),
+            Object::Float(right),
+        )),
+    }
+}
+
 /// ## 布尔二元表达式求值
 fn eval_boolean_binary_expression(
     operator: &BinaryOperator,
@@ -394,38 +936,39 @@ fn eval_boolean_binary_expression(
     }
 }
 
-/// ## object转bool
-fn eval_object_to_bool(object: Object) -> bool {
-    match object {
-        Object::Integer(i) => i != 0,
-        Object::Boolean(bool) => bool,
-        Object::String(str) => !str.trim().is_empty(),
-        Object::_Float(f) => f.ne(&0.0) && !f.is_nan(),
-        Object::Null => false,
-        _ => panic!("错误对象:{}，不能转换为bool", object),
-    }
-}
-
 /// # 一元表达式求职
 fn eval_unary_expression(operator: &UnaryOperator, operand: Object) -> EvalResult {
     match operator {
         UnaryOperator::Not => eval_not_operator_expression(operand),
         UnaryOperator::Neg => eval_neg_operator_expression(operand),
+        UnaryOperator::BitNot => eval_bit_not_operator_expression(operand),
     }
 }
 
 /// ## 取非
 fn eval_not_operator_expression(operand: Object) -> EvalResult {
-    Ok(Object::Boolean(!eval_object_to_bool(operand)))
+    Ok(Object::Boolean(!operand.is_truthy()))
 }
 
 /// ## 取反
 fn eval_neg_operator_expression(operand: Object) -> EvalResult {
     match operand {
         Object::Integer(i) => Ok(Object::Integer(-i)),
+        Object::Float(f) => Ok(Object::Float(-f)),
         _ => Err(RuntimeError::UnknownUnaryOperator(
             UnaryOperator::Neg,
             operand,
         )),
     }
 }
+
+/// ## 按位取反
+fn eval_bit_not_operator_expression(operand: Object) -> EvalResult {
+    match operand {
+        Object::Integer(i) => Ok(Object::Integer(!i)),
+        _ => Err(RuntimeError::UnknownUnaryOperator(
+            UnaryOperator::BitNot,
+            operand,
+        )),
+    }
+}
@@ -1,10 +1,10 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::rc::Rc;
 
 use crate::eval::evaluator::EvalResult;
-use crate::object::{Object, RuntimeError};
-use crate::object::builtins::lookup;
+use crate::object::{equals_once, Object, RuntimeError, Visited};
 
 pub mod evaluator;
 mod test;
@@ -12,20 +12,62 @@ mod test;
 type Env = Rc<RefCell<Environment>>;
 type Val = Rc<RefCell<Object>>;
 
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Clone, Default)]
 pub struct Environment {
     store: HashMap<String, Val>,
+    // const声明的变量名
+    constants: HashSet<String>,
+    // 结构体方法的环境, 其中的this不能赋值
+    receiver: bool,
     outer: Option<Env>,
 }
 
+// 函数对象引用定义它的环境, 环境中又有该函数, 只输出变量名以免无限递归
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Environment")
+            .field("keys", &self.store.keys())
+            .finish()
+    }
+}
+
+// 同样因为函数和环境互相引用, 比较时记录正在比较的环境
+impl PartialEq for Environment {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut vec![])
+    }
+}
+
 impl Environment {
     pub fn new() -> Self {
         Default::default()
     }
 
+    pub fn equals(&self, other: &Environment, visited: &mut Visited) -> bool {
+        let outer_equals = match (&self.outer, &other.outer) {
+            (None, None) => true,
+            (Some(a), Some(b)) => equals_once(Rc::as_ptr(a), Rc::as_ptr(b), visited, |visited| {
+                a.borrow().equals(&b.borrow(), visited)
+            }),
+            _ => false,
+        };
+        self.constants == other.constants
+            && self.receiver == other.receiver
+            && self.store.len() == other.store.len()
+            && self.store.iter().all(|(key, a)| {
+                other
+                    .store
+                    .get(key)
+                    .is_some_and(|b| a.borrow().equals(&b.borrow(), visited))
+            })
+            && outer_equals
+    }
+
     pub fn extend(outer: Env) -> Self {
         Environment {
             store: HashMap::new(),
+            constants: HashSet::new(),
+            receiver: false,
             outer: Some(outer),
         }
     }
@@ -39,13 +81,53 @@ impl Environment {
         }
     }
 
+    /// 在当前环境绑定变量(参数、循环变量等), 与虚拟机一样可以遮蔽内置函数
     pub fn set(&mut self, key: &str, val: Object) -> EvalResult<()> {
-        if lookup(key).is_some() {
+        self.store
+            .insert(key.to_string(), Rc::new(RefCell::new(val)));
+        Ok(())
+    }
+    /// 在当前环境声明变量, 可以遮蔽外层同名变量和内置函数, 同一环境内不能重复声明
+    pub fn declare(&mut self, key: &str, val: Object) -> EvalResult<()> {
+        if self.store.contains_key(key) {
             Err(RuntimeError::VariableHasBeenDeclared(key.to_string()))
         } else {
-            self.store
-                .insert(key.to_string(), Rc::new(RefCell::new(val)));
-            Ok(())
+            self.set(key, val)
+        }
+    }
+    /// 声明const变量, 之后不能再赋值
+    pub fn declare_const(&mut self, key: &str, val: Object) -> EvalResult<()> {
+        self.declare(key, val)?;
+        self.constants.insert(key.to_string());
+        Ok(())
+    }
+    /// 声明与其他变量共用存储的const变量(导入的模块成员)
+    pub fn declare_shared(&mut self, key: &str, val: Val) -> EvalResult<()> {
+        if self.store.contains_key(key) {
+            return Err(RuntimeError::VariableHasBeenDeclared(key.to_string()));
+        }
+        self.store.insert(key.to_string(), val);
+        self.constants.insert(key.to_string());
+        Ok(())
+    }
+    /// 给已声明的变量赋值, 沿外层环境查找并原地修改, 闭包内外共享同一个变量
+    pub fn assign(&self, key: &str, val: Object) -> EvalResult<()> {
+        match self.store.get(key) {
+            Some(_) if self.constants.contains(key) => {
+                Err(RuntimeError::AssignToConstant(key.to_string()))
+            }
+            Some(_) if self.receiver && key == "this" => Err(RuntimeError::CustomErrMsg(format!(
+                "can't assign to {}",
+                key
+            ))),
+            Some(v) => {
+                *v.borrow_mut() = val;
+                Ok(())
+            }
+            None => match &self.outer {
+                Some(outer) => outer.borrow().assign(key, val),
+                None => Err(RuntimeError::IdentifierNotFound(key.to_string())),
+            },
         }
     }
     pub fn contains(&self, key: &str) -> bool {
@@ -1,13 +1,14 @@
 #[cfg(test)]
 mod tests {
     use crate::parser::ast::{
-        BinaryOperator, BlockStatement, Expression, Statement, UnaryOperator,
+        BinaryOperator, BlockStatement, Expression, Parameter, Statement, UnaryOperator,
     };
     use crate::parser::Parser;
     use crate::eval::evaluator;
     use crate::eval::evaluator::{Env, EvalResult};
     use crate::eval::Environment;
-    use crate::object::{HashKey, Object, RuntimeError};
+    use crate::module::ModuleError;
+    use crate::object::{Container, Function, HashKey, Object, RuntimeError};
     use std::cell::RefCell;
     use std::collections::HashMap;
     use std::fmt::{Debug, Display};
@@ -27,6 +28,28 @@ mod tests {
         check_input(&inputs);
     }
     #[test]
+    fn break_and_continue() {
+        let inputs = [(
+            "let f = fn() { while (true) { break typo } }; let r = 0; try { f() } catch e { r = e.kind }; r",
+            Object::String("UndefinedLabel".to_string()),
+        )];
+        check_input(&inputs);
+        check_error(&[
+            (
+                "break",
+                RuntimeError::LoopControlOutsideLoop(Object::Break(None)),
+            ),
+            (
+                "let n = 1; while (true) { break typo }; n",
+                RuntimeError::UndefinedLabel("typo".to_string()),
+            ),
+            (
+                "outer: while (true) { fn() { continue outer }() }",
+                RuntimeError::UndefinedLabel("outer".to_string()),
+            ),
+        ]);
+    }
+    #[test]
     fn test_hash() {
         let mut map = HashMap::new();
         map.insert(
@@ -44,7 +67,7 @@ mod tests {
         let inputs = &[
             (
                 r#"{ "one": 1, "two": 2, "three": 3 }"#,
-                Object::Hash(RefCell::new(map)),
+                Object::Hash(Rc::new(Container::new(map))),
             ),
             (
                 r#"let map = { "one": 1, "two": 2, "three": 3 }; map["one"] = 10; map["one"]"#,
@@ -70,11 +93,11 @@ mod tests {
     fn test_array() {
         let inputs = [(
             "[1, 2*2, 3+3]",
-            Object::Array(RefCell::new(vec![
+            Object::Array(Rc::new(Container::new(vec![
                 Object::Integer(1),
                 Object::Integer(4),
                 Object::Integer(6),
-            ])),
+            ]))),
         )];
         check_input(&inputs);
     }
@@ -100,6 +123,78 @@ mod tests {
         check_error(&inputs);
     }
 
+    #[test]
+    fn struct_types() {
+        check_error(&[
+            (
+                "struct P { x; fn m() { this = 5 } }; P(1).m()",
+                RuntimeError::CustomErrMsg("can't assign to this".to_string()),
+            ),
+            (
+                "struct P { x }; P = 1",
+                RuntimeError::AssignToConstant("P".to_string()),
+            ),
+        ]);
+    }
+    #[test]
+    fn modules() {
+        check_error(&[
+            (
+                "import \"res/modules/cycle_a.my\" as a",
+                RuntimeError::Import(ModuleError::Cycle(
+                    ["cycle_a", "cycle_b", "cycle_a"]
+                        .map(|name| {
+                            let file = format!("res/modules/{}.my", name);
+                            let file = std::path::Path::new(&file).canonicalize().unwrap();
+                            file.display().to_string()
+                        })
+                        .to_vec(),
+                )),
+            ),
+            (
+                "import \"res/modules/counter.my\" as c; c = 1",
+                RuntimeError::AssignToConstant("c".to_string()),
+            ),
+            (
+                "import { n } from \"res/modules/counter.my\"; n += 1",
+                RuntimeError::AssignToConstant("n".to_string()),
+            ),
+        ]);
+    }
+    #[test]
+    fn constants() {
+        check_error(&[
+            (
+                "const a = 1; a = 2",
+                RuntimeError::AssignToConstant("a".to_string()),
+            ),
+            (
+                "const a = 1; let f = fn() { a += 1 }; f()",
+                RuntimeError::AssignToConstant("a".to_string()),
+            ),
+        ]);
+    }
+    #[test]
+    fn block_scopes() {
+        check_error(&[
+            (
+                "let a = 1; let a = 2;",
+                RuntimeError::VariableHasBeenDeclared("a".to_string()),
+            ),
+            (
+                "let f = fn(a) { let a = 1 }; f(0)",
+                RuntimeError::VariableHasBeenDeclared("a".to_string()),
+            ),
+            (
+                "if (true) { let b = 1; let b = 2 }",
+                RuntimeError::VariableHasBeenDeclared("b".to_string()),
+            ),
+            (
+                "for i in 0..3 { i }; i",
+                RuntimeError::IdentifierNotFound("i".to_string()),
+            ),
+        ]);
+    }
     #[test]
     fn test_closure() {
         let inputs = [(
@@ -147,8 +242,9 @@ mod tests {
     fn test_function_object() {
         let inputs = [(
             "fn(x) {x+2;}",
-            Object::Function(None,
-                vec!["x".to_string()],
+            Object::Function(Rc::new(Function::new(
+                None,
+                vec![Parameter::new("x".to_string())],
                 BlockStatement {
                     statements: vec![Statement::Expression(Expression::Binary(
                         BinaryOperator::Plus,
@@ -157,7 +253,7 @@ mod tests {
                     ))],
                 },
                 Rc::new(RefCell::new(Environment::new())),
-            ),
+            ))),
         )];
         check_input(&inputs);
     }
@@ -167,7 +263,6 @@ mod tests {
         let inputs = [("let a = 1; a = 2; a", Object::Integer(2))];
         check_input(&inputs);
     }
-
     #[test]
     fn test_let_statement() {
         let inputs = [
@@ -252,6 +347,9 @@ mod tests {
             ("if(1>2) { 10 }", Object::Null),
             ("if(1<2) { 10 } else { 20 }", Object::Integer(10)),
             ("if(1>2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if(1>2) { 10 } else if(1<2) { 20 } else { 30 }", Object::Integer(20)),
+            ("if 1>2 { 10 } else if 2<1 { 20 } else { 30 }", Object::Integer(30)),
+            ("if 1>2 { 10 } else if 2<1 { 20 }", Object::Null),
         ];
         check_input(&inputs);
     }
@@ -269,6 +367,10 @@ mod tests {
             ("1>2", Object::Boolean(false)),
             ("1<1", Object::Boolean(false)),
             ("1>1", Object::Boolean(false)),
+            ("1>=1", Object::Boolean(true)),
+            ("1<=0", Object::Boolean(false)),
+            ("2 >= 1.5", Object::Boolean(true)),
+            ("1.5 <= 1.5", Object::Boolean(true)),
             ("1==1", Object::Boolean(true)),
             ("1!=1", Object::Boolean(false)),
             ("1==2", Object::Boolean(false)),
@@ -285,6 +387,16 @@ mod tests {
         check_input(&tests)
     }
 
+    #[test]
+    fn test_eval_logical_operator() {
+        let tests = [
+            // 短路: 右侧不会被求值
+            ("false && undefined_fn()", Object::Boolean(false)),
+            ("true || undefined_fn()", Object::Boolean(true)),
+        ];
+        check_input(&tests);
+    }
+
     #[test]
     fn test_eval_integer_expression() {
         let inputs = [
@@ -332,7 +444,7 @@ mod tests {
                     test_object(&err, expected, i);
                 }
                 Ok(evaluated) => {
-                    eprintln!("unexpected: input: {}, evaluated: {}", input, evaluated)
+                    panic!("unexpected: input: {}, evaluated: {}", input, evaluated)
                 }
             }
         }
@@ -350,7 +462,7 @@ mod tests {
     }
 
     fn read_from_file(filename: &str) -> String {
-        let path = format!(r"/Users/l/Code/RustroverProjects/emmmm/res/{}", filename);
+        let path = format!("{}/res/{}", env!("CARGO_MANIFEST_DIR"), filename);
         std::fs::read_to_string(path).unwrap()
     }
 }
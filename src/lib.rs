@@ -15,9 +15,11 @@ use crate::vm::{Globals, Vm};
 pub mod benchmark;
 mod compiler;
 mod eval;
+mod module;
 mod object;
 mod parser;
 pub mod repl;
+mod test;
 mod vm;
 
 fn create_rc_ref_cell<T>(t: T) -> Rc<RefCell<T>> {
@@ -0,0 +1,137 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::{env, fs, iter};
+
+use crate::eval::evaluator::EvalResult;
+use crate::object::{Module, Object, RuntimeError};
+use crate::parser::ast::Program;
+use crate::parser::Parser;
+
+/// 模块查找路径的环境变量, 多个路径用系统的路径分隔符隔开
+const SEARCH_PATH_VAR: &str = "ALIAN_PATH";
+
+/// 导入模块的错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModuleError {
+    NotFound(String),
+    /// 循环导入, 依次为导入链上的文件
+    Cycle(Vec<String>),
+    /// 模块文件有语法错误(文件, 第一个错误)
+    Syntax(String, String),
+}
+
+impl Display for ModuleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModuleError::NotFound(path) => write!(f, "module `{}` not found", path),
+            ModuleError::Cycle(files) => write!(f, "import cycle: {}", files.join(" -> ")),
+            ModuleError::Syntax(file, err) => write!(f, "syntax error in {}: {}", file, err),
+        }
+    }
+}
+
+/// 模块加载器, 编译器在编译时、求值器在运行时导入模块
+struct Loader {
+    search_paths: Vec<PathBuf>,
+    // 正在导入的文件, 最后一个为当前文件, 相对路径以它所在的目录为准
+    loading: Vec<PathBuf>,
+    // 求值器已导入的模块
+    modules: HashMap<PathBuf, Object>,
+}
+
+thread_local! {
+    static LOADER: RefCell<Loader> = RefCell::new(Loader {
+        search_paths: env::var_os(SEARCH_PATH_VAR)
+            .map(|paths| env::split_paths(&paths).collect())
+            .unwrap_or_default(),
+        loading: vec![],
+        modules: HashMap::new(),
+    });
+}
+
+/// 设置运行的文件, 没有时相对路径以当前目录为准
+pub fn set_main_file(file: &str) {
+    let file = Path::new(file);
+    let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+    LOADER.with(|loader| loader.borrow_mut().loading = vec![file]);
+}
+
+/// 查找模块文件: 先相对于当前文件所在的目录, 再依次在各个查找路径中查找
+pub fn resolve(path: &str) -> Result<PathBuf, ModuleError> {
+    LOADER.with(|loader| {
+        let loader = loader.borrow();
+        let base = loader
+            .loading
+            .last()
+            .and_then(|file| file.parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        iter::once(&base)
+            .chain(&loader.search_paths)
+            .map(|dir| dir.join(path))
+            .find(|file| file.is_file())
+            .and_then(|file| file.canonicalize().ok())
+            .ok_or_else(|| ModuleError::NotFound(path.to_string()))
+    })
+}
+
+/// 开始导入模块: 检查循环导入, 读取并解析文件, 导入完成后须调用leave
+pub fn enter(file: &Path) -> Result<Program, ModuleError> {
+    LOADER.with(|loader| {
+        let mut loader = loader.borrow_mut();
+        if let Some(pos) = loader.loading.iter().position(|f| f == file) {
+            let files = loader.loading[pos..]
+                .iter()
+                .map(PathBuf::as_path)
+                .chain(iter::once(file))
+                .map(|f| f.display().to_string())
+                .collect();
+            return Err(ModuleError::Cycle(files));
+        }
+        let input = fs::read_to_string(file)
+            .map_err(|_| ModuleError::NotFound(file.display().to_string()))?;
+        let mut parser = Parser::from(&input);
+        let program = parser.parse_program();
+        if let Some(err) = parser.errors().first() {
+            return Err(ModuleError::Syntax(
+                file.display().to_string(),
+                format!("{:?}", err),
+            ));
+        }
+        loader.loading.push(file.to_path_buf());
+        Ok(program)
+    })
+}
+
+/// 模块导入完成
+pub fn leave() {
+    LOADER.with(|loader| loader.borrow_mut().loading.pop());
+}
+
+/// 模块名, 即不含扩展名的文件名
+pub fn module_name(file: &Path) -> String {
+    file.file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// 求值器导入模块, 每个文件只执行一次, run执行模块并返回导出变量的存储
+pub fn import<F>(path: &str, run: F) -> EvalResult
+where
+    F: FnOnce(&Program) -> EvalResult<Vec<(String, Rc<RefCell<Object>>)>>,
+{
+    let file = resolve(path).map_err(RuntimeError::Import)?;
+    let cached = LOADER.with(|loader| loader.borrow().modules.get(&file).cloned());
+    if let Some(module) = cached {
+        return Ok(module);
+    }
+    let program = enter(&file).map_err(RuntimeError::Import)?;
+    let members = run(&program);
+    leave();
+    let module = Object::Module(Rc::new(Module::shared(module_name(&file), members?)));
+    LOADER.with(|loader| loader.borrow_mut().modules.insert(file, module.clone()));
+    Ok(module)
+}
@@ -1,7 +1,7 @@
-use std::cell::RefCell;
+use std::rc::Rc;
 
 use crate::eval::evaluator::EvalResult;
-use crate::object::{Object, RuntimeError};
+use crate::object::{BuiltinFunction, Container, Object, RuntimeError};
 use std::time::SystemTime;
 
 macro_rules! builtin {
@@ -27,6 +27,12 @@ pub const BUILTINS: &[Builtin] = &[
     builtin!(push),
     builtin!(print),
     builtin!(time),
+    // type是关键字, 不能用作函数名
+    Builtin {
+        name: "type",
+        builtin: Object::Builtin(type_of),
+    },
+    builtin!(freeze),
 ];
 
 pub fn lookup(name: &str) -> Option<Object> {
@@ -38,11 +44,32 @@ pub fn lookup(name: &str) -> Option<Object> {
     None
 }
 
+/// 按接收者类型查找内置方法, 调用时接收者作为第一个参数: arr.push(1) 即 push(arr, 1)
+pub fn lookup_method(receiver: &Object, name: &str) -> Option<Object> {
+    let method: BuiltinFunction = match (receiver, name) {
+        (Object::String(_) | Object::Array(_) | Object::Hash(_), "len") => len,
+        (Object::String(_) | Object::Array(_), "first") => first,
+        (Object::String(_) | Object::Array(_), "last") => last,
+        (Object::String(_) | Object::Array(_), "rest") => rest,
+        (Object::String(_) | Object::Array(_), "push") => push,
+        (Object::Hash(_), "keys") => keys,
+        (Object::Hash(_), "values") => values,
+        (Object::Array(_) | Object::Hash(_), "freeze") => freeze,
+        _ => return None,
+    };
+    Some(Object::Builtin(method))
+}
+
 pub fn len(args: Vec<Object>) -> EvalResult {
     assert_argument_count(1, &args)?;
     let len = match &args[0] {
         Object::String(str) => str.len(),
         Object::Array(items) => items.borrow().len(),
+        Object::Hash(pairs) => pairs.borrow().len(),
+        Object::Range(range) => match range.count() {
+            Some(count) => count,
+            None => return Err(RuntimeError::BuiltinUnSupportedArg("len".to_string(), args)),
+        },
         _ => return Err(RuntimeError::BuiltinUnSupportedArg("len".to_string(), args)),
     };
     Ok(Object::Integer(len as i64))
@@ -56,6 +83,7 @@ pub fn first(args: Vec<Object>) -> EvalResult {
             .next()
             .map_or(Object::Null, |c| Object::String(c.to_string())),
         Object::Array(items) => items.borrow().first().unwrap_or(&Object::Null).clone(),
+        Object::Range(range) => range.nth(0).map_or(Object::Null, Object::Integer),
         _ => {
             return Err(RuntimeError::BuiltinUnSupportedArg(
                 "first".to_string(),
@@ -74,6 +102,9 @@ pub fn last(args: Vec<Object>) -> EvalResult {
             .last()
             .map_or(Object::Null, |c| Object::String(c.to_string())),
         Object::Array(items) => items.borrow().last().unwrap_or(&Object::Null).clone(),
+        Object::Range(range) if range.end.is_some() => {
+            range.last().map_or(Object::Null, Object::Integer)
+        }
         _ => {
             return Err(RuntimeError::BuiltinUnSupportedArg(
                 "last".to_string(),
@@ -90,7 +121,7 @@ pub fn rest(args: Vec<Object>) -> EvalResult {
         Object::String(str) => Object::String(str[1..].to_string()),
         Object::Array(items) => {
             let x = items.borrow()[1..].to_vec();
-            Object::Array(RefCell::new(x))
+            Object::Array(Rc::new(Container::new(x)))
         }
         _ => {
             return Err(RuntimeError::BuiltinUnSupportedArg(
@@ -101,10 +132,11 @@ pub fn rest(args: Vec<Object>) -> EvalResult {
     };
     Ok(rest)
 }
-//toto 现在数组暂时还是不可对原数组增减的
+/// 原地追加元素并返回原数组
 pub fn push(args: Vec<Object>) -> EvalResult {
     let push = match &args[0] {
         Object::Array(items) => {
+            args[0].expect_mutable()?;
             for x in args[1..].iter().cloned() {
                 items.borrow_mut().push(x)
             }
@@ -127,6 +159,35 @@ pub fn push(args: Vec<Object>) -> EvalResult {
     Ok(push)
 }
 
+/// Hash的所有键, 顺序不固定
+pub fn keys(args: Vec<Object>) -> EvalResult {
+    assert_argument_count(1, &args)?;
+    match &args[0] {
+        Object::Hash(pairs) => {
+            let keys = pairs.borrow().keys().map(|k| k.to_object()).collect();
+            Ok(Object::Array(Rc::new(Container::new(keys))))
+        }
+        _ => Err(RuntimeError::BuiltinUnSupportedArg(
+            "keys".to_string(),
+            args,
+        )),
+    }
+}
+/// Hash的所有值, 顺序不固定
+pub fn values(args: Vec<Object>) -> EvalResult {
+    assert_argument_count(1, &args)?;
+    match &args[0] {
+        Object::Hash(pairs) => {
+            let values = pairs.borrow().values().cloned().collect();
+            Ok(Object::Array(Rc::new(Container::new(values))))
+        }
+        _ => Err(RuntimeError::BuiltinUnSupportedArg(
+            "values".to_string(),
+            args,
+        )),
+    }
+}
+
 pub fn print(args: Vec<Object>) -> EvalResult {
     args.iter()
         .map(|arg| arg.to_string())
@@ -143,6 +204,18 @@ pub fn time(args: Vec<Object>) -> EvalResult {
     Ok(Object::Integer(time as i64))
 }
 
+/// 类型名, 结构体实例为结构体名
+pub fn type_of(args: Vec<Object>) -> EvalResult {
+    assert_argument_count(1, &args)?;
+    Ok(Object::String(args[0].type_name().to_string()))
+}
+
+/// 冻结数组或Hash并返回它, 之后不能再赋值或push
+pub fn freeze(args: Vec<Object>) -> EvalResult {
+    assert_argument_count(1, &args)?;
+    Ok(args[0].clone().freeze())
+}
+
 fn assert_argument_count(expected: usize, args: &[Object]) -> EvalResult<()> {
     if expected != args.len() {
         Err(RuntimeError::BuiltinIncorrectArgNum(expected, args.len()))
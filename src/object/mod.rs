@@ -1,53 +1,262 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use std::fmt::{Display, Formatter, Result};
+use std::fmt::{Debug, Display, Formatter, Result};
 use std::rc::Rc;
 
 use crate::compiler::code::{print_instructions, Instructions, Opcode};
 use crate::eval::evaluator::EvalResult;
 use crate::eval::Environment;
-use crate::parser::ast::{BinaryOperator, BlockStatement, Expression, UnaryOperator};
+use crate::module::ModuleError;
+use crate::parser::ast::{BinaryOperator, BlockStatement, Expression, Parameter, UnaryOperator};
 
 pub mod builtins;
 
 type BuiltinFunction = fn(Vec<Object>) -> EvalResult<Object>;
 
-#[derive(Debug, PartialEq, Clone)]
+/// 数组和Hash的数据, 与是否已被freeze的标记放在一起
+///
+/// 解引用为内部的 `RefCell`, 读写数据时与 `RefCell` 的用法相同
+pub struct Container<T> {
+    data: RefCell<T>,
+    frozen: Cell<bool>,
+}
+
+impl<T> Container<T> {
+    pub fn new(data: T) -> Self {
+        Container {
+            data: RefCell::new(data),
+            frozen: Cell::new(false),
+        }
+    }
+    pub fn freeze(&self) {
+        self.frozen.set(true)
+    }
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.get()
+    }
+}
+
+impl<T> std::ops::Deref for Container<T> {
+    type Target = RefCell<T>;
+
+    fn deref(&self) -> &RefCell<T> {
+        &self.data
+    }
+}
+
+/// 运行时对象
+///
+/// 数组和Hash是引用类型: 赋值、传参、放入其他容器或被闭包捕获时共享同一份数据,
+/// 通过任一引用所做的修改对其他引用可见, 需要副本时可用切片 `arr[..]`
+///
+/// `==` 和 `!=` 比较的是值(逐个元素比较), 而不是是否为同一个对象,
+/// 循环引用的数组、Hash和实例再次遇到时视为相等, 输出为 `[...]` 或 `{...}`
+#[derive(Clone)]
 pub enum Object {
     Integer(i64),
-    _Float(f64),
+    Float(f64),
     Boolean(bool),
     String(String),
-    Array(RefCell<Vec<Object>>),
-    Hash(RefCell<HashMap<HashKey, Object>>),
-    Function(
-        Option<String>,
-        Vec<String>,
-        BlockStatement,
-        Rc<RefCell<Environment>>,
-    ),
+    Array(Rc<Container<Vec<Object>>>),
+    Hash(Rc<Container<HashMap<HashKey, Object>>>),
+    Range(Range),
+    Function(Rc<Function>),
     // CompiledFunction(Instructions, usize, usize),
-    CompiledFunction(CompiledFunction),
+    CompiledFunction(Rc<CompiledFunction>),
     Builtin(BuiltinFunction),
     /// compiled function, free variables
     // Closure(CompiledFunction, Vec<Rc<Object>>),
     Closure(Closure),
     Return(Box<Object>),
+    /// 循环控制(标签), 仅用于求值器中传递
+    Break(Option<String>),
+    Continue(Option<String>),
+    /// for-in循环的迭代器, 仅用于虚拟机栈上
+    Iterator(Box<RefCell<Iter>>),
+    /// 被闭包捕获的变量, 仅用于虚拟机的局部变量槽位和闭包的自由变量
+    Cell(RefCell<Rc<Object>>),
+    /// 结构体类型, 调用即构造实例
+    Struct(Rc<StructType>),
+    /// 结构体实例, 与数组一样是引用类型
+    Instance(Rc<Instance>),
+    /// 运行时错误被catch捕获后的错误对象(种类, 信息)
+    Error(String, String),
+    /// 导入的模块
+    Module(Rc<Module>),
     Null,
 }
-#[derive(Debug, Clone, PartialEq)]
+/// 求值器中的函数, 保留定义时的环境
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: Option<String>,
+    pub params: Vec<Parameter>,
+    pub body: BlockStatement,
+    pub env: Rc<RefCell<Environment>>,
+    /// 结构体的方法, 第一个参数this不能赋值
+    pub method: bool,
+}
+
+impl Function {
+    pub fn new(
+        name: Option<String>,
+        params: Vec<Parameter>,
+        body: BlockStatement,
+        env: Rc<RefCell<Environment>>,
+    ) -> Self {
+        Self {
+            name,
+            params,
+            body,
+            env,
+            method: false,
+        }
+    }
+    /// 环境中通常有函数自身, 环境按地址记录以免无限递归
+    pub fn equals(&self, other: &Function, visited: &mut Visited) -> bool {
+        self.name == other.name
+            && self.method == other.method
+            && self.params == other.params
+            && self.body == other.body
+            && equals_once(
+                Rc::as_ptr(&self.env),
+                Rc::as_ptr(&other.env),
+                visited,
+                |visited| self.env.borrow().equals(&other.env.borrow(), visited),
+            )
+    }
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut vec![])
+    }
+}
+
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut vec![])
+    }
+}
+
+/// 正在比较的数组、Hash、实例等共享数据的地址对, 再次遇到时说明存在循环引用
+pub type Visited = Vec<(usize, usize)>;
+
+/// 比较两个共享的数据, 同一对已经在比较中时视为相等, 以免循环引用无限递归
+pub fn equals_once<T: ?Sized, U: ?Sized>(
+    a: *const T,
+    b: *const U,
+    visited: &mut Visited,
+    equals: impl FnOnce(&mut Visited) -> bool,
+) -> bool {
+    let pair = (a as *const () as usize, b as *const () as usize);
+    if visited.contains(&pair) {
+        return true;
+    }
+    visited.push(pair);
+    let equal = equals(visited);
+    visited.pop();
+    equal
+}
+
+impl Object {
+    /// 按值比较, visited为正在比较的共享数据
+    pub fn equals(&self, other: &Object, visited: &mut Visited) -> bool {
+        match (self, other) {
+            (Object::Integer(a), Object::Integer(b)) => a == b,
+            (Object::Float(a), Object::Float(b)) => a == b,
+            (Object::Boolean(a), Object::Boolean(b)) => a == b,
+            (Object::String(a), Object::String(b)) => a == b,
+            (Object::Array(a), Object::Array(b)) => {
+                equals_once(Rc::as_ptr(a), Rc::as_ptr(b), visited, |visited| {
+                    let (a, b) = (a.borrow(), b.borrow());
+                    a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| x.equals(y, visited))
+                })
+            }
+            (Object::Hash(a), Object::Hash(b)) => {
+                equals_once(Rc::as_ptr(a), Rc::as_ptr(b), visited, |visited| {
+                    let (a, b) = (a.borrow(), b.borrow());
+                    a.len() == b.len()
+                        && a.iter()
+                            .all(|(key, x)| b.get(key).is_some_and(|y| x.equals(y, visited)))
+                })
+            }
+            (Object::Range(a), Object::Range(b)) => a == b,
+            (Object::Function(a), Object::Function(b)) => Rc::ptr_eq(a, b) || a.equals(b, visited),
+            (Object::CompiledFunction(a), Object::CompiledFunction(b)) => a == b,
+            //函数指针的地址不唯一, 只能判断是否为同一个地址
+            (Object::Builtin(a), Object::Builtin(b)) => std::ptr::fn_addr_eq(*a, *b),
+            (Object::Closure(a), Object::Closure(b)) => a.equals(b, visited),
+            (Object::Return(a), Object::Return(b)) => a.equals(b, visited),
+            (Object::Break(a), Object::Break(b)) => a == b,
+            (Object::Continue(a), Object::Continue(b)) => a == b,
+            (Object::Iterator(a), Object::Iterator(b)) => a == b,
+            (Object::Cell(a), Object::Cell(b)) => {
+                equals_once(a.as_ptr(), b.as_ptr(), visited, |visited| {
+                    a.borrow().equals(&b.borrow(), visited)
+                })
+            }
+            (Object::Struct(a), Object::Struct(b)) => a == b,
+            (Object::Instance(a), Object::Instance(b)) => {
+                equals_once(Rc::as_ptr(a), Rc::as_ptr(b), visited, |visited| {
+                    a.equals(b, visited)
+                })
+            }
+            (Object::Error(a, a_msg), Object::Error(b, b_msg)) => a == b && a_msg == b_msg,
+            (Object::Module(a), Object::Module(b)) => a.equals(b, visited),
+            (Object::Null, Object::Null) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Closure {
-    pub compiled_function: CompiledFunction,
+    pub compiled_function: Rc<CompiledFunction>,
     pub free_variables: Vec<Rc<Object>>,
 }
 
 impl Closure {
-    pub fn new(compiled_function: CompiledFunction, free_variables: Vec<Rc<Object>>) -> Self {
+    pub fn new(compiled_function: Rc<CompiledFunction>, free_variables: Vec<Rc<Object>>) -> Self {
         Self {
             compiled_function,
             free_variables,
         }
     }
+    pub fn equals(&self, other: &Closure, visited: &mut Visited) -> bool {
+        self.compiled_function == other.compiled_function
+            && self.free_variables.len() == other.free_variables.len()
+            && self
+                .free_variables
+                .iter()
+                .zip(other.free_variables.iter())
+                .all(|(x, y)| x.equals(y, visited))
+    }
+}
+
+impl Closure {
+    fn fmt_debug(&self, f: &mut Formatter<'_>, visiting: &Visiting) -> Result {
+        let free_variables = DebugWith(|f| {
+            f.debug_list()
+                .entries(self.free_variables.iter().map(|obj| Nested(obj, visiting)))
+                .finish()
+        });
+        f.debug_struct("Closure")
+            .field("compiled_function", &self.compiled_function)
+            .field("free_variables", &free_variables)
+            .finish()
+    }
+}
+
+impl Debug for Closure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        self.fmt_debug(f, &Visiting::default())
+    }
+}
+
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut vec![])
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -56,6 +265,26 @@ pub struct CompiledFunction {
     pub insts: Rc<Instructions>,
     pub num_locals: usize,
     pub num_parameters: usize,
+    // 有默认值的参数个数
+    pub num_defaults: usize,
+    // 最后一个参数是否为剩余参数
+    pub rest: bool,
+}
+
+/// 检查实参个数: 不少于必须的参数个数, 没有剩余参数时不多于参数个数
+pub fn check_argument_count(
+    required: usize,
+    positional: usize,
+    rest: bool,
+    given: usize,
+) -> EvalResult<()> {
+    if given < required {
+        return Err(RuntimeError::WrongArgumentCount(required, given));
+    }
+    if !rest && given > positional {
+        return Err(RuntimeError::WrongArgumentCount(positional, given));
+    }
+    Ok(())
 }
 
 impl CompiledFunction {
@@ -73,10 +302,334 @@ impl CompiledFunction {
             insts,
             num_locals,
             num_parameters,
+            num_defaults: 0,
+            rest: false,
+        }
+    }
+}
+/// 结构体类型: 字段按声明顺序排列, 字段的下标即实例中的槽位
+#[derive(Debug, Clone)]
+pub struct StructType {
+    pub name: String,
+    pub fields: Vec<String>,
+    /// 方法名和函数, 调用时实例作为第一个参数this
+    pub methods: Vec<(String, Object)>,
+}
+
+impl StructType {
+    pub fn new(name: String, fields: Vec<String>, methods: Vec<(String, Object)>) -> Self {
+        Self {
+            name,
+            fields,
+            methods,
+        }
+    }
+    /// 字段的槽位
+    pub fn slot(&self, field: &str) -> Option<usize> {
+        self.fields.iter().position(|f| f == field)
+    }
+    pub fn method(&self, name: &str) -> Option<Object> {
+        self.methods
+            .iter()
+            .find(|(method, _)| method == name)
+            .map(|(_, fun)| fun.clone())
+    }
+    /// 构造实例, 参数按字段的声明顺序给出
+    pub fn instantiate(self: &Rc<Self>, args: Vec<Object>) -> EvalResult {
+        if args.len() != self.fields.len() {
+            return Err(RuntimeError::WrongArgumentCount(
+                self.fields.len(),
+                args.len(),
+            ));
+        }
+        Ok(Object::Instance(Rc::new(Instance {
+            struct_type: self.clone(),
+            fields: RefCell::new(args),
+        })))
+    }
+}
+
+// 方法可能捕获包含该类型自身的环境, 只比较名称和字段布局
+impl PartialEq for StructType {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.fields == other.fields
+    }
+}
+
+/// 模块: 导出的成员按声明顺序排列
+///
+/// 求值器中成员与模块的变量共用存储, 模块内重新赋值后读取成员得到新值;
+/// 虚拟机中编译器把lib.name解析为模块的全局变量, 成员只是导入完成时的值
+#[derive(Clone)]
+pub struct Module {
+    pub name: String,
+    pub members: Vec<(String, Rc<RefCell<Object>>)>,
+}
+
+impl Module {
+    pub fn new(name: String, members: Vec<(String, Object)>) -> Self {
+        let members = members
+            .into_iter()
+            .map(|(name, value)| (name, Rc::new(RefCell::new(value))))
+            .collect();
+        Self::shared(name, members)
+    }
+    /// 成员与给定的变量共用存储
+    pub fn shared(name: String, members: Vec<(String, Rc<RefCell<Object>>)>) -> Self {
+        Self { name, members }
+    }
+    /// 成员的存储
+    pub fn cell(&self, name: &str) -> Option<Rc<RefCell<Object>>> {
+        self.members
+            .iter()
+            .find(|(member, _)| member == name)
+            .map(|(_, value)| value.clone())
+    }
+    pub fn member(&self, name: &str) -> Option<Object> {
+        self.cell(name).map(|value| value.borrow().clone())
+    }
+    pub fn equals(&self, other: &Module, visited: &mut Visited) -> bool {
+        self.name == other.name
+            && self.members.len() == other.members.len()
+            && self
+                .members
+                .iter()
+                .zip(other.members.iter())
+                .all(|((x, a), (y, b))| x == y && a.borrow().equals(&b.borrow(), visited))
+    }
+}
+
+impl Module {
+    fn fmt_debug(&self, f: &mut Formatter<'_>, visiting: &Visiting) -> Result {
+        let members = DebugWith(|f| {
+            let mut list = f.debug_list();
+            for (name, value) in &self.members {
+                list.entry(&(name, Nested(&value.borrow(), visiting)));
+            }
+            list.finish()
+        });
+        f.debug_struct("Module")
+            .field("name", &self.name)
+            .field("members", &members)
+            .finish()
+    }
+}
+
+impl Debug for Module {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        self.fmt_debug(f, &Visiting::default())
+    }
+}
+
+impl PartialEq for Module {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut vec![])
+    }
+}
+
+pub struct Instance {
+    pub struct_type: Rc<StructType>,
+    pub fields: RefCell<Vec<Object>>,
+}
+
+impl Instance {
+    /// 实例可能引用自身, 调用者负责用equals_once记录实例的地址
+    pub fn equals(&self, other: &Instance, visited: &mut Visited) -> bool {
+        self.struct_type == other.struct_type
+            && self
+                .fields
+                .borrow()
+                .iter()
+                .zip(other.fields.borrow().iter())
+                .all(|(x, y)| x.equals(y, visited))
+    }
+}
+
+impl Instance {
+    fn fmt_debug(&self, f: &mut Formatter<'_>, visiting: &Visiting) -> Result {
+        fmt_once(self, visiting, f, "{...}", |f| {
+            let mut debug = f.debug_struct(&self.struct_type.name);
+            for (name, value) in self
+                .struct_type
+                .fields
+                .iter()
+                .zip(self.fields.borrow().iter())
+            {
+                debug.field(name, &Nested(value, visiting));
+            }
+            debug.finish()
+        })
+    }
+}
+
+impl Debug for Instance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        self.fmt_debug(f, &Visiting::default())
+    }
+}
+
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        equals_once(self, other, &mut vec![], |visited| {
+            self.equals(other, visited)
+        })
+    }
+}
+
+/// 区间 start..end 或 start..=end, 不生成数组
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Range {
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    pub inclusive: bool,
+}
+
+impl Range {
+    /// 区间端点只能是整数, null表示省略
+    pub fn new(start: &Object, end: &Object, inclusive: bool) -> EvalResult<Self> {
+        let bound = |obj: &Object| match obj {
+            Object::Integer(i) => Ok(Some(*i)),
+            Object::Null => Ok(None),
+            _ => Err(RuntimeError::InvalidRangeBound(obj.clone())),
+        };
+        Ok(Self {
+            start: bound(start)?,
+            end: bound(end)?,
+            inclusive,
+        })
+    }
+    /// 区间的第n个值, 省略起点时从0开始
+    pub fn nth(&self, n: usize) -> Option<i64> {
+        let value = self.start.unwrap_or(0).checked_add(n as i64)?;
+        match self.end {
+            Some(end) if value > end || (value == end && !self.inclusive) => None,
+            _ => Some(value),
+        }
+    }
+    /// 元素个数, 省略终点时为None
+    pub fn count(&self) -> Option<usize> {
+        let start = self.start.unwrap_or(0) as i128;
+        let end = self.end? as i128 + if self.inclusive { 1 } else { 0 };
+        Some((end - start).max(0) as usize)
+    }
+    pub fn last(&self) -> Option<i64> {
+        self.count()?.checked_sub(1).and_then(|n| self.nth(n))
+    }
+    /// 截取数组或字符串, 超出长度的部分忽略
+    pub fn slice(&self, obj: &Object) -> Option<Object> {
+        let bounds = |len: usize| {
+            let clamp = |i: i64| i.clamp(0, len as i64) as usize;
+            let start = clamp(self.start.unwrap_or(0));
+            let end = match self.end {
+                None => len,
+                Some(end) if self.inclusive => clamp(end.saturating_add(1)),
+                Some(end) => clamp(end),
+            };
+            (start, end.max(start))
+        };
+        match obj {
+            Object::Array(items) => {
+                let items = items.borrow();
+                let (start, end) = bounds(items.len());
+                Some(Object::Array(Rc::new(Container::new(
+                    items[start..end].to_vec(),
+                ))))
+            }
+            Object::String(str) => {
+                let (start, end) = bounds(str.chars().count());
+                Some(Object::String(
+                    str.chars().skip(start).take(end - start).collect(),
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Display for Range {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if let Some(start) = self.start {
+            write!(f, "{}", start)?;
+        }
+        write!(f, "{}", if self.inclusive { "..=" } else { ".." })?;
+        if let Some(end) = self.end {
+            write!(f, "{}", end)?;
         }
+        Ok(())
     }
 }
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+
+/// 迭代器, 依次产出(索引/键, 元素/值)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Iter {
+    source: Rc<Object>,
+    // Hash迭代开始时的键, 循环中修改Hash不影响迭代
+    keys: Vec<HashKey>,
+    // 已迭代的元素个数
+    index: usize,
+    // 字符串的字节偏移
+    offset: usize,
+}
+
+impl Iter {
+    pub fn new(source: Rc<Object>) -> EvalResult<Self> {
+        let keys = match source.as_ref() {
+            //按键排序, 遍历顺序与插入顺序和运行环境无关
+            Object::Hash(pairs) => {
+                let mut keys: Vec<_> = pairs.borrow().keys().cloned().collect();
+                keys.sort();
+                keys
+            }
+            Object::Array(_) | Object::String(_) | Object::Range(_) => vec![],
+            _ => return Err(RuntimeError::NotIterable(Object::clone(&source))),
+        };
+        Ok(Self {
+            source,
+            keys,
+            index: 0,
+            offset: 0,
+        })
+    }
+    /// 单变量迭代: Hash产出键, 其余产出元素
+    pub fn next_value(&mut self) -> Option<Object> {
+        let is_hash = matches!(self.source.as_ref(), Object::Hash(_));
+        self.next_pair()
+            .map(|(key, value)| if is_hash { key } else { value })
+    }
+    /// 双变量迭代: (索引, 元素)或(键, 值)
+    pub fn next_pair(&mut self) -> Option<(Object, Object)> {
+        let index = self.index;
+        let pair = match self.source.as_ref() {
+            Object::Array(items) => {
+                let item = items.borrow().get(index).cloned()?;
+                (Object::Integer(index as i64), item)
+            }
+            Object::String(string) => {
+                let ch = string[self.offset..].chars().next()?;
+                self.offset += ch.len_utf8();
+                (
+                    Object::Integer(index as i64),
+                    Object::String(ch.to_string()),
+                )
+            }
+            Object::Range(range) => {
+                let value = range.nth(index)?;
+                (Object::Integer(index as i64), Object::Integer(value))
+            }
+            Object::Hash(pairs) => {
+                let key = self.keys.get(index)?;
+                let value = pairs.borrow().get(key).cloned().unwrap_or(Object::Null);
+                (key.to_object(), value)
+            }
+            _ => return None,
+        };
+        self.index += 1;
+        Some(pair)
+    }
+}
+
+/// Hash的键, 排序时整数键在前, 然后是字符串键, 最后是布尔键
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Debug)]
 pub enum HashKey {
     Integer(i64),
     String(String),
@@ -92,6 +645,13 @@ impl HashKey {
             _ => Err(RuntimeError::UnsupportedHashKey(obj.clone())),
         }
     }
+    pub fn to_object(&self) -> Object {
+        match self {
+            HashKey::Integer(int) => Object::Integer(*int),
+            HashKey::String(str) => Object::String(str.to_string()),
+            HashKey::Boolean(bool) => Object::Boolean(*bool),
+        }
+    }
 }
 
 impl Display for HashKey {
@@ -114,7 +674,12 @@ pub enum RuntimeError {
     },
     UnSupportedBinOperation(Opcode, Object, Object),
     UnSupportedBinOperator(Opcode),
-    ByZero(Object, Object),
+    /// 除数为0(运算符, 被除数, 除数)
+    ByZero(BinaryOperator, Object, Object),
+    /// 移位位数不在0..64内
+    ShiftOverflow(Object, Object),
+    /// 整数运算结果超出范围
+    Overflow(BinaryOperator, Object, Object),
 
     UnSupportedUnOperation(Opcode, Object),
     UnSupportedIndexOperation(Object, Object),
@@ -138,11 +703,83 @@ pub enum RuntimeError {
     BuiltinIncorrectArgNum(usize, usize),
 
     IndexUnsupported(Object),
-    AssignUnsupported(Expression, Expression),
+    AssignUnsupported(Box<Expression>, Box<Expression>),
 
     UnsupportedHashKey(Object),
+    /// 成员或方法不存在(对象, 名称)
+    NoSuchMember(Object, String),
 
     VariableHasBeenDeclared(String),
+    /// break/continue不在循环内
+    LoopControlOutsideLoop(Object),
+    /// break/continue的标签不是外层循环的标签
+    UndefinedLabel(String),
+    NotIterable(Object),
+    InvalidRangeBound(Object),
+    /// 解构的值类型不符(期望的类型, 值)
+    CannotDestructure(String, Object),
+    /// 展开的值不是数组
+    CannotSpread(Object),
+    /// throw抛出的值没有被catch捕获
+    Thrown(Object),
+    /// 导入模块失败
+    Import(ModuleError),
+    /// 给const变量重新赋值
+    AssignToConstant(String),
+    /// 修改被freeze的值
+    FrozenValue(Object),
+}
+
+impl RuntimeError {
+    /// catch捕获到的值: throw抛出的值原样返回, 其余运行时错误转换为错误对象
+    pub fn into_object(self) -> Object {
+        match self {
+            RuntimeError::Thrown(value) => value,
+            err => Object::Error(err.kind().to_string(), err.to_string()),
+        }
+    }
+    /// 错误的种类, 即catch得到的错误对象的kind, 取枚举的名称, 如ByZero
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RuntimeError::StackNoElement => "StackNoElement",
+            RuntimeError::StackOverflow => "StackOverflow",
+            RuntimeError::ArrayOutOfBound { .. } => "ArrayOutOfBound",
+            RuntimeError::UnSupportedBinOperation(..) => "UnSupportedBinOperation",
+            RuntimeError::UnSupportedBinOperator(..) => "UnSupportedBinOperator",
+            RuntimeError::ByZero(..) => "ByZero",
+            RuntimeError::ShiftOverflow(..) => "ShiftOverflow",
+            RuntimeError::Overflow(..) => "Overflow",
+            RuntimeError::UnSupportedUnOperation(..) => "UnSupportedUnOperation",
+            RuntimeError::UnSupportedIndexOperation(..) => "UnSupportedIndexOperation",
+            RuntimeError::UnKnownOpCode(..) => "UnKnownOpCode",
+            RuntimeError::CustomErrMsg(..) => "CustomErrMsg",
+            RuntimeError::WrongArgumentCount(..) => "WrongArgumentCount",
+            RuntimeError::NotFunction(..) => "NotFunction",
+            RuntimeError::TypeMismatch(..) => "TypeMismatch",
+            RuntimeError::UnknownUnaryOperator(..) => "UnknownUnaryOperator",
+            RuntimeError::UnknownBinaryOperator(..) => "UnknownBinaryOperator",
+            RuntimeError::IdentifierNotFound(..) => "IdentifierNotFound",
+            RuntimeError::NotCallable(..) => "NotCallable",
+            RuntimeError::UnsupportedExpression(..) => "UnsupportedExpression",
+            RuntimeError::BuiltinUnSupportedArg(..) => "BuiltinUnSupportedArg",
+            RuntimeError::BuiltinIncorrectArgNum(..) => "BuiltinIncorrectArgNum",
+            RuntimeError::IndexUnsupported(..) => "IndexUnsupported",
+            RuntimeError::AssignUnsupported(..) => "AssignUnsupported",
+            RuntimeError::UnsupportedHashKey(..) => "UnsupportedHashKey",
+            RuntimeError::NoSuchMember(..) => "NoSuchMember",
+            RuntimeError::VariableHasBeenDeclared(..) => "VariableHasBeenDeclared",
+            RuntimeError::LoopControlOutsideLoop(..) => "LoopControlOutsideLoop",
+            RuntimeError::UndefinedLabel(..) => "UndefinedLabel",
+            RuntimeError::NotIterable(..) => "NotIterable",
+            RuntimeError::InvalidRangeBound(..) => "InvalidRangeBound",
+            RuntimeError::CannotDestructure(..) => "CannotDestructure",
+            RuntimeError::CannotSpread(..) => "CannotSpread",
+            RuntimeError::Thrown(..) => "Thrown",
+            RuntimeError::Import(..) => "Import",
+            RuntimeError::AssignToConstant(..) => "AssignToConstant",
+            RuntimeError::FrozenValue(..) => "FrozenValue",
+        }
+    }
 }
 
 impl Display for RuntimeError {
@@ -195,6 +832,9 @@ impl Display for RuntimeError {
             RuntimeError::UnsupportedHashKey(obj) => {
                 write!(f, "can't used as a hash key: {}", obj.type_name())
             }
+            RuntimeError::NoSuchMember(obj, name) => {
+                write!(f, "{} has no member `{}`", obj.type_name(), name)
+            }
             RuntimeError::StackNoElement => write!(f, "stack is empty"),
             RuntimeError::StackOverflow => write!(f, "stack overflow"),
             RuntimeError::ArrayOutOfBound { len, index } => {
@@ -206,7 +846,13 @@ impl Display for RuntimeError {
             RuntimeError::UnSupportedBinOperator(op) => {
                 write!(f, "unsupported binary operator: {}", op)
             }
-            RuntimeError::ByZero(a, b) => write!(f, "by zero: {} / {}", a, b),
+            RuntimeError::ByZero(op, a, b) => write!(f, "by zero: {} {} {}", a, op, b),
+            RuntimeError::ShiftOverflow(a, b) => {
+                write!(f, "shift overflow: {} shifted by {}", a, b)
+            }
+            RuntimeError::Overflow(op, a, b) => {
+                write!(f, "integer overflow: {} {} {}", a, op, b)
+            }
             RuntimeError::UnSupportedUnOperation(op, operand) => {
                 write!(f, "unsupported unary operation: {} {}", op, operand)
             }
@@ -222,61 +868,406 @@ impl Display for RuntimeError {
             RuntimeError::VariableHasBeenDeclared(name) => {
                 write!(f, "variable : {} has been declared", name)
             }
+            RuntimeError::LoopControlOutsideLoop(control) => {
+                write!(f, "`{}` outside of loop", control)
+            }
+            RuntimeError::UndefinedLabel(label) => write!(f, "undefined label `{}`", label),
+            RuntimeError::NotIterable(obj) => write!(f, "{} is not iterable", obj.type_name()),
+            RuntimeError::CannotDestructure(expected, obj) => {
+                write!(f, "can't destructure {} as {}", obj.type_name(), expected)
+            }
+            RuntimeError::CannotSpread(obj) => write!(f, "can't spread {}", obj.type_name()),
+            RuntimeError::Thrown(value) => write!(f, "uncaught exception: {}", value),
+            RuntimeError::Import(err) => write!(f, "{}", err),
+            RuntimeError::AssignToConstant(name) => {
+                write!(f, "can't assign to constant: {}", name)
+            }
+            RuntimeError::FrozenValue(obj) => {
+                write!(f, "can't modify frozen {}", obj.type_name())
+            }
+            RuntimeError::InvalidRangeBound(obj) => {
+                write!(f, "range bound must be integer, got {}", obj.type_name())
+            }
         }
     }
 }
 
 impl Object {
+    /// 条件判断时的真值: false, null, 0, 0.0, NaN, 空白字符串为假, 其余为真
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Object::Boolean(bool) => *bool,
+            Object::Integer(i) => *i != 0,
+            Object::Float(f) => f.ne(&0.0) && !f.is_nan(),
+            Object::String(str) => !str.trim().is_empty(),
+            Object::Null => false,
+            _ => true,
+        }
+    }
+    /// 成员访问 obj.field, 即以字符串为键读取Hash, 不存在时为null
+    pub fn get_field(&self, name: &str) -> EvalResult {
+        match self {
+            Object::Hash(pairs) => {
+                let key = HashKey::String(name.to_string());
+                Ok(pairs.borrow().get(&key).cloned().unwrap_or(Object::Null))
+            }
+            Object::Instance(instance) => match instance.struct_type.slot(name) {
+                Some(slot) => Ok(instance.fields.borrow()[slot].clone()),
+                None => Err(RuntimeError::NoSuchMember(self.clone(), name.to_string())),
+            },
+            Object::Error(kind, _) if name == "kind" => Ok(Object::String(kind.clone())),
+            Object::Error(_, message) if name == "message" => Ok(Object::String(message.clone())),
+            Object::Module(module) => module
+                .member(name)
+                .ok_or_else(|| RuntimeError::NoSuchMember(self.clone(), name.to_string())),
+            _ => Err(RuntimeError::NoSuchMember(self.clone(), name.to_string())),
+        }
+    }
+    /// 解构 [a, b]: 值须为至少有min_len个元素的数组
+    pub fn expect_array(&self, min_len: usize) -> EvalResult<()> {
+        match self {
+            Object::Array(items) if items.borrow().len() >= min_len => Ok(()),
+            Object::Array(items) => Err(RuntimeError::ArrayOutOfBound {
+                len: items.borrow().len(),
+                index: min_len - 1,
+            }),
+            _ => Err(RuntimeError::CannotDestructure(
+                "ARRAY".to_string(),
+                self.clone(),
+            )),
+        }
+    }
+    /// 解构 {a, b}: 值须为包含各个键的Hash或结构体实例
+    pub fn expect_members<K>(&self, keys: &[K]) -> EvalResult<()>
+    where
+        K: std::borrow::Borrow<Object>,
+    {
+        if !matches!(self, Object::Hash(_) | Object::Instance(_)) {
+            return Err(RuntimeError::CannotDestructure(
+                "HASH".to_string(),
+                self.clone(),
+            ));
+        }
+        for key in keys {
+            let name = match key.borrow() {
+                Object::String(name) => name,
+                key => return Err(RuntimeError::UnsupportedHashKey(key.clone())),
+            };
+            let found = match self {
+                Object::Hash(pairs) => pairs.borrow().contains_key(&HashKey::String(name.clone())),
+                Object::Instance(instance) => instance.struct_type.slot(name).is_some(),
+                _ => false,
+            };
+            if !found {
+                return Err(RuntimeError::NoSuchMember(self.clone(), name.clone()));
+            }
+        }
+        Ok(())
+    }
+    /// 解构 {a = 1}: 读取成员, 结构体实例没有该成员时与Hash一样为null, 以使用默认值
+    pub fn destructure_field(&self, name: &str) -> EvalResult {
+        match self {
+            Object::Instance(instance) if instance.struct_type.slot(name).is_none() => {
+                Ok(Object::Null)
+            }
+            _ => self.get_field(name),
+        }
+    }
+    /// 冻结数组或Hash, 之后不能再修改其元素(只冻结这一层), 其他值原样返回
+    pub fn freeze(self) -> Object {
+        match &self {
+            Object::Array(items) => items.freeze(),
+            Object::Hash(pairs) => pairs.freeze(),
+            _ => {}
+        }
+        self
+    }
+    /// 是否已被冻结
+    pub fn is_frozen(&self) -> bool {
+        match self {
+            Object::Array(items) => items.is_frozen(),
+            Object::Hash(pairs) => pairs.is_frozen(),
+            _ => false,
+        }
+    }
+    /// 修改前检查是否被冻结
+    pub fn expect_mutable(&self) -> EvalResult<()> {
+        if self.is_frozen() {
+            Err(RuntimeError::FrozenValue(self.clone()))
+        } else {
+            Ok(())
+        }
+    }
+    /// 按索引路径原地修改嵌套容器: self[k1][k2]..[kn] = value
+    pub fn set_index_path<K>(&self, keys: &[K], value: Object) -> EvalResult<()>
+    where
+        K: std::borrow::Borrow<Object>,
+    {
+        let (index, rest) = match keys.split_first() {
+            Some((index, rest)) => (index.borrow(), rest),
+            None => return Ok(()),
+        };
+        match (self, index) {
+            (Object::Array(items), Object::Integer(i)) => {
+                let len = items.borrow().len();
+                let i = *i as usize;
+                if i >= len {
+                    return Err(RuntimeError::ArrayOutOfBound { len, index: i });
+                }
+                if rest.is_empty() {
+                    self.expect_mutable()?;
+                    items.borrow_mut()[i] = value;
+                    Ok(())
+                } else {
+                    //先取出内层容器(共享引用)再修改, 避免与外层借用冲突
+                    let inner = items.borrow()[i].clone();
+                    inner.set_index_path(rest, value)
+                }
+            }
+            (Object::Hash(pairs), _) => {
+                let key = HashKey::from_object(index)?;
+                if rest.is_empty() {
+                    self.expect_mutable()?;
+                    pairs.borrow_mut().insert(key, value);
+                    Ok(())
+                } else {
+                    //中间层不存在时视为对null赋值
+                    let inner = pairs.borrow().get(&key).cloned().unwrap_or(Object::Null);
+                    inner.set_index_path(rest, value)
+                }
+            }
+            (Object::Instance(instance), Object::String(field)) => {
+                let slot = instance
+                    .struct_type
+                    .slot(field)
+                    .ok_or_else(|| RuntimeError::NoSuchMember(self.clone(), field.clone()))?;
+                if rest.is_empty() {
+                    instance.fields.borrow_mut()[slot] = value;
+                    Ok(())
+                } else {
+                    let inner = instance.fields.borrow()[slot].clone();
+                    inner.set_index_path(rest, value)
+                }
+            }
+            _ => Err(RuntimeError::UnSupportedIndexOperation(
+                self.clone(),
+                index.clone(),
+            )),
+        }
+    }
     fn type_name(&self) -> &str {
         match self {
             Object::Integer(_) => "INTEGER",
-            // Object::_Float(_) => "FLOAT",
+            Object::Float(_) => "FLOAT",
             Object::Boolean(_) => "BOOLEAN",
             Object::String(_) => "STRING",
             Object::Array(_) => "ARRAY",
             Object::Hash(_) => "HASH",
-            Object::Function(_, _, _, _) => "FUNCTION",
+            Object::Range(_) => "RANGE",
+            Object::Function(_) | Object::Closure(_) => "FUNCTION",
             Object::Builtin(_) => "BUILTIN_FUNCTION",
+            Object::Struct(_) => "STRUCT",
+            Object::Instance(instance) => &instance.struct_type.name,
+            Object::Error(_, _) => "ERROR",
+            Object::Module(_) => "MODULE",
             Object::Null => "NULL",
             _ => "UNKNOWN",
         }
     }
 }
 
+impl Debug for Object {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        self.fmt_debug(f, &Visiting::default())
+    }
+}
+
 impl Display for Object {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        self.fmt_display(f, &Visiting::default())
+    }
+}
+
+/// 正在输出的数组、Hash、实例等共享数据的地址
+type Visiting = RefCell<Vec<usize>>;
+
+/// 输出共享的数据, 已经在输出中时说明存在循环引用, 只输出省略的形式
+fn fmt_once<T: ?Sized>(
+    ptr: *const T,
+    visiting: &Visiting,
+    f: &mut Formatter<'_>,
+    ellipsis: &str,
+    fmt: impl FnOnce(&mut Formatter<'_>) -> Result,
+) -> Result {
+    let addr = ptr as *const () as usize;
+    if visiting.borrow().contains(&addr) {
+        return f.write_str(ellipsis);
+    }
+    visiting.borrow_mut().push(addr);
+    let result = fmt(f);
+    visiting.borrow_mut().pop();
+    result
+}
+
+/// 嵌套在其他对象中的对象, 输出时沿用外层正在输出的地址
+struct Nested<'a>(&'a Object, &'a Visiting);
+
+impl Debug for Nested<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        self.0.fmt_debug(f, self.1)
+    }
+}
+
+impl Display for Nested<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        self.0.fmt_display(f, self.1)
+    }
+}
+
+/// 用闭包输出Debug, 用于debug_struct等的字段
+struct DebugWith<F: Fn(&mut Formatter<'_>) -> Result>(F);
+
+impl<F: Fn(&mut Formatter<'_>) -> Result> Debug for DebugWith<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        (self.0)(f)
+    }
+}
+
+impl Object {
+    fn fmt_debug(&self, f: &mut Formatter<'_>, visiting: &Visiting) -> Result {
+        match self {
+            Object::Integer(int) => f.debug_tuple("Integer").field(int).finish(),
+            Object::Float(float) => f.debug_tuple("Float").field(float).finish(),
+            Object::Boolean(bool) => f.debug_tuple("Boolean").field(bool).finish(),
+            Object::String(string) => f.debug_tuple("String").field(string).finish(),
+            Object::Array(elements) => fmt_once(Rc::as_ptr(elements), visiting, f, "[...]", |f| {
+                let elements = elements.borrow();
+                let elements = DebugWith(|f| {
+                    f.debug_list()
+                        .entries(elements.iter().map(|obj| Nested(obj, visiting)))
+                        .finish()
+                });
+                f.debug_tuple("Array").field(&elements).finish()
+            }),
+            Object::Hash(hash) => fmt_once(Rc::as_ptr(hash), visiting, f, "{...}", |f| {
+                let hash = hash.borrow();
+                let pairs = DebugWith(|f| {
+                    f.debug_map()
+                        .entries(hash.iter().map(|(k, v)| (k, Nested(v, visiting))))
+                        .finish()
+                });
+                f.debug_tuple("Hash").field(&pairs).finish()
+            }),
+            Object::Range(range) => f.debug_tuple("Range").field(range).finish(),
+            Object::Function(fun) => f.debug_tuple("Function").field(fun).finish(),
+            Object::CompiledFunction(cf) => f.debug_tuple("CompiledFunction").field(cf).finish(),
+            Object::Builtin(builtin) => f.debug_tuple("Builtin").field(builtin).finish(),
+            Object::Closure(closure) => {
+                let closure = DebugWith(|f| closure.fmt_debug(f, visiting));
+                f.debug_tuple("Closure").field(&closure).finish()
+            }
+            Object::Return(obj) => f
+                .debug_tuple("Return")
+                .field(&Nested(obj, visiting))
+                .finish(),
+            Object::Break(label) => f.debug_tuple("Break").field(label).finish(),
+            Object::Continue(label) => f.debug_tuple("Continue").field(label).finish(),
+            Object::Iterator(iter) => f.debug_tuple("Iterator").field(iter).finish(),
+            Object::Cell(cell) => fmt_once(cell.as_ptr(), visiting, f, "...", |f| {
+                let value = cell.borrow();
+                f.debug_tuple("Cell")
+                    .field(&Nested(&value, visiting))
+                    .finish()
+            }),
+            // 方法可能捕获类型自身, 只输出类型名
+            Object::Struct(struct_type) => {
+                f.debug_tuple("Struct").field(&struct_type.name).finish()
+            }
+            Object::Instance(instance) => {
+                let instance = DebugWith(|f| instance.fmt_debug(f, visiting));
+                f.debug_tuple("Instance").field(&instance).finish()
+            }
+            Object::Error(kind, message) => {
+                f.debug_tuple("Error").field(kind).field(message).finish()
+            }
+            Object::Module(module) => {
+                let module = DebugWith(|f| module.fmt_debug(f, visiting));
+                f.debug_tuple("Module").field(&module).finish()
+            }
+            Object::Null => f.write_str("Null"),
+        }
+    }
+
+    fn fmt_display(&self, f: &mut Formatter<'_>, visiting: &Visiting) -> Result {
         match self {
             Object::Integer(int) => write!(f, "{}", int),
-            Object::_Float(float) => write!(f, "{}", float),
+            Object::Float(float) => write!(f, "{:?}", float),
             Object::Boolean(bool) => write!(f, "{}", bool),
             Object::String(string) => write!(f, "{}", string),
-            Object::Array(elements) => {
-                let objs = elements
-                    .borrow()
-                    .iter()
-                    .map(|obj| obj.to_string())
-                    .collect::<Vec<String>>()
-                    .join(", ");
-                write!(f, "[{elements}]", elements = objs)
-            }
+            Object::Array(elements) => fmt_once(Rc::as_ptr(elements), visiting, f, "[...]", |f| {
+                write!(f, "[")?;
+                for (i, obj) in elements.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", Nested(obj, visiting))?;
+                }
+                write!(f, "]")
+            }),
+            Object::Range(range) => write!(f, "{}", range),
             Object::Null => write!(f, "null"),
-            Object::Return(obj) => write!(f, "{}", obj),
-            Object::Function(_name, _params, _block, _env) => {
+            Object::Return(obj) => write!(f, "{}", Nested(obj, visiting)),
+            Object::Break(label) => match label {
+                None => write!(f, "break"),
+                Some(label) => write!(f, "break {}", label),
+            },
+            Object::Continue(label) => match label {
+                None => write!(f, "continue"),
+                Some(label) => write!(f, "continue {}", label),
+            },
+            Object::Function(_fun) => {
                 // write!(f, "fun({}){}", params.join(", "), block)
                 write!(f, "Function")
             }
             Object::Builtin(_) => write!(f, "Builtin Function"),
-            Object::Hash(hash) => {
-                let x = hash
-                    .borrow()
-                    .iter()
-                    .map(|(k, v)| format!("{}: {}", k, v))
-                    .collect::<Vec<String>>()
-                    .join(", ");
-                write!(f, "{{{map}}}", map = x)
-            }
+            Object::Hash(hash) => fmt_once(Rc::as_ptr(hash), visiting, f, "{...}", |f| {
+                write!(f, "{{")?;
+                for (i, (k, v)) in hash.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", k, Nested(v, visiting))?;
+                }
+                write!(f, "}}")
+            }),
             Object::CompiledFunction(cf) => write!(f, "{}", print_instructions(&cf.insts)),
             Object::Closure(cl) => write!(f, "{}", print_instructions(&cl.compiled_function.insts)),
+            Object::Iterator(iter) => write!(f, "Iterator({})", iter.borrow().source),
+            Object::Cell(cell) => fmt_once(cell.as_ptr(), visiting, f, "...", |f| {
+                write!(f, "{}", Nested(&cell.borrow(), visiting))
+            }),
+            Object::Struct(struct_type) => write!(f, "struct {}", struct_type.name),
+            Object::Instance(instance) => {
+                fmt_once(Rc::as_ptr(instance), visiting, f, "{...}", |f| {
+                    write!(f, "{} {{ ", instance.struct_type.name)?;
+                    let fields = instance.fields.borrow();
+                    for (i, (name, value)) in instance
+                        .struct_type
+                        .fields
+                        .iter()
+                        .zip(fields.iter())
+                        .enumerate()
+                    {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}: {}", name, Nested(value, visiting))?;
+                    }
+                    write!(f, " }}")
+                })
+            }
+            Object::Error(kind, message) => write!(f, "{}: {}", kind, message),
+            Object::Module(module) => write!(f, "module {}", module.name),
         }
     }
 }
@@ -11,12 +11,34 @@ impl Program {
     pub fn _new(input: &str) -> Self {
         Parser::from(input).parse_program()
     }
+    /// 作为模块时导出的变量名
+    pub fn exports(&self) -> Vec<&str> {
+        self.statements
+            .iter()
+            .flat_map(|statement| match statement {
+                Statement::Export(statement) => match statement.as_ref() {
+                    Statement::Let(name, _)
+                    | Statement::Const(name, _)
+                    | Statement::Function(name, _, _)
+                    | Statement::Struct(name, _, _) => vec![name.as_str()],
+                    Statement::Destructure(pattern, _) => pattern.bindings(),
+                    _ => vec![],
+                },
+                _ => vec![],
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
     // let ident = expr
     Let(String, Expression),
+    // const ident = expr
+    Const(String, Expression),
+    // let [a, b, ...rest] = expr
+    // let {name, age = 0} = expr
+    Destructure(LetPattern, Expression),
     // for (initial; condition; last) { blockStatement }
     // For(
     //     Option<Expression>,
@@ -34,11 +56,108 @@ pub enum Statement {
         Option<Expression>,     // after
         BlockStatement,         // blocks
     ),
-    Function(String, Vec<String>, BlockStatement),
+    // for x in iterable { block }
+    // for (k, v) in iterable { block }
+    ForIn(Vec<String>, Expression, BlockStatement),
+    // while (cond) { block }
+    While(Expression, BlockStatement),
+    // break label
+    Break(Option<String>),
+    // continue label
+    Continue(Option<String>),
+    // label: for/while
+    Labeled(String, Box<Statement>),
+    Function(String, Vec<Parameter>, BlockStatement),
+    // throw expr
+    Throw(Expression),
+    // try { block } catch (e) { block } finally { block }
+    Try(
+        BlockStatement,
+        Option<(Option<String>, BlockStatement)>, // catch
+        Option<BlockStatement>,                   // finally
+    ),
+    // import "path" as name
+    // import { name, name as alias } from "path"
+    Import(String, Import),
+    // export let/const/fn/struct
+    Export(Box<Statement>),
+    // { block }
+    Block(BlockStatement),
+    // struct Name { field, ...; fn method(args) { block } }
+    Struct(
+        String,
+        Vec<String>,
+        Vec<(String, Vec<Parameter>, BlockStatement)>,
+    ),
     //
     Expression(Expression),
 }
 
+/// 函数参数
+#[derive(Debug, PartialEq, Clone)]
+pub struct Parameter {
+    pub name: String,
+    // 默认值 b = 2, 参数缺省或为null时使用
+    pub default: Option<Expression>,
+    // 剩余参数 ...rest, 收集多出的参数
+    pub rest: bool,
+}
+
+impl Parameter {
+    pub fn new(name: String) -> Self {
+        Parameter {
+            name,
+            default: None,
+            rest: false,
+        }
+    }
+}
+
+/// let解构的模式
+#[derive(Debug, PartialEq, Clone)]
+pub enum LetPattern {
+    // 变量名
+    Name(String),
+    // [p1, p2 = default, ...rest]
+    Array(Vec<(LetPattern, Option<Expression>)>, Option<String>),
+    // {key, key: p, key = default}
+    Hash(Vec<(String, LetPattern, Option<Expression>)>),
+}
+
+impl LetPattern {
+    /// 数组模式要求的最少元素个数, 最后一个没有默认值的元素及其之前的都必须存在
+    pub fn required_len(items: &[(LetPattern, Option<Expression>)]) -> usize {
+        items
+            .iter()
+            .rposition(|(_, default)| default.is_none())
+            .map_or(0, |i| i + 1)
+    }
+    /// 模式中绑定的变量
+    pub fn bindings(&self) -> Vec<&str> {
+        match self {
+            LetPattern::Name(name) => vec![name],
+            LetPattern::Array(items, rest) => items
+                .iter()
+                .flat_map(|(item, _)| item.bindings())
+                .chain(rest.as_deref())
+                .collect(),
+            LetPattern::Hash(pairs) => pairs
+                .iter()
+                .flat_map(|(_, pattern, _)| pattern.bindings())
+                .collect(),
+        }
+    }
+}
+
+/// 导入的内容
+#[derive(Debug, PartialEq, Clone)]
+pub enum Import {
+    /// 整个模块绑定到一个变量
+    Module(String),
+    /// 模块的部分成员(成员名, 变量名)
+    Names(Vec<(String, String)>),
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct BlockStatement {
     pub statements: Vec<Statement>,
@@ -54,33 +173,89 @@ pub enum Expression {
     FloatLiteral(f64),
     // 字符串字面量
     StringLiteral(String),
+    // 模板字符串 `a ${b}`, 各片段的值转为字符串后拼接
+    Template(Vec<Expression>),
     // 布尔值字面量
     BoolLiteral(bool),
     // 函数字面量
-    FunctionLiteral(Vec<String>, BlockStatement),
+    FunctionLiteral(Vec<Parameter>, BlockStatement),
     // 数组字面量
     ArrayLiteral(Vec<Expression>),
     // 索引表达式
     Index(Box<Expression>, Box<Expression>),
     // 映射表
     HashLiteral(Vec<(Expression, Expression)>),
+    // 展开 ...expr, 只能用于调用参数和数组字面量
+    Spread(Box<Expression>),
 
     // 一元表达式
     Unary(UnaryOperator, Box<Expression>),
     // 二元表达式
     Binary(BinaryOperator, Box<Expression>, Box<Expression>),
 
+    // 区间表达式, (起点, 终点, 是否包含终点)
+    Range(Option<Box<Expression>>, Option<Box<Expression>>, bool),
     // if表达式
     If(Box<Expression>, BlockStatement, Option<BlockStatement>),
     // 函数调用表达式, (函数, 参数)
     Call(Box<Expression>, Vec<Expression>),
+    // 成员访问 obj.field, 即 obj["field"]
+    Field(Box<Expression>, String),
+    // 方法调用 value.method(args), (接收者, 方法名, 参数)
+    MethodCall(Box<Expression>, String, Vec<Expression>),
+    // match表达式, (被匹配的值, 各分支)
+    Match(Box<Expression>, Vec<MatchArm>),
     //
 }
 
+/// match分支: pattern if guard => body
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: BlockStatement,
+}
+
+/// 模式
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    // _ 匹配任意值
+    Wildcard,
+    // 字面量, 按 == 比较
+    Literal(Expression),
+    // 变量名, 匹配任意值并绑定
+    Binding(String),
+    // p1 | p2, 任一模式匹配即可
+    Or(Vec<Pattern>),
+    // [p1, p2], 长度相同且各元素匹配
+    Array(Vec<Pattern>),
+    // {key: p}, 键存在且值匹配
+    Hash(Vec<(Expression, Pattern)>),
+}
+
+impl Pattern {
+    /// 模式中绑定的变量, 多选模式的各分支绑定相同的变量, 取第一个分支
+    pub fn bindings(&self) -> Vec<&str> {
+        match self {
+            Pattern::Wildcard | Pattern::Literal(_) => vec![],
+            Pattern::Binding(name) => vec![name],
+            Pattern::Or(alternatives) => alternatives
+                .first()
+                .map_or(vec![], |pattern| pattern.bindings()),
+            Pattern::Array(items) => items.iter().flat_map(|item| item.bindings()).collect(),
+            Pattern::Hash(pairs) => pairs
+                .iter()
+                .flat_map(|(_, pattern)| pattern.bindings())
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum UnaryOperator {
     Not,
     Neg,
+    BitNot,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -89,6 +264,15 @@ pub enum BinaryOperator {
     Minus,
     Mul,
     Div,
+    Mod,
+    Pow,
+
+    // 位运算
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
 
     Gt,
     Ge,
@@ -97,7 +281,41 @@ pub enum BinaryOperator {
     Eq,
     NotEq,
 
+    And,
+    Or,
+
     Assign,
+    // 复合赋值
+    PlusAssign,
+    MinusAssign,
+    MulAssign,
+    DivAssign,
+    ModAssign,
+}
+
+impl Expression {
+    /// 展开表达式 ...expr
+    pub fn is_spread(&self) -> bool {
+        matches!(self, Expression::Spread(_))
+    }
+}
+
+impl BinaryOperator {
+    /// 复合赋值对应的运算符, 如 += 对应 +
+    pub fn compound_operator(&self) -> Option<BinaryOperator> {
+        match self {
+            BinaryOperator::PlusAssign => Some(BinaryOperator::Plus),
+            BinaryOperator::MinusAssign => Some(BinaryOperator::Minus),
+            BinaryOperator::MulAssign => Some(BinaryOperator::Mul),
+            BinaryOperator::DivAssign => Some(BinaryOperator::Div),
+            BinaryOperator::ModAssign => Some(BinaryOperator::Mod),
+            _ => None,
+        }
+    }
+    /// 赋值或复合赋值
+    pub fn is_assign(&self) -> bool {
+        self == &BinaryOperator::Assign || self.compound_operator().is_some()
+    }
 }
 
 impl Display for Program {
@@ -123,6 +341,8 @@ impl Display for Statement {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match &self {
             Statement::Let(name, val) => write!(f, "let {} = {}; ", name, val),
+            Statement::Const(name, val) => write!(f, "const {} = {}; ", name, val),
+            Statement::Destructure(pattern, val) => write!(f, "let {} = {}; ", pattern, val),
             Statement::Return(opt) => {
                 let expression = opt
                     .clone()
@@ -150,13 +370,63 @@ impl Display for Statement {
                     blocks.to_string()
                 }
             ),
+            Statement::ForIn(names, iterable, blocks) => match names.as_slice() {
+                [name] => write!(f, "for {} in {} {}", name, iterable, blocks),
+                _ => write!(f, "for ({}) in {} {}", names.join(", "), iterable, blocks),
+            },
+            Statement::While(cond, blocks) => write!(f, "while {} {}", cond, blocks),
+            Statement::Break(label) => match label {
+                None => write!(f, "break; "),
+                Some(label) => write!(f, "break {}; ", label),
+            },
+            Statement::Continue(label) => match label {
+                None => write!(f, "continue; "),
+                Some(label) => write!(f, "continue {}; ", label),
+            },
+            Statement::Labeled(label, statement) => write!(f, "{}: {}", label, statement),
             Statement::Function(name, params, blocks) => write!(
                 f,
                 "fn {name}({params}) {blocks}",
                 name = name,
-                params = params.join(", "),
+                params = join_params(params),
                 blocks = blocks
             ),
+            Statement::Throw(value) => write!(f, "throw {}; ", value),
+            Statement::Try(body, catch, finally) => {
+                write!(f, "try {}", body)?;
+                match catch {
+                    Some((Some(name), handler)) => write!(f, " catch ({}) {}", name, handler)?,
+                    Some((None, handler)) => write!(f, " catch {}", handler)?,
+                    None => {}
+                }
+                if let Some(finally) = finally {
+                    write!(f, " finally {}", finally)?;
+                }
+                Ok(())
+            }
+            Statement::Import(path, Import::Module(name)) => {
+                write!(f, "import \"{}\" as {}; ", path, name)
+            }
+            Statement::Import(path, Import::Names(names)) => {
+                let names = names
+                    .iter()
+                    .map(|(name, alias)| match name == alias {
+                        true => name.clone(),
+                        false => format!("{} as {}", name, alias),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "import {{ {} }} from \"{}\"; ", names, path)
+            }
+            Statement::Export(statement) => write!(f, "export {}", statement),
+            Statement::Block(block) => write!(f, "{}", block),
+            Statement::Struct(name, fields, methods) => {
+                write!(f, "struct {} {{ {}", name, fields.join(", "))?;
+                for (method, params, blocks) in methods {
+                    write!(f, "; fn {}({}) {}", method, join_params(params), blocks)?;
+                }
+                write!(f, " }}")
+            }
         }
     }
 }
@@ -167,10 +437,31 @@ impl Display for Expression {
             Expression::IntLiteral(int) => write!(f, "{}", int),
             Expression::FloatLiteral(float) => write!(f, "{}", float),
             Expression::StringLiteral(string) => write!(f, "{}", string),
+            Expression::Template(parts) => {
+                write!(f, "`")?;
+                for part in parts {
+                    match part {
+                        Expression::StringLiteral(string) => write!(f, "{}", string)?,
+                        expr => write!(f, "${{{}}}", expr)?,
+                    }
+                }
+                write!(f, "`")
+            }
             Expression::BoolLiteral(boolean) => write!(f, "{}", boolean),
             Expression::Identifier(id) => write!(f, "{}", id),
             Expression::Unary(pfx, expr) => write!(f, "({}{})", pfx, expr),
             Expression::Binary(ifx, left, right) => write!(f, "({} {} {})", left, ifx, right),
+            Expression::Range(start, end, inclusive) => {
+                write!(f, "(")?;
+                if let Some(start) = start {
+                    write!(f, "{}", start)?;
+                }
+                write!(f, "{}", if *inclusive { "..=" } else { ".." })?;
+                if let Some(end) = end {
+                    write!(f, "{}", end)?;
+                }
+                write!(f, ")")
+            }
             Expression::If(condition, consequence, alternative) => {
                 write!(f, "if {} {}", condition, consequence)?;
                 if let Some(alternative) = alternative {
@@ -181,7 +472,7 @@ impl Display for Expression {
             Expression::FunctionLiteral(params, blocks) => write!(
                 f,
                 "fn({params}) {blocks}",
-                params = params.join(", "),
+                params = join_params(params),
                 blocks = blocks
             ),
             Expression::Call(fun, exprs) => {
@@ -192,6 +483,23 @@ impl Display for Expression {
                     .join(", ");
                 write!(f, "{fun}({exprs})", fun = fun, exprs = exprs)
             }
+            Expression::Field(obj, field) => write!(f, "{}.{}", obj, field),
+            Expression::MethodCall(obj, method, args) => {
+                let args: String = args
+                    .iter()
+                    .map(|arg| arg.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "{}.{}({})", obj, method, args)
+            }
+            Expression::Match(subject, arms) => {
+                let arms = arms
+                    .iter()
+                    .map(|arm| arm.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "match {} {{ {} }}", subject, arms)
+            }
             Expression::ArrayLiteral(elements) => {
                 let exprs: String = elements
                     .iter()
@@ -214,6 +522,101 @@ impl Display for Expression {
                     .join(", ");
                 write!(f, "{{{}}}", r)
             }
+            Expression::Spread(expr) => write!(f, "...{}", expr),
+        }
+    }
+}
+
+impl Display for MatchArm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.pattern)?;
+        if let Some(guard) = &self.guard {
+            write!(f, " if {}", guard)?;
+        }
+        write!(f, " => {}", self.body)
+    }
+}
+
+impl Display for Parameter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if self.rest {
+            write!(f, "...")?;
+        }
+        write!(f, "{}", self.name)?;
+        if let Some(default) = &self.default {
+            write!(f, " = {}", default)?;
+        }
+        Ok(())
+    }
+}
+
+fn join_params(params: &[Parameter]) -> String {
+    params
+        .iter()
+        .map(|param| param.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl Display for LetPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let with_default = |pattern: String, default: &Option<Expression>| match default {
+            Some(default) => format!("{} = {}", pattern, default),
+            None => pattern,
+        };
+        match self {
+            LetPattern::Name(name) => write!(f, "{}", name),
+            LetPattern::Array(items, rest) => {
+                let mut items = items
+                    .iter()
+                    .map(|(item, default)| with_default(item.to_string(), default))
+                    .collect::<Vec<String>>();
+                if let Some(rest) = rest {
+                    items.push(format!("...{}", rest));
+                }
+                write!(f, "[{}]", items.join(", "))
+            }
+            LetPattern::Hash(pairs) => {
+                let pairs = pairs
+                    .iter()
+                    .map(|(key, pattern, default)| {
+                        let pattern = match pattern {
+                            LetPattern::Name(name) if name == key => key.clone(),
+                            _ => format!("{}: {}", key, pattern),
+                        };
+                        with_default(pattern, default)
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "{{{}}}", pairs)
+            }
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let join = |patterns: &[Pattern], sep| {
+            patterns
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect::<Vec<String>>()
+                .join(sep)
+        };
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Literal(literal) => write!(f, "{}", literal),
+            Pattern::Binding(name) => write!(f, "{}", name),
+            Pattern::Or(alternatives) => write!(f, "{}", join(alternatives, " | ")),
+            Pattern::Array(items) => write!(f, "[{}]", join(items, ", ")),
+            Pattern::Hash(pairs) => {
+                let pairs = pairs
+                    .iter()
+                    .map(|(k, v)| format!("{}:{}", k, v))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "{{{}}}", pairs)
+            }
         }
     }
 }
@@ -223,6 +626,7 @@ impl Display for UnaryOperator {
         match self {
             UnaryOperator::Not => write!(f, "!"),
             UnaryOperator::Neg => write!(f, "-"),
+            UnaryOperator::BitNot => write!(f, "~"),
         }
     }
 }
@@ -234,13 +638,27 @@ impl Display for BinaryOperator {
             BinaryOperator::Minus => write!(f, "-"),
             BinaryOperator::Mul => write!(f, "*"),
             BinaryOperator::Div => write!(f, "/"),
+            BinaryOperator::Mod => write!(f, "%"),
+            BinaryOperator::Pow => write!(f, "**"),
+            BinaryOperator::BitAnd => write!(f, "&"),
+            BinaryOperator::BitOr => write!(f, "|"),
+            BinaryOperator::BitXor => write!(f, "^"),
+            BinaryOperator::Shl => write!(f, "<<"),
+            BinaryOperator::Shr => write!(f, ">>"),
             BinaryOperator::Gt => write!(f, ">"),
             BinaryOperator::Ge => write!(f, ">="),
             BinaryOperator::Lt => write!(f, "<"),
             BinaryOperator::Le => write!(f, "<="),
             BinaryOperator::Eq => write!(f, "=="),
             BinaryOperator::NotEq => write!(f, "!="),
+            BinaryOperator::And => write!(f, "&&"),
+            BinaryOperator::Or => write!(f, "||"),
             BinaryOperator::Assign => write!(f, "="),
+            BinaryOperator::PlusAssign => write!(f, "+="),
+            BinaryOperator::MinusAssign => write!(f, "-="),
+            BinaryOperator::MulAssign => write!(f, "*="),
+            BinaryOperator::DivAssign => write!(f, "/="),
+            BinaryOperator::ModAssign => write!(f, "%="),
         }
     }
 }
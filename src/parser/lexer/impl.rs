@@ -2,23 +2,26 @@ use std::iter::Peekable;
 use std::str::Chars;
 
 use crate::parser::lexer::token::*;
-use crate::parser::lexer::{is_digit, is_letter, Lexer};
+use crate::parser::lexer::{is_digit, is_letter, LexError, Lexer};
 
 impl Lexer {
     pub fn new(input: &str) -> Self {
         let chars = input.chars().collect::<Vec<_>>();
         let mut lexer = Self {
-            input: String::from(input),
             position: 0,
             ch: EOF,
             chars,
+            newline: false,
         };
         lexer.read_char();
         lexer
     }
     /// 读取Token
     pub fn parse_token(&mut self) -> Token {
-        self.skip_whitespace();
+        self.newline = false;
+        if let Err(err) = self.skip_whitespace() {
+            return Token::Illegal(err);
+        }
         let token = match self.ch {
             '(' => Token::Lparen,
             ')' => Token::Rparen,
@@ -31,64 +34,91 @@ impl Lexer {
             ':' => Token::Colon,
             ';' => Token::Semicolon,
 
-            '+' => Token::Plus,
-            '-' => Token::Minus,
-            '*' => Token::Asterisk,
+            '+' => self.peek_is_eat_or('=', Token::PlusAssign, Token::Plus),
+            '-' => self.peek_is_eat_or('=', Token::MinusAssign, Token::Minus),
+            '*' => match self.peek_is_eat_or('*', Token::Power, Token::Asterisk) {
+                Token::Asterisk => self.peek_is_eat_or('=', Token::AsteriskAssign, Token::Asterisk),
+                token => token,
+            },
+            '%' => self.peek_is_eat_or('=', Token::PercentAssign, Token::Percent),
             '/' => {
                 if self.peek_char() == &'/' {
                     let line = self.read_line();
                     Token::Comment(line.to_string())
                 } else {
-                    Token::Slash
+                    self.peek_is_eat_or('=', Token::SlashAssign, Token::Slash)
                 }
             }
-            '>' => self.peek_is_or('=', Token::Ge, Token::Gt),
-            '<' => self.peek_is_or('=', Token::Le, Token::Lt),
+            '>' => match self.peek_is_eat_or('>', Token::ShiftRight, Token::Gt) {
+                Token::Gt => self.peek_is_eat_or('=', Token::Ge, Token::Gt),
+                token => token,
+            },
+            '<' => match self.peek_is_eat_or('<', Token::ShiftLeft, Token::Lt) {
+                Token::Lt => self.peek_is_eat_or('=', Token::Le, Token::Lt),
+                token => token,
+            },
             '!' => self.peek_is_eat_or('=', Token::NotEq, Token::Bang),
-            '=' => self.peek_is_eat_or('=', Token::Eq, Token::Assign),
-            '"' | '`' => {
-                //may be string
-                let string = self.read_string();
-                Token::String(string)
-            }
+            '=' => match self.peek_is_eat_or('=', Token::Eq, Token::Assign) {
+                Token::Assign => self.peek_is_eat_or('>', Token::FatArrow, Token::Assign),
+                token => token,
+            },
+            '&' => self.peek_is_eat_or('&', Token::And, Token::BitAnd),
+            '|' => match self.peek_is_eat_or('|', Token::Or, Token::BitOr) {
+                Token::BitOr => self.peek_is_eat_or('>', Token::Pipeline, Token::BitOr),
+                token => token,
+            },
+            '^' => Token::BitXor,
+            '~' => Token::Tilde,
+            '.' => match self.peek_is_eat_or('.', Token::DotDot, Token::Dot) {
+                Token::DotDot => match self.peek_is_eat_or('=', Token::DotDotEq, Token::DotDot) {
+                    Token::DotDot => self.peek_is_eat_or('.', Token::Ellipsis, Token::DotDot),
+                    token => token,
+                },
+                token => token,
+            },
+            '"' => match self.read_string() {
+                Ok(string) => Token::String(string),
+                Err(err) => Token::Illegal(err),
+            },
+            '`' => self.read_template(),
+            'r' if self.peek_char() == &'"' => match self.read_raw_string() {
+                Ok(string) => Token::String(string),
+                Err(err) => Token::Illegal(err),
+            },
             EOF => Token::Eof,
             c => {
                 return if is_letter(c) {
                     //标识符
                     let id = self.read_identifier();
-                    Token::lookup_id(id)
+                    Token::lookup_id(&id)
                 } else if is_digit(c) {
                     //数字
-                    let num = self.read_number();
-                    if num.contains('.') {
-                        Token::Float(num.to_string())
-                    } else {
-                        Token::Int(num.to_string())
-                    }
+                    self.read_number()
                 } else {
                     //非法字符
-                    return Token::Illegal;
+                    self.read_char();
+                    Token::Illegal(LexError::IllegalChar(c))
                 };
             }
         };
         self.read_char();
         token
     }
-    fn read_line(&mut self) -> &str {
+    fn read_line(&mut self) -> String {
         let position = self.position;
-        while self.peek_char() != &'\n' {
+        while !matches!(*self.peek_char(), '\n' | EOF) {
             self.read_char();
         }
-        &self.input[position..self.position]
+        self.chars[position..=self.position].iter().collect()
     }
     //跳过整行
     fn _skip_line(&mut self) {
-        while self.peek_char() != &'\n' {
+        while !matches!(*self.peek_char(), '\n' | EOF) {
             self.read_char();
         }
     }
     //读取标识符
-    fn read_identifier(&mut self) -> &str {
+    fn read_identifier(&mut self) -> String {
         let position = self.position;
         //the first char must be a letter
         if is_letter(self.ch) {
@@ -97,76 +127,269 @@ impl Lexer {
         while is_letter(self.ch) || is_digit(self.ch) {
             self.read_char();
         }
-        &self.input[position..self.position]
+        self.read_from(position)
     }
-    //读取数字
-    fn read_number(&mut self) -> &str {
+    //读取数字: 0xFF 0b1010 0o755 1_000 1.5 1e9 2.5e-3
+    //先读取紧跟的字母、数字和下划线, 再检查格式, 1.2.3 和 12abc 都是错误
+    fn read_number(&mut self) -> Token {
         let position = self.position;
-        while is_digit(self.ch) || self.ch == '.' {
+        let radix = match (self.ch, *self.peek_char()) {
+            ('0', 'x' | 'X') => 16,
+            ('0', 'o' | 'O') => 8,
+            ('0', 'b' | 'B') => 2,
+            _ => 10,
+        };
+        self.read_char();
+        loop {
+            let prev = self.chars[self.position - 1];
+            match self.ch {
+                c if c.is_ascii_alphanumeric() || c == '_' => {}
+                //小数点后必须是数字, 1..2 中的 .. 是区间
+                '.' if radix == 10 && self.peek_char().is_ascii_digit() => {}
+                '+' | '-' if radix == 10 && matches!(prev, 'e' | 'E') => {}
+                _ => break,
+            }
             self.read_char();
         }
-        &self.input[position..self.position]
+        let num = self.read_from(position);
+        let chars = num.chars().collect::<Vec<_>>();
+        let token = if radix == 10 {
+            scan_decimal(&chars).map(|is_float| {
+                if is_float {
+                    Token::Float(num.clone())
+                } else {
+                    Token::Int(num.clone())
+                }
+            })
+        } else {
+            scan_digits(&chars, 2, radix)
+                .filter(|&end| end == chars.len())
+                .map(|_| Token::Int(num.clone()))
+        };
+        token.unwrap_or_else(|| {
+            let (line, column) = self.line_column(position);
+            Token::Illegal(LexError::InvalidNumber(num, line, column))
+        })
+    }
+    //字符下标所在的行和列, 从1开始
+    fn line_column(&self, position: usize) -> (usize, usize) {
+        let before = &self.chars[..position];
+        let line = before.iter().filter(|&&c| c == '\n').count() + 1;
+        let column = before.iter().rev().take_while(|&&c| c != '\n').count() + 1;
+        (line, column)
     }
-    //读取字符串
-    fn read_string(&mut self) -> String {
-        let around_ch = self.ch;
-        self.read_char(); // start with " or `
-        let mut last_char = EOF;
+    //从position到当前位置(不含)的字符
+    fn read_from(&self, position: usize) -> String {
+        self.chars[position..self.position].iter().collect()
+    }
+    //读取字符串, 可以跨行, 换行原样保留
+    fn read_string(&mut self) -> Result<String, LexError> {
+        self.read_char(); // eat "
         let mut result = String::new();
+        //转义错误时继续读到字符串结尾, 之后的Token不受影响
+        let mut error = None;
         loop {
-            if last_char == '\\' {
-                //escape
-                let escaped_str = self.escape_char(self.ch);
-                if escaped_str != EOF {
-                    result.push(escaped_str);
-                    if escaped_str == '\\' {
-                        last_char = '\u{0}';
-                        continue;
-                    }
-                }
-            } else if self.ch == '\\' {
-            } else {
-                if self.ch == around_ch {
-                    break;
-                }
-                result.push(self.ch);
+            match self.ch {
+                '"' => break,
+                EOF => return Err(LexError::UnterminatedString),
+                '\\' => match self.read_escape() {
+                    Ok(c) => result.push(c),
+                    Err(LexError::UnterminatedString) => return Err(LexError::UnterminatedString),
+                    Err(err) => error = error.or(Some(err)),
+                },
+                c => result.push(c),
+            }
+            self.read_char();
+        }
+        error.map_or(Ok(result), Err)
+    }
+    //读取原始字符串r"...", 不处理转义
+    fn read_raw_string(&mut self) -> Result<String, LexError> {
+        self.read_char(); // eat r
+        self.read_char(); // eat "
+        let position = self.position;
+        while self.ch != '"' {
+            if self.ch == EOF {
+                return Err(LexError::UnterminatedString);
             }
-            last_char = self.ch;
             self.read_char();
         }
-        result
+        Ok(self.read_from(position))
     }
-    //
-    fn escape_char(&mut self, c: char) -> char {
-        match c {
+    //读取转义序列, 当前字符为\, 读完停在转义序列的最后一个字符上
+    fn read_escape(&mut self) -> Result<char, LexError> {
+        self.read_char(); // eat \
+        let c = match self.ch {
             't' => '\t',
             'n' => '\n',
-            '"' => '\"',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
             '`' => '`',
-            '\\' => {
-                self.read_char();
-                '\\'
+            '$' => '$',
+            // \xNN, 只能表示ASCII字符
+            'x' => {
+                let digits = self.read_hex_digits(2);
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if byte.is_ascii() && digits.len() == 2 => byte as char,
+                    _ => return Err(LexError::InvalidEscape(format!("\\x{}", digits))),
+                }
             }
-            _ => EOF,
+            // \u{NNNN}, 1到6位十六进制数
+            'u' => {
+                let mut digits = String::new();
+                let mut closed = false;
+                if self.peek_char() == &'{' {
+                    self.read_char();
+                    digits = self.read_hex_digits(6);
+                    closed = self.peek_char() == &'}';
+                    if closed {
+                        self.read_char();
+                    }
+                }
+                let c = u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32);
+                match c {
+                    Some(c) if closed => c,
+                    _ => return Err(LexError::InvalidEscape(format!("\\u{{{}", digits))),
+                }
+            }
+            EOF => return Err(LexError::UnterminatedString),
+            c => return Err(LexError::InvalidEscape(format!("\\{}", c))),
+        };
+        Ok(c)
+    }
+    //读取转义序列中最多max个十六进制数字
+    fn read_hex_digits(&mut self, max: usize) -> String {
+        let mut digits = String::new();
+        while digits.len() < max && self.peek_char().is_ascii_hexdigit() {
+            self.read_char();
+            digits.push(self.ch);
         }
+        digits
     }
-    //忽略空格
-    fn skip_whitespace(&mut self) {
-        while self.ch == ' ' || self.ch == '\r' || self.ch == '\t' || self.ch == '\n' {
+    //读取模板字符串, 没有插值时为普通字符串
+    fn read_template(&mut self) -> Token {
+        self.read_char(); // eat `
+        let mut parts = vec![];
+        let mut string = String::new();
+        let mut error = None;
+        loop {
+            match self.ch {
+                '`' => break,
+                EOF => return Token::Illegal(LexError::UnterminatedString),
+                '\\' => match self.read_escape() {
+                    Ok(c) => string.push(c),
+                    Err(LexError::UnterminatedString) => {
+                        return Token::Illegal(LexError::UnterminatedString)
+                    }
+                    Err(err) => error = error.or(Some(err)),
+                },
+                '$' if self.peek_char() == &'{' => {
+                    if !string.is_empty() {
+                        parts.push(TemplatePart::Str(std::mem::take(&mut string)));
+                    }
+                    self.read_char(); // eat $
+                    self.read_char(); // eat {
+                    match self.read_interpolation() {
+                        Ok(source) => parts.push(TemplatePart::Expr(source)),
+                        Err(err) => return Token::Illegal(err),
+                    }
+                }
+                c => string.push(c),
+            }
+            self.read_char();
+        }
+        if let Some(err) = error {
+            return Token::Illegal(err);
+        }
+        if parts.is_empty() {
+            return Token::String(string);
+        }
+        if !string.is_empty() {
+            parts.push(TemplatePart::Str(string));
+        }
+        Token::Template(parts)
+    }
+    //读取${}中表达式的源码, 停在右括号上, 跳过其中的字符串和成对的大括号
+    fn read_interpolation(&mut self) -> Result<String, LexError> {
+        let position = self.position;
+        let mut depth = 0;
+        loop {
+            match self.ch {
+                EOF => return Err(LexError::UnterminatedString),
+                '{' => depth += 1,
+                '}' if depth == 0 => break,
+                '}' => depth -= 1,
+                '"' => {
+                    self.read_string()?;
+                }
+                // 嵌套的模板字符串
+                '`' => {
+                    if let Token::Illegal(err) = self.read_template() {
+                        return Err(err);
+                    }
+                }
+                _ => {}
+            }
+            self.read_char();
+        }
+        Ok(self.read_from(position))
+    }
+    //忽略空白和块注释
+    /// 最近读取的Token与上一个Token之间是否有换行
+    pub fn newline_before(&self) -> bool {
+        self.newline
+    }
+    fn skip_whitespace(&mut self) -> Result<(), LexError> {
+        loop {
+            match self.ch {
+                ' ' | '\r' | '\t' => self.read_char(),
+                '\n' => {
+                    self.newline = true;
+                    self.read_char();
+                }
+                '/' if self.peek_char() == &'*' => self.skip_block_comment()?,
+                _ => return Ok(()),
+            }
+        }
+    }
+    //跳过块注释/* */, 可以嵌套, 停在注释后的第一个字符上
+    fn skip_block_comment(&mut self) -> Result<(), LexError> {
+        let mut depth = 0;
+        loop {
+            match (self.ch, *self.peek_char()) {
+                (EOF, _) => return Err(LexError::UnterminatedComment),
+                ('/', '*') => {
+                    depth += 1;
+                    self.read_char();
+                }
+                ('*', '/') => {
+                    depth -= 1;
+                    self.read_char();
+                    if depth == 0 {
+                        self.read_char();
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
             self.read_char();
         }
     }
     //读取一个字符
     fn read_char(&mut self) {
-        self.position += if self.ch == EOF {
-            0
-        } else {
-            self.ch.len_utf8()
-        };
+        //position为字符的下标
+        if self.ch != EOF {
+            self.position += 1;
+        }
         self.ch = self.chars.get(self.position).cloned().unwrap_or(EOF);
     }
     //查看字符
-    fn peek_char(&mut self) -> &char {
+    fn peek_char(&self) -> &char {
         self.chars.get(self.position + 1).unwrap_or(&EOF)
     }
     //预检下个字符是否为期待字符，是则返回期待Token，并向后读取一个字符，否则返回默认Token
@@ -187,3 +410,35 @@ impl Lexer {
         }
     }
 }
+//从start开始读取数字, 下划线只能出现在两个数字之间, 返回结束位置, 没有数字时为None
+fn scan_digits(chars: &[char], start: usize, radix: u32) -> Option<usize> {
+    let mut i = start;
+    while let Some(&c) = chars.get(i) {
+        let between_digits = c == '_'
+            && i > start
+            && chars.get(i + 1).is_some_and(|next| next.is_digit(radix));
+        if !c.is_digit(radix) && !between_digits {
+            break;
+        }
+        i += 1;
+    }
+    (i > start).then_some(i)
+}
+//检查十进制数的格式, 返回是否为浮点数
+fn scan_decimal(chars: &[char]) -> Option<bool> {
+    let mut i = scan_digits(chars, 0, 10)?;
+    let mut is_float = false;
+    if chars.get(i) == Some(&'.') {
+        i = scan_digits(chars, i + 1, 10)?;
+        is_float = true;
+    }
+    if matches!(chars.get(i), Some('e' | 'E')) {
+        i += 1;
+        if matches!(chars.get(i), Some('+' | '-')) {
+            i += 1;
+        }
+        i = scan_digits(chars, i, 10)?;
+        is_float = true;
+    }
+    (i == chars.len()).then_some(is_float)
+}
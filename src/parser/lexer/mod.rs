@@ -1,3 +1,4 @@
+use std::fmt;
 use std::iter::Peekable;
 use std::str::Chars;
 
@@ -7,10 +8,39 @@ pub mod token;
 
 #[derive(Debug, Clone)]
 pub struct Lexer {
-    input: String,
     position: usize,
     ch: char,
     chars: Vec<char>,
+    // 最近读取的Token之前是否有换行
+    newline: bool,
+}
+
+/// 词法错误
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LexError {
+    IllegalChar(char),
+    /// 字符串或模板字符串没有闭合
+    UnterminatedString,
+    /// 块注释没有闭合
+    UnterminatedComment,
+    /// 无效的转义序列, 如 \q \x80 \u{110000}
+    InvalidEscape(String),
+    /// 格式错误的数字(字面量, 行, 列), 如 1.2.3 0x 1__0
+    InvalidNumber(String, usize, usize),
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexError::IllegalChar(c) => write!(f, "illegal character `{}`", c),
+            LexError::UnterminatedString => write!(f, "unterminated string"),
+            LexError::UnterminatedComment => write!(f, "unterminated block comment"),
+            LexError::InvalidEscape(escape) => write!(f, "invalid escape `{}`", escape),
+            LexError::InvalidNumber(num, line, column) => {
+                write!(f, "invalid number `{}` at {}:{}", num, line, column)
+            }
+        }
+    }
 }
 
 fn is_letter(ch: char) -> bool {
@@ -1,7 +1,7 @@
 #[cfg(test)]
 mod tests {
-    use crate::parser::lexer::token::Token;
-    use crate::parser::lexer::Lexer;
+    use crate::parser::lexer::token::{TemplatePart, Token};
+    use crate::parser::lexer::{LexError, Lexer};
     use Token::Plus;
     #[test]
     fn print_token() {
@@ -28,7 +28,7 @@ mod tests {
         return x + y;
     };
     let result = add(five, ten);
-    !-/*5;
+    !-/ *5;
     5 < 10 > 5;
     if 5 < 10 {
         return true;
@@ -125,6 +125,198 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_logical_token() {
+        let input = "a && b || !c";
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::Ident("a".to_string()),
+            Token::And,
+            Token::Ident("b".to_string()),
+            Token::Or,
+            Token::Bang,
+            Token::Ident("c".to_string()),
+            Token::Eof,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+    }
+
+    #[test]
+    fn test_bitwise_token() {
+        let input = "a ** 2 & b | c ^ ~d << 1 >> 2 <= >=";
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::Ident("a".to_string()),
+            Token::Power,
+            Token::Int("2".to_string()),
+            Token::BitAnd,
+            Token::Ident("b".to_string()),
+            Token::BitOr,
+            Token::Ident("c".to_string()),
+            Token::BitXor,
+            Token::Tilde,
+            Token::Ident("d".to_string()),
+            Token::ShiftLeft,
+            Token::Int("1".to_string()),
+            Token::ShiftRight,
+            Token::Int("2".to_string()),
+            Token::Le,
+            Token::Ge,
+            Token::Eof,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+    }
+
+    #[test]
+    fn test_assign_token() {
+        let input = "a += 1 -= *= /= %= % / -";
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::Ident("a".to_string()),
+            Token::PlusAssign,
+            Token::Int("1".to_string()),
+            Token::MinusAssign,
+            Token::AsteriskAssign,
+            Token::SlashAssign,
+            Token::PercentAssign,
+            Token::Percent,
+            Token::Slash,
+            Token::Minus,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+    }
+
+    #[test]
+    fn test_range_token() {
+        let input = "0..n 1..=3 ..5 1.5";
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::Int("0".to_string()),
+            Token::DotDot,
+            Token::Ident("n".to_string()),
+            Token::Int("1".to_string()),
+            Token::DotDotEq,
+            Token::Int("3".to_string()),
+            Token::DotDot,
+            Token::Int("5".to_string()),
+            Token::Float("1.5".to_string()),
+            Token::Eof,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+    }
+
+    #[test]
+    fn test_dot_token() {
+        let input = "p.x a.b..c ...r";
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::Ident("p".to_string()),
+            Token::Dot,
+            Token::Ident("x".to_string()),
+            Token::Ident("a".to_string()),
+            Token::Dot,
+            Token::Ident("b".to_string()),
+            Token::DotDot,
+            Token::Ident("c".to_string()),
+            Token::Ellipsis,
+            Token::Ident("r".to_string()),
+            Token::Eof,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+    }
+
+    #[test]
+    fn test_match_token() {
+        let input = "match x { _ => y }";
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::Match,
+            Token::Ident("x".to_string()),
+            Token::Lbrace,
+            Token::Ident("_".to_string()),
+            Token::FatArrow,
+            Token::Ident("y".to_string()),
+            Token::Rbrace,
+            Token::Eof,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+    }
+
+    #[test]
+    fn test_pipeline_token() {
+        let input = "x |> f | g || h";
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::Ident("x".to_string()),
+            Token::Pipeline,
+            Token::Ident("f".to_string()),
+            Token::BitOr,
+            Token::Ident("g".to_string()),
+            Token::Or,
+            Token::Ident("h".to_string()),
+            Token::Eof,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+    }
+
+    #[test]
+    fn test_exception_token() {
+        let input = "try {} catch e {} finally {} throw";
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::Try,
+            Token::Lbrace,
+            Token::Rbrace,
+            Token::Catch,
+            Token::Ident("e".to_string()),
+            Token::Lbrace,
+            Token::Rbrace,
+            Token::Finally,
+            Token::Lbrace,
+            Token::Rbrace,
+            Token::Throw,
+            Token::Eof,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+    }
+
+    #[test]
+    fn test_module_token() {
+        let input = "import { a as b } from \"m.my\" export";
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::Import,
+            Token::Lbrace,
+            Token::Ident("a".to_string()),
+            Token::Ident("as".to_string()),
+            Token::Ident("b".to_string()),
+            Token::Rbrace,
+            Token::Ident("from".to_string()),
+            Token::String("m.my".to_string()),
+            Token::Export,
+            Token::Eof,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+    }
+
     #[test]
     fn test_token() {
         let input = r"
@@ -151,4 +343,169 @@ mod tests {
             assert_eq!(tk, &lexer.parse_token())
         }
     }
+
+    #[test]
+    fn test_const_token() {
+        let input = "const HALF = 0.5;";
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::Const,
+            Token::Ident("HALF".to_string()),
+            Token::Assign,
+            Token::Float("0.5".to_string()),
+            Token::Semicolon,
+            Token::Eof,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+    }
+
+    #[test]
+    fn test_template_token() {
+        let input = r#"`a ${b + 1} c` `x\${y}` `${h["}"]}${`${n}`}` `${"#;
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::Template(vec![
+                TemplatePart::Str("a ".to_string()),
+                TemplatePart::Expr("b + 1".to_string()),
+                TemplatePart::Str(" c".to_string()),
+            ]),
+            Token::String("x${y}".to_string()),
+            Token::Template(vec![
+                TemplatePart::Expr("h[\"}\"]".to_string()),
+                TemplatePart::Expr("`${n}`".to_string()),
+            ]),
+            Token::Illegal(LexError::UnterminatedString),
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+    }
+
+    #[test]
+    fn test_block_comment() {
+        let input = "a /* one /* two */ still one */ + /**/ b /* 注释 */ // end";
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::Ident("a".to_string()),
+            Token::Plus,
+            Token::Ident("b".to_string()),
+            Token::Comment("// end".to_string()),
+            Token::Eof,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+        let mut lexer = Lexer::new("a /* /* */");
+        assert_eq!(Token::Ident("a".to_string()), lexer.parse_token());
+        assert_eq!(
+            Token::Illegal(LexError::UnterminatedComment),
+            lexer.parse_token()
+        );
+        assert_eq!(Token::Eof, lexer.parse_token());
+    }
+
+    #[test]
+    fn test_string_escape() {
+        let input = r#""\t\n\r\0\\\"\'\x41\u{1F47D}\u{e9}" `\x7e\u{4E2D}\`` "\q" "\x80" "\x4" "\u{110000}" "\u41" "\u{41" 1"#;
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::String("\t\n\r\0\\\"'A\u{1F47D}é".to_string()),
+            Token::String("~中`".to_string()),
+            Token::Illegal(LexError::InvalidEscape("\\q".to_string())),
+            Token::Illegal(LexError::InvalidEscape("\\x80".to_string())),
+            Token::Illegal(LexError::InvalidEscape("\\x4".to_string())),
+            Token::Illegal(LexError::InvalidEscape("\\u{110000".to_string())),
+            Token::Illegal(LexError::InvalidEscape("\\u{".to_string())),
+            Token::Illegal(LexError::InvalidEscape("\\u{41".to_string())),
+            Token::Int("1".to_string()),
+            Token::Eof,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+    }
+
+    #[test]
+    fn test_raw_and_multiline_string() {
+        let input = "r\"C:\\new\\{x}\" \"line 1\n  line 2\" r\"a\nb\" rest r\"open";
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::String("C:\\new\\{x}".to_string()),
+            Token::String("line 1\n  line 2".to_string()),
+            Token::String("a\nb".to_string()),
+            Token::Ident("rest".to_string()),
+            Token::Illegal(LexError::UnterminatedString),
+            Token::Eof,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+    }
+
+    #[test]
+    fn test_unicode_and_illegal() {
+        let input = "let 名字 = \"你好\" // 注释\n@ \"open";
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::Let,
+            Token::Ident("名字".to_string()),
+            Token::Assign,
+            Token::String("你好".to_string()),
+            Token::Comment("// 注释".to_string()),
+            Token::Illegal(LexError::IllegalChar('@')),
+            Token::Illegal(LexError::UnterminatedString),
+            Token::Eof,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+    }
+
+    #[test]
+    fn test_number_literal() {
+        let input = "0xFF 0B1010 0o755 1_000_000 1e9 2.5e-3 1E+2 1_0.5 0..n 1.5..2";
+        let mut lexer = Lexer::new(input);
+        let tests = [
+            Token::Int("0xFF".to_string()),
+            Token::Int("0B1010".to_string()),
+            Token::Int("0o755".to_string()),
+            Token::Int("1_000_000".to_string()),
+            Token::Float("1e9".to_string()),
+            Token::Float("2.5e-3".to_string()),
+            Token::Float("1E+2".to_string()),
+            Token::Float("1_0.5".to_string()),
+            Token::Int("0".to_string()),
+            Token::DotDot,
+            Token::Ident("n".to_string()),
+            Token::Float("1.5".to_string()),
+            Token::DotDot,
+            Token::Int("2".to_string()),
+            Token::Eof,
+        ];
+        for tk in tests.iter() {
+            assert_eq!(tk, &lexer.parse_token())
+        }
+        let invalid = ["1.2.3", "12abc", "0x", "0b102", "0o8", "1__0", "1_", "1e", "1e+", "2.5e-x"];
+        for input in invalid {
+            let mut lexer = Lexer::new(input);
+            assert_eq!(
+                Token::Illegal(LexError::InvalidNumber(input.to_string(), 1, 1)),
+                lexer.parse_token(),
+                "input: {}",
+                input
+            );
+            assert_eq!(Token::Eof, lexer.parse_token(), "input: {}", input);
+        }
+        let mut lexer = Lexer::new("let a = 1\n  let b = 0x1G;");
+        let tokens = std::iter::from_fn(|| Some(lexer.parse_token()))
+            .take_while(|tk| tk != &Token::Eof)
+            .collect::<Vec<_>>();
+        assert!(tokens.contains(&Token::Illegal(LexError::InvalidNumber(
+            "0x1G".to_string(),
+            2,
+            11
+        ))));
+    }
 }
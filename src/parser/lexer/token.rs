@@ -1,11 +1,13 @@
 use std::fmt;
 
+use crate::parser::lexer::LexError;
+
 pub const EOF: char = '\u{0}';
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Token {
-    /// 非法字符
-    Illegal,
+    /// 非法字符、未闭合的字符串等词法错误
+    Illegal(LexError),
     /// End Of File
     Eof,
     //标识符和字面量
@@ -13,6 +15,8 @@ pub enum Token {
     Int(String),
     Float(String),
     String(String),
+    /// 带插值的模板字符串`a ${b}`
+    Template(Vec<TemplatePart>),
     /// 行注释// xxx
     Comment(String),
     //操作符
@@ -28,6 +32,32 @@ pub enum Token {
     Asterisk,
     /// /
     Slash,
+    /// %
+    Percent,
+    /// **
+    Power,
+    /// &
+    BitAnd,
+    /// |
+    BitOr,
+    /// ^
+    BitXor,
+    /// ~
+    Tilde,
+    /// <<
+    ShiftLeft,
+    /// >>
+    ShiftRight,
+    /// +=
+    PlusAssign,
+    /// -=
+    MinusAssign,
+    /// *=
+    AsteriskAssign,
+    /// /=
+    SlashAssign,
+    /// %=
+    PercentAssign,
     /// <
     Lt,
     /// >
@@ -40,6 +70,22 @@ pub enum Token {
     Le,
     /// >=
     Ge,
+    /// &&
+    And,
+    /// ||
+    Or,
+    /// .
+    Dot,
+    /// ..
+    DotDot,
+    /// ..=
+    DotDotEq,
+    /// ...
+    Ellipsis,
+    /// =>
+    FatArrow,
+    /// |>
+    Pipeline,
     //分隔符等其他符号
     /// ,
     Comma,
@@ -73,10 +119,45 @@ pub enum Token {
     If,
     /// for
     For,
+    /// in
+    In,
+    /// while
+    While,
+    /// break
+    Break,
+    /// continue
+    Continue,
     /// else
     Else,
     /// return
     Return,
+    /// struct
+    Struct,
+    /// match
+    Match,
+    /// try
+    Try,
+    /// catch
+    Catch,
+    /// finally
+    Finally,
+    /// throw
+    Throw,
+    /// import
+    Import,
+    /// export
+    Export,
+    /// const
+    Const,
+}
+
+/// 模板字符串的片段
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TemplatePart {
+    /// 原样输出的字符串
+    Str(String),
+    /// ${}中表达式的源码
+    Expr(String),
 }
 
 impl Token {
@@ -88,8 +169,21 @@ impl Token {
             "false" => Token::False,
             "if" => Token::If,
             "for" => Token::For,
+            "in" => Token::In,
+            "while" => Token::While,
+            "break" => Token::Break,
+            "continue" => Token::Continue,
             "else" => Token::Else,
             "return" => Token::Return,
+            "struct" => Token::Struct,
+            "match" => Token::Match,
+            "try" => Token::Try,
+            "catch" => Token::Catch,
+            "finally" => Token::Finally,
+            "throw" => Token::Throw,
+            "import" => Token::Import,
+            "export" => Token::Export,
+            "const" => Token::Const,
             id => Token::Ident(id.to_string()),
         }
     }
@@ -98,24 +192,55 @@ impl Token {
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Token::Illegal => write!(f, "ILLEGAL"),
+            Token::Illegal(err) => write!(f, "ILLEGAL({})", err),
             Token::Eof => write!(f, "EOF"),
             Token::Ident(id) => write!(f, "Ident({})", id),
             Token::Int(int) => write!(f, "Int({})", int),
             Token::Float(float) => write!(f, "Float({})", float),
             Token::String(string) => write!(f, "String(\"{}\")", string),
+            Token::Template(parts) => {
+                write!(f, "Template(`")?;
+                for part in parts {
+                    match part {
+                        TemplatePart::Str(string) => write!(f, "{}", string)?,
+                        TemplatePart::Expr(source) => write!(f, "${{{}}}", source)?,
+                    }
+                }
+                write!(f, "`)")
+            }
             Token::Assign => write!(f, "="),
             Token::Plus => write!(f, "+"),
             Token::Minus => write!(f, "-"),
             Token::Bang => write!(f, "!"),
             Token::Asterisk => write!(f, "*"),
             Token::Slash => write!(f, "/"),
+            Token::Percent => write!(f, "%"),
+            Token::Power => write!(f, "**"),
+            Token::BitAnd => write!(f, "&"),
+            Token::BitOr => write!(f, "|"),
+            Token::BitXor => write!(f, "^"),
+            Token::Tilde => write!(f, "~"),
+            Token::ShiftLeft => write!(f, "<<"),
+            Token::ShiftRight => write!(f, ">>"),
+            Token::PlusAssign => write!(f, "+="),
+            Token::MinusAssign => write!(f, "-="),
+            Token::AsteriskAssign => write!(f, "*="),
+            Token::SlashAssign => write!(f, "/="),
+            Token::PercentAssign => write!(f, "%="),
             Token::Lt => write!(f, "<"),
             Token::Gt => write!(f, ">"),
             Token::Eq => write!(f, "=="),
             Token::NotEq => write!(f, "!="),
             Token::Le => write!(f, "<="),
             Token::Ge => write!(f, ">="),
+            Token::And => write!(f, "&&"),
+            Token::Or => write!(f, "||"),
+            Token::Dot => write!(f, "."),
+            Token::DotDot => write!(f, ".."),
+            Token::DotDotEq => write!(f, "..="),
+            Token::Ellipsis => write!(f, "..."),
+            Token::FatArrow => write!(f, "=>"),
+            Token::Pipeline => write!(f, "|>"),
             Token::Comma => write!(f, ","),
             Token::Colon => write!(f, ":"),
             Token::Semicolon => write!(f, ";"),
@@ -134,6 +259,19 @@ impl fmt::Display for Token {
             Token::Return => write!(f, "return"),
             Token::Comment(comment) => write!(f, "// {}", comment),
             Token::For => write!(f, "for"),
+            Token::In => write!(f, "in"),
+            Token::While => write!(f, "while"),
+            Token::Break => write!(f, "break"),
+            Token::Continue => write!(f, "continue"),
+            Token::Struct => write!(f, "struct"),
+            Token::Match => write!(f, "match"),
+            Token::Try => write!(f, "try"),
+            Token::Catch => write!(f, "catch"),
+            Token::Finally => write!(f, "finally"),
+            Token::Throw => write!(f, "throw"),
+            Token::Import => write!(f, "import"),
+            Token::Export => write!(f, "export"),
+            Token::Const => write!(f, "const"),
         }
     }
 }
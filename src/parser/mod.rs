@@ -1,7 +1,10 @@
 use ast::Expression;
-use lexer::token::Token;
-use crate::parser::ast::{BinaryOperator, BlockStatement, Statement, UnaryOperator};
-use crate::parser::lexer::Lexer;
+use lexer::token::{TemplatePart, Token};
+use crate::parser::ast::{
+    BinaryOperator, BlockStatement, Import, LetPattern, MatchArm, Parameter, Pattern, Statement,
+    UnaryOperator,
+};
+use crate::parser::lexer::{LexError, Lexer};
 use crate::parser::ParserError::ExpectedUnaryOp;
 use crate::Program;
 
@@ -19,6 +22,8 @@ pub struct Parser {
     token: Token,
     peek_token: Token,
     errors: Vec<ParserError>,
+    // 正在解析match分支的守卫, 其中的 ident => 是分支的箭头而不是箭头函数
+    in_guard: bool,
 }
 
 /// 优先级
@@ -27,16 +32,34 @@ enum Precedence {
     Lowest,
     /// =
     Assign,
+    /// |>
+    Pipeline,
+    /// .. or ..=
+    Range,
+    /// ||
+    LogicalOr,
+    /// &&
+    LogicalAnd,
     /// ==
     Equals,
     /// \> or <
     LessGreater,
+    /// |
+    BitOr,
+    /// ^
+    BitXor,
+    /// &
+    BitAnd,
+    /// << or >>
+    Shift,
     /// `+`
     Sum,
     /// `*`
     Product,
-    /// -x or !x
+    /// -x or !x or ~x
     Prefix,
+    /// `**`, 比负号优先级高: -2 ** 2 == -4
+    Power,
     /// my_fun
     Call,
 
@@ -58,9 +81,28 @@ pub enum ParserError {
     ExpectedFloat(Token),
     ExpectedString(Token),
     ExpectedBoolean(Token),
+    /// 标签后只能跟循环语句
+    ExpectedLoop(Token),
+    /// 结构体字段重复声明
+    DuplicateField(String),
+    ExpectedPattern(Token),
+    /// 同一模式中重复绑定的变量
+    DuplicateBinding(String),
+    /// import和export只能出现在顶层
+    NotTopLevel(Token),
+    /// 剩余参数之后还有参数
+    RestParameterNotLast(Token),
+    /// 有默认值的参数之后的参数没有默认值
+    MissingParameterDefault(String),
+    /// 多选模式的各分支绑定的变量不同
+    OrPatternBindings(String),
 
+    /// 整数超出范围
     ParseInt(String),
-    // ParseFloat(String),
+    /// 浮点数超出范围
+    ParseFloat(String),
+    /// 词法错误
+    Lex(LexError),
 }
 
 impl Parser {
@@ -71,6 +113,7 @@ impl Parser {
             token: Token::Eof,
             peek_token: Token::Eof,
             errors: vec![],
+            in_guard: false,
         };
         parser.next_token();
         parser.next_token();
@@ -85,7 +128,12 @@ impl Parser {
     pub fn parse_program(&mut self) -> Program {
         let mut statements = vec![];
         while self.has_next() {
-            match self.parse_statement() {
+            let statement = match self.token {
+                Token::Import => self.parse_import_statement(),
+                Token::Export => self.parse_export_statement(),
+                _ => self.parse_statement(),
+            };
+            match statement {
                 Ok(statement) => statements.push(statement),
                 Err(err) => self.errors.push(err),
             }
@@ -102,10 +150,20 @@ impl Parser {
     fn parse_statement(&mut self) -> ParseResult<Statement> {
         match &self.token {
             Token::Let => self.parse_let_statement(),
+            Token::Const => self.parse_const_statement(),
             Token::Return => self.parse_return_statement(),
             Token::Comment(comment) => Ok(Statement::Comment(comment.to_string())),
             Token::For => self.parse_for_statement(),
+            Token::While => self.parse_while_statement(),
+            Token::Break => self.parse_break_statement(),
+            Token::Continue => self.parse_continue_statement(),
+            Token::Ident(_) if self.peek_token == Token::Colon => self.parse_labeled_statement(),
             Token::Function => self.parse_function_statement(),
+            Token::Struct => self.parse_struct_statement(),
+            Token::Throw => self.parse_throw_statement(),
+            Token::Import | Token::Export => Err(ParserError::NotTopLevel(self.token.clone())),
+            Token::Try => self.parse_try_statement(),
+            Token::Lbrace => self.parse_block_or_expression_statement(),
             _ => self.parse_expression_statement(),
         }
     }
@@ -117,6 +175,7 @@ impl Parser {
         //ident
         let name = match &self.peek_token {
             Token::Ident(ident) => ident.clone(),
+            Token::Lbracket | Token::Lbrace => return self.parse_destructure_statement(),
             token => return Err(ParserError::ExpectedIdentifier(token.clone())),
         };
         //eat let
@@ -138,6 +197,108 @@ impl Parser {
         }
         Ok(result)
     }
+    /// 解析const语句, 必须有初始值
+    ///
+    /// const identifier = expression;
+    fn parse_const_statement(&mut self) -> ParseResult<Statement> {
+        let name = self.parse_next_identifier()?;
+        self.expect_peek(Token::Assign, ParserError::ExpectedAssign)?;
+        self.next_token(); //eat =
+        let expression = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token == Token::Semicolon {
+            self.next_token(); //eat ;
+        }
+        Ok(Statement::Const(name, expression))
+    }
+    /// 解析解构赋值
+    ///
+    /// 1. let [a, b = 1, ...rest] = expr;
+    /// 2. let {name, age = 0, pos: [x, y]} = expr;
+    fn parse_destructure_statement(&mut self) -> ParseResult<Statement> {
+        self.next_token(); // eat let
+        let pattern = self.parse_let_pattern()?;
+        let mut bindings = pattern.bindings();
+        bindings.sort_unstable();
+        if let Some(name) = bindings.windows(2).find(|w| w[0] == w[1]) {
+            return Err(ParserError::DuplicateBinding(name[0].to_string()));
+        }
+        self.expect_peek(Token::Assign, ParserError::ExpectedAssign)?;
+        self.next_token(); // eat =
+        let expression = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token == Token::Semicolon {
+            self.next_token(); //eat ;
+        }
+        Ok(Statement::Destructure(pattern, expression))
+    }
+    fn parse_let_pattern(&mut self) -> ParseResult<LetPattern> {
+        match &self.token {
+            Token::Ident(name) => Ok(LetPattern::Name(name.clone())),
+            Token::Lbracket => {
+                self.next_token(); // eat [
+                let mut items = vec![];
+                let mut rest = None;
+                while self.token != Token::Rbracket {
+                    if self.token == Token::Ellipsis {
+                        //剩余元素只能放在最后
+                        rest = Some(self.parse_identifier_after_ellipsis()?);
+                        self.expect_peek_is(Token::Rbracket)?;
+                        break;
+                    } else if self.token != Token::Comma {
+                        let item = self.parse_let_pattern()?;
+                        items.push((item, self.parse_pattern_default()?));
+                    }
+                    self.next_token(); // eat pattern or ,
+                }
+                Ok(LetPattern::Array(items, rest))
+            }
+            Token::Lbrace => {
+                self.next_token(); // eat {
+                let mut pairs = vec![];
+                while self.token != Token::Rbrace {
+                    if self.token != Token::Comma {
+                        let key = match &self.token {
+                            Token::Ident(key) | Token::String(key) => key.clone(),
+                            token => return Err(ParserError::ExpectedIdentifier(token.clone())),
+                        };
+                        //省略值模式时绑定到同名变量
+                        let pattern = if self.peek_token == Token::Colon {
+                            self.next_token(); // eat key
+                            self.next_token(); // eat :
+                            self.parse_let_pattern()?
+                        } else if let Token::Ident(_) = self.token {
+                            LetPattern::Name(key.clone())
+                        } else {
+                            return Err(ParserError::Expected(
+                                Token::Colon,
+                                self.peek_token.clone(),
+                            ));
+                        };
+                        pairs.push((key, pattern, self.parse_pattern_default()?));
+                    }
+                    self.next_token(); // eat pattern or ,
+                }
+                Ok(LetPattern::Hash(pairs))
+            }
+            token => Err(ParserError::ExpectedPattern(token.clone())),
+        }
+    }
+    /// 解析 ...name 中的变量名
+    fn parse_identifier_after_ellipsis(&mut self) -> ParseResult<String> {
+        self.next_token(); // eat ...
+        match &self.token {
+            Token::Ident(name) => Ok(name.clone()),
+            token => Err(ParserError::ExpectedIdentifier(token.clone())),
+        }
+    }
+    /// 解析模式后可选的默认值 = expr
+    fn parse_pattern_default(&mut self) -> ParseResult<Option<Expression>> {
+        if self.peek_token != Token::Assign {
+            return Ok(None);
+        }
+        self.next_token(); // eat pattern
+        self.next_token(); // eat =
+        Ok(Some(self.parse_expression(Precedence::Assign)?))
+    }
     /// 解析return语句
     ///
     /// 1. return;
@@ -154,10 +315,142 @@ impl Parser {
         }
         Ok(Statement::Return(option))
     }
+    /// 解析import语句, as和from不是关键字
+    ///
+    /// 1. import "path" as name;
+    /// 2. import { name, name as alias } from "path";
+    fn parse_import_statement(&mut self) -> ParseResult<Statement> {
+        self.next_token(); // eat import
+        let statement = match &self.token {
+            Token::String(path) => {
+                let path = path.clone();
+                self.expect_contextual_keyword("as")?;
+                let name = self.parse_next_identifier()?;
+                Statement::Import(path, Import::Module(name))
+            }
+            Token::Lbrace => {
+                let mut names = vec![];
+                while self.peek_token != Token::Rbrace {
+                    let name = self.parse_next_identifier()?;
+                    let alias = match &self.peek_token {
+                        Token::Ident(ident) if ident == "as" => {
+                            self.next_token(); // eat name
+                            self.parse_next_identifier()?
+                        }
+                        _ => name.clone(),
+                    };
+                    names.push((name, alias));
+                    if self.peek_token != Token::Rbrace {
+                        self.expect_peek_is(Token::Comma)?;
+                    }
+                }
+                self.next_token(); // eat last name
+                self.expect_contextual_keyword("from")?;
+                let path = match &self.peek_token {
+                    Token::String(path) => path.clone(),
+                    token => return Err(ParserError::ExpectedString(token.clone())),
+                };
+                self.next_token(); // eat from
+                Statement::Import(path, Import::Names(names))
+            }
+            token => return Err(ParserError::ExpectedString(token.clone())),
+        };
+        if self.peek_token == Token::Semicolon {
+            self.next_token(); //eat ;
+        }
+        Ok(statement)
+    }
+    /// 下一个token须为指定的标识符
+    fn expect_contextual_keyword(&mut self, keyword: &str) -> ParseResult<()> {
+        match &self.peek_token {
+            Token::Ident(ident) if ident == keyword => {
+                self.next_token();
+                Ok(())
+            }
+            token => Err(ParserError::Expected(
+                Token::Ident(keyword.to_string()),
+                token.clone(),
+            )),
+        }
+    }
+    /// 读取下一个标识符
+    fn parse_next_identifier(&mut self) -> ParseResult<String> {
+        self.next_token();
+        self.parse_identifier_string()
+    }
+    /// 解析export语句, 只能导出let/const/fn/struct声明的变量
+    ///
+    /// export let name = expr;
+    fn parse_export_statement(&mut self) -> ParseResult<Statement> {
+        self.next_token(); // eat export
+        match self.token {
+            Token::Let | Token::Const | Token::Function | Token::Struct => {
+                Ok(Statement::Export(Box::new(self.parse_statement()?)))
+            }
+            _ => Err(ParserError::Expected(Token::Let, self.token.clone())),
+        }
+    }
+    /// 解析throw语句
+    ///
+    /// throw expr;
+    fn parse_throw_statement(&mut self) -> ParseResult<Statement> {
+        self.next_token(); //eat throw
+        let value = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token == Token::Semicolon {
+            self.next_token(); //eat ;
+        }
+        Ok(Statement::Throw(value))
+    }
+    /// 解析try语句, catch和finally至少有一个, catch的变量和括号都可以省略
+    ///
+    /// try { block } catch (e) { block } finally { block }
+    fn parse_try_statement(&mut self) -> ParseResult<Statement> {
+        self.expect_peek_is(Token::Lbrace)?; // eat try
+        let body = self.parse_block_statement()?;
+        let mut catch = None;
+        if self.peek_token == Token::Catch {
+            self.next_token(); // eat }
+            let has_bracket = self.peek_token == Token::Lparen;
+            if has_bracket {
+                self.next_token(); // eat catch
+            }
+            let mut name = None;
+            if let Token::Ident(ident) = &self.peek_token {
+                name = Some(ident.clone());
+                self.next_token(); // eat catch or (
+            }
+            if has_bracket {
+                self.expect_peek_is(Token::Rparen)?;
+            }
+            self.expect_peek_is(Token::Lbrace)?;
+            catch = Some((name, self.parse_block_statement()?));
+        }
+        let mut finally = None;
+        if self.peek_token == Token::Finally {
+            self.next_token(); // eat }
+            self.expect_peek_is(Token::Lbrace)?; // eat finally
+            finally = Some(self.parse_block_statement()?);
+        }
+        if catch.is_none() && finally.is_none() {
+            return Err(ParserError::Expected(Token::Catch, self.peek_token.clone()));
+        }
+        if self.peek_token == Token::Semicolon {
+            self.next_token(); //eat ;
+        }
+        Ok(Statement::Try(body, catch, finally))
+    }
     /// 解析for语句
     /// for (init; cond; after) { block_statement }
     fn parse_for_statement(&mut self) -> ParseResult<Statement> {
+        // for x in iterable
+        if let Token::Ident(_) = self.peek_token {
+            return self.parse_for_in_statement();
+        }
         self.next_token();
+        // for (k, v) in iterable
+        if let Token::Ident(_) = self.peek_token {
+            return self.parse_for_in_statement();
+        }
         let mut init = None;
         if self.peek_token != Token::Semicolon {
             self.next_token();
@@ -182,6 +475,88 @@ impl Parser {
         let for_statement = Statement::For(init, cond, after, blocks);
         Ok(for_statement)
     }
+    /// 解析for-in语句
+    /// 1. for x in iterable { block_statement }
+    /// 2. for (k, v) in iterable { block_statement }
+    fn parse_for_in_statement(&mut self) -> ParseResult<Statement> {
+        let has_bracket = self.token == Token::Lparen;
+        let mut names = vec![];
+        loop {
+            self.next_token();
+            match &self.token {
+                Token::Ident(name) => names.push(name.clone()),
+                token => return Err(ParserError::ExpectedIdentifier(token.clone())),
+            }
+            if !has_bracket || names.len() == 2 || self.peek_token != Token::Comma {
+                break;
+            }
+            self.next_token(); //eat ,
+        }
+        if has_bracket {
+            self.expect_peek_is(Token::Rparen)?;
+        }
+        self.expect_peek_is(Token::In)?;
+        self.next_token(); // eat in
+        let iterable = self.parse_expression(Precedence::Lowest)?;
+        self.expect_peek_is(Token::Lbrace)?;
+        let blocks = self.parse_block_statement()?;
+        if self.peek_token == Token::Semicolon {
+            self.next_token(); //eat ;
+        }
+        Ok(Statement::ForIn(names, iterable, blocks))
+    }
+    /// 解析while语句
+    /// while (cond) { block_statement }
+    fn parse_while_statement(&mut self) -> ParseResult<Statement> {
+        self.next_token(); // eat while
+        let cond = self.parse_expression(Precedence::Lowest)?;
+        self.expect_peek_is(Token::Lbrace)?;
+        let blocks = self.parse_block_statement()?;
+        if self.peek_token == Token::Semicolon {
+            self.next_token(); //eat ;
+        }
+        Ok(Statement::While(cond, blocks))
+    }
+    /// 解析break语句
+    /// 1. break;
+    /// 2. break label;
+    fn parse_break_statement(&mut self) -> ParseResult<Statement> {
+        let label = self.parse_loop_label();
+        Ok(Statement::Break(label))
+    }
+    /// 解析continue语句
+    /// 1. continue;
+    /// 2. continue label;
+    fn parse_continue_statement(&mut self) -> ParseResult<Statement> {
+        let label = self.parse_loop_label();
+        Ok(Statement::Continue(label))
+    }
+    /// 解析break/continue后的标签, 同一行紧跟的标识符即为标签
+    fn parse_loop_label(&mut self) -> Option<String> {
+        let mut label = None;
+        if let Token::Ident(name) = &self.peek_token {
+            if !self.lexer.newline_before() {
+                label = Some(name.clone());
+                self.next_token(); // eat break/continue
+            }
+        }
+        if self.peek_token == Token::Semicolon {
+            self.next_token(); //eat ;
+        }
+        label
+    }
+    /// 解析带标签的循环语句
+    /// label: for/while ...
+    fn parse_labeled_statement(&mut self) -> ParseResult<Statement> {
+        let label = self.parse_identifier_string()?;
+        self.next_token(); // eat label
+        self.next_token(); // eat :
+        let statement = match self.token {
+            Token::For | Token::While => self.parse_statement()?,
+            _ => return Err(ParserError::ExpectedLoop(self.token.clone())),
+        };
+        Ok(Statement::Labeled(label, Box::new(statement)))
+    }
     /// 解析函数语句 fn ident(args..) { blocks }
     fn parse_function_statement(&mut self) -> ParseResult<Statement> {
         //cur token: fn
@@ -196,6 +571,43 @@ impl Parser {
             self.parse_expression_statement()
         }
     }
+    /// 解析结构体声明
+    ///
+    /// struct Name { field, field; fn method(args..) { blocks } }
+    fn parse_struct_statement(&mut self) -> ParseResult<Statement> {
+        self.next_token(); // eat struct
+        let name = self.parse_identifier_string()?;
+        self.expect_peek_is(Token::Lbrace)?; // eat name
+        self.next_token(); // eat {
+        let mut fields: Vec<String> = vec![];
+        let mut methods = vec![];
+        while self.token != Token::Rbrace {
+            match &self.token {
+                Token::Comma | Token::Semicolon => {}
+                Token::Ident(field) => {
+                    if fields.contains(field) {
+                        return Err(ParserError::DuplicateField(field.clone()));
+                    }
+                    fields.push(field.clone())
+                }
+                Token::Function => {
+                    self.next_token(); // eat fn
+                    let method = self.parse_identifier_string()?;
+                    self.next_token(); // eat ident
+                    let params = self.parse_function_parameters()?;
+                    self.expect_peek_is(Token::Lbrace)?; // eat )
+                    let blocks = self.parse_block_statement()?;
+                    methods.push((method, params, blocks));
+                }
+                token => return Err(ParserError::ExpectedIdentifier(token.clone())),
+            }
+            self.next_token();
+        }
+        if self.peek_token == Token::Semicolon {
+            self.next_token(); //eat ;
+        }
+        Ok(Statement::Struct(name, fields, methods))
+    }
 
     /// 解析表达式语句
     ///
@@ -207,6 +619,25 @@ impl Parser {
         }
         expression.map(Statement::Expression)
     }
+    /// 解析以 { 开头的语句: 能解析为Hash字面量时是表达式语句, 否则是语句块
+    ///
+    /// { let x = 5; x }
+    fn parse_block_or_expression_statement(&mut self) -> ParseResult<Statement> {
+        let lexer = self.lexer.clone();
+        let (token, peek_token) = (self.token.clone(), self.peek_token.clone());
+        if let Ok(statement) = self.parse_expression_statement() {
+            return Ok(statement);
+        }
+        //回到 { 重新解析
+        self.lexer = lexer;
+        self.token = token;
+        self.peek_token = peek_token;
+        let block = self.parse_block_statement()?;
+        if self.peek_token == Token::Semicolon {
+            self.next_token();
+        }
+        Ok(Statement::Block(block))
+    }
     /// 解析语句块
     ///
     /// {
@@ -241,13 +672,52 @@ impl Parser {
         }
         Ok(left_expr)
     }
-    ///解析分组表达式
+    ///解析分组表达式, 或者 (a, b) => a + b 形式的箭头函数
     fn parse_grouped_expression(&mut self) -> ParseResult {
+        if !self.in_guard && self.is_arrow_parameters() {
+            let params = self.parse_function_parameters()?;
+            return self.parse_arrow_function(params);
+        }
         self.next_token(); // eat (
         let expr = self.parse_expression(Precedence::Lowest)?;
         self.expect_peek(Token::Rparen, |tk| ParserError::Expected(Token::Rparen, tk))?;
         Ok(expr)
     }
+    /// 当前的(是否为箭头函数的参数列表, 即匹配的)之后是否为=>
+    fn is_arrow_parameters(&self) -> bool {
+        let mut lexer = self.lexer.clone();
+        let mut token = self.peek_token.clone();
+        let mut depth = 1;
+        loop {
+            match token {
+                Token::Lparen => depth += 1,
+                Token::Rparen if depth == 1 => return lexer.parse_token() == Token::FatArrow,
+                Token::Rparen => depth -= 1,
+                Token::Eof => return false,
+                _ => {}
+            }
+            token = lexer.parse_token();
+        }
+    }
+    /// 解析箭头函数 => 之后的部分, 当前Token为参数列表的最后一个Token
+    ///
+    /// x => x * 2; (a, b) => { a + b }
+    fn parse_arrow_function(&mut self, params: Vec<Parameter>) -> ParseResult {
+        self.expect_peek_is(Token::FatArrow)?;
+        let body = self.parse_fat_arrow_body()?;
+        Ok(Expression::FunctionLiteral(params, body))
+    }
+    /// 解析 => 之后的语句块或单个表达式, 当前Token为=>
+    fn parse_fat_arrow_body(&mut self) -> ParseResult<BlockStatement> {
+        self.next_token(); // eat =>
+        if self.token == Token::Lbrace {
+            return self.parse_block_statement();
+        }
+        let expression = self.parse_expression(Precedence::Lowest)?;
+        Ok(BlockStatement {
+            statements: vec![Statement::Expression(expression)],
+        })
+    }
     /// ## 解析数组字面量
     fn parse_array_literal(&mut self) -> ParseResult {
         let args = self.parse_comma_arguments(Token::Rbracket)?;
@@ -270,13 +740,33 @@ impl Parser {
         Ok(Expression::HashLiteral(v))
     }
     /// 解析函数声明参数列表
-    fn parse_function_parameters(&mut self) -> ParseResult<Vec<String>> {
+    fn parse_function_parameters(&mut self) -> ParseResult<Vec<Parameter>> {
         self.next_token(); // eat (
-        let mut params = vec![];
+        let mut params: Vec<Parameter> = vec![];
         while self.token != Token::Rparen {
             if self.token != Token::Comma {
                 // if not eq )
-                params.push(self.parse_identifier_string()?);
+                if params.last().is_some_and(|param| param.rest) {
+                    return Err(ParserError::RestParameterNotLast(self.token.clone()));
+                }
+                let param = if self.token == Token::Ellipsis {
+                    Parameter {
+                        rest: true,
+                        ..Parameter::new(self.parse_identifier_after_ellipsis()?)
+                    }
+                } else {
+                    let name = self.parse_identifier_string()?;
+                    Parameter {
+                        default: self.parse_pattern_default()?,
+                        ..Parameter::new(name)
+                    }
+                };
+                //有默认值的参数之后不能再有必须的参数
+                let after_default = params.iter().any(|param| param.default.is_some());
+                if after_default && param.default.is_none() && !param.rest {
+                    return Err(ParserError::MissingParameterDefault(param.name));
+                }
+                params.push(param);
             }
             self.next_token(); //eat param or colon
         }
@@ -287,6 +777,18 @@ impl Parser {
         let arguments = self.parse_comma_arguments(Token::Rparen)?;
         Ok(Expression::Call(Box::new(function), arguments))
     }
+    /// 解析成员访问 obj.field 和方法调用 value.method(args)
+    fn parse_field_expression(&mut self, left: Expression) -> ParseResult {
+        self.next_token(); // eat .
+        let name = self.parse_identifier_string()?;
+        if self.peek_token == Token::Lparen {
+            self.next_token(); // eat name
+            let arguments = self.parse_comma_arguments(Token::Rparen)?;
+            Ok(Expression::MethodCall(Box::new(left), name, arguments))
+        } else {
+            Ok(Expression::Field(Box::new(left), name))
+        }
+    }
     fn parse_index_expression(&mut self, left: Expression) -> ParseResult {
         self.next_token();
         let index = self.parse_expression(Precedence::Lowest)?;
@@ -296,14 +798,18 @@ impl Parser {
     /// 解析函数调用参数列表
     fn parse_comma_arguments(&mut self, end_token: Token) -> ParseResult<Vec<Expression>> {
         self.next_token(); // eat start_token
+                           //括号内不会有分支的箭头, 守卫中的 f(x => x) 也是箭头函数
+        let in_guard = std::mem::replace(&mut self.in_guard, false);
         let mut arguments = vec![];
         while self.token != end_token {
             if self.token != Token::Comma {
-                let expr = self.parse_expression(Precedence::Lowest)?;
+                let expr = self.parse_expression(Precedence::Lowest);
+                let expr = expr.inspect_err(|_| self.in_guard = in_guard)?;
                 arguments.push(expr);
             }
             self.next_token(); // eat expr or ,
         }
+        self.in_guard = in_guard;
         Ok(arguments)
     }
     /// 解析函数表达式
@@ -328,14 +834,23 @@ impl Parser {
         }
         self.expect_peek_is(Token::Lbrace)?;
         let consequence = self.parse_block_statement()?;
-        self.next_token(); // eat }
 
         // parse else block
         let mut alternative = None;
-        if self.token == Token::Else {
-            self.next_token(); //eat else
-            let else_block = self.parse_block_statement()?;
-            alternative = Some(else_block);
+        if self.peek_token == Token::Else {
+            self.next_token(); //eat }
+            if self.peek_token == Token::If {
+                // else if 转换为else块中嵌套的if表达式
+                self.next_token(); //eat else
+                let else_if = self.parse_if_expression()?;
+                alternative = Some(BlockStatement {
+                    statements: vec![Statement::Expression(else_if)],
+                });
+            } else {
+                self.expect_peek_is(Token::Lbrace)?; //eat else
+                let else_block = self.parse_block_statement()?;
+                alternative = Some(else_block);
+            }
         }
         Ok(Expression::If(
             Box::new(condition),
@@ -343,6 +858,117 @@ impl Parser {
             alternative,
         ))
     }
+    /// 解析match表达式, 以表达式为分支体时各分支之间需要用逗号分隔
+    ///
+    /// match value { pattern if guard => expr, pattern => { block } }
+    fn parse_match_expression(&mut self) -> ParseResult {
+        self.next_token(); // eat match
+        let subject = self.parse_expression(Precedence::Lowest)?;
+        self.expect_peek_is(Token::Lbrace)?;
+        self.next_token(); // eat {
+        let mut arms = vec![];
+        while self.token != Token::Rbrace {
+            if self.token != Token::Comma {
+                arms.push(self.parse_match_arm()?);
+            }
+            self.next_token();
+        }
+        Ok(Expression::Match(Box::new(subject), arms))
+    }
+    /// 解析match分支
+    fn parse_match_arm(&mut self) -> ParseResult<MatchArm> {
+        let pattern = self.parse_pattern()?;
+        let mut bindings = pattern.bindings();
+        bindings.sort_unstable();
+        if let Some(name) = bindings.windows(2).find(|w| w[0] == w[1]) {
+            return Err(ParserError::DuplicateBinding(name[0].to_string()));
+        }
+        let mut guard = None;
+        if self.peek_token == Token::If {
+            self.next_token(); // eat pattern
+            self.next_token(); // eat if
+            let in_guard = std::mem::replace(&mut self.in_guard, true);
+            let expression = self.parse_expression(Precedence::Lowest);
+            self.in_guard = in_guard;
+            guard = Some(expression?);
+        }
+        self.expect_peek_is(Token::FatArrow)?;
+        let body = self.parse_fat_arrow_body()?;
+        Ok(MatchArm {
+            pattern,
+            guard,
+            body,
+        })
+    }
+    /// 解析模式, 多个模式用 | 分隔
+    fn parse_pattern(&mut self) -> ParseResult<Pattern> {
+        let mut alternatives = vec![self.parse_single_pattern()?];
+        while self.peek_token == Token::BitOr {
+            self.next_token(); // eat pattern
+            self.next_token(); // eat |
+            alternatives.push(self.parse_single_pattern()?);
+        }
+        if alternatives.len() == 1 {
+            return Ok(alternatives.remove(0));
+        }
+        //各分支必须绑定相同的变量
+        let mut expected = alternatives[0].bindings();
+        expected.sort_unstable();
+        for alternative in &alternatives[1..] {
+            let mut bindings = alternative.bindings();
+            bindings.sort_unstable();
+            if bindings != expected {
+                let name = expected
+                    .iter()
+                    .find(|name| !bindings.contains(name))
+                    .or_else(|| bindings.iter().find(|name| !expected.contains(name)))
+                    .map_or(String::new(), |name| name.to_string());
+                return Err(ParserError::OrPatternBindings(name));
+            }
+        }
+        Ok(Pattern::Or(alternatives))
+    }
+    fn parse_single_pattern(&mut self) -> ParseResult<Pattern> {
+        match &self.token {
+            Token::Ident(name) if name == "_" => Ok(Pattern::Wildcard),
+            Token::Ident(name) => Ok(Pattern::Binding(name.clone())),
+            Token::Int(_)
+            | Token::Float(_)
+            | Token::String(_)
+            | Token::True
+            | Token::False
+            | Token::Minus => {
+                let literal = self.parse_expression(Precedence::Prefix)?;
+                Ok(Pattern::Literal(literal))
+            }
+            Token::Lbracket => {
+                self.next_token(); // eat [
+                let mut items = vec![];
+                while self.token != Token::Rbracket {
+                    if self.token != Token::Comma {
+                        items.push(self.parse_pattern()?);
+                    }
+                    self.next_token(); // eat pattern or ,
+                }
+                Ok(Pattern::Array(items))
+            }
+            Token::Lbrace => {
+                self.next_token(); // eat {
+                let mut pairs = vec![];
+                while self.token != Token::Rbrace {
+                    if self.token != Token::Comma {
+                        let key = self.parse_expression(Precedence::Lowest)?;
+                        self.expect_peek_is(Token::Colon)?;
+                        self.next_token(); // eat :
+                        pairs.push((key, self.parse_pattern()?));
+                    }
+                    self.next_token(); // eat pattern or ,
+                }
+                Ok(Pattern::Hash(pairs))
+            }
+            token => Err(ParserError::ExpectedPattern(token.clone())),
+        }
+    }
     /*一元表达式相关*/
     /// 解析一元表达式
     fn parse_unary_expression(&mut self) -> ParseResult {
@@ -361,17 +987,24 @@ impl Parser {
             Token::True => Parser::parse_boolean,
             Token::False => Parser::parse_boolean,
             Token::String(_) => Parser::parse_string_literal,
+            Token::Template(_) => Parser::parse_template_literal,
 
             Token::Bang => Parser::parse_unary_expression,
             Token::Minus => Parser::parse_unary_expression,
+            Token::Tilde => Parser::parse_unary_expression,
 
             Token::Lparen => Parser::parse_grouped_expression,
             Token::Lbracket => Parser::parse_array_literal,
             Token::Lbrace => Parser::parse_hash_literal,
 
             Token::If => Parser::parse_if_expression,
+            Token::Match => Parser::parse_match_expression,
             Token::Function => Parser::parse_function_expression,
 
+            Token::DotDot | Token::DotDotEq => Parser::parse_range_prefix,
+            Token::Ellipsis => Parser::parse_spread_expression,
+
+            Token::Illegal(ref err) => return Err(ParserError::Lex(err.clone())),
             _ => return Err(ExpectedUnaryOp(self.token.clone())),
         };
         Ok(upf)
@@ -381,16 +1014,76 @@ impl Parser {
         match token {
             Token::Bang => Ok(UnaryOperator::Not),
             Token::Minus => Ok(UnaryOperator::Neg),
+            Token::Tilde => Ok(UnaryOperator::BitNot),
             other => Err(ParserError::ExpectedUnaryOp(other.clone())),
         }
     }
 
+    /// 解析展开表达式 ...expr
+    fn parse_spread_expression(&mut self) -> ParseResult {
+        self.next_token(); // eat ...
+        let expr = self.parse_expression(Precedence::Lowest)?;
+        Ok(Expression::Spread(Box::new(expr)))
+    }
+    /// 解析省略起点的区间表达式 ..end
+    fn parse_range_prefix(&mut self) -> ParseResult {
+        self.parse_range(None)
+    }
+    /// 解析区间表达式 start..end, start..=end, start..
+    fn parse_range_expression(&mut self, start: Expression) -> ParseResult {
+        self.parse_range(Some(Box::new(start)))
+    }
+    fn parse_range(&mut self, start: Option<Box<Expression>>) -> ParseResult {
+        let inclusive = self.token == Token::DotDotEq;
+        let mut end = None;
+        //后面没有表达式时省略终点, 如 arr[1..] 或 for i in 0.. { }
+        let omit_end = matches!(
+            self.peek_token,
+            Token::Rbracket
+                | Token::Rparen
+                | Token::Lbrace
+                | Token::Rbrace
+                | Token::Comma
+                | Token::Semicolon
+                | Token::Eof
+        );
+        if !omit_end {
+            self.next_token(); // eat .. or ..=
+            end = Some(Box::new(self.parse_expression(Precedence::Range)?));
+        }
+        Ok(Expression::Range(start, end, inclusive))
+    }
+
     /*二元表达式相关*/
     /// 解析二元表达式
     fn parse_binary_expression(&mut self, left: Expression) -> ParseResult {
         let (precedence, operator) = self.binary_token(&self.token);
+        //管道 value |> f(args) 改写为 f(value, args), 右边不是调用时为 f(value)
+        if self.token == Token::Pipeline {
+            self.next_token(); // eat |>
+            let right = self.parse_expression(precedence)?;
+            let expression = match right {
+                Expression::Call(function, mut arguments) => {
+                    arguments.insert(0, left);
+                    Expression::Call(function, arguments)
+                }
+                Expression::MethodCall(receiver, method, mut arguments) => {
+                    arguments.insert(0, left);
+                    Expression::MethodCall(receiver, method, arguments)
+                }
+                function => Expression::Call(Box::new(function), vec![left]),
+            };
+            return Ok(expression);
+        }
         let operator = operator.ok_or_else(|| ParserError::ExpectedBinaryOp(self.token.clone()))?;
         self.next_token(); //eat op
+
+        // ** 右结合: 2 ** 3 ** 2 == 2 ** (3 ** 2)
+        let precedence = if operator == BinaryOperator::Pow {
+            Precedence::Prefix
+        } else {
+            precedence
+        };
         let right = self.parse_expression(precedence)?;
         let expression = Expression::Binary(operator, Box::new(left), Box::new(right));
         Ok(expression)
@@ -399,16 +1092,35 @@ impl Parser {
     fn binary_parse_fn(&self) -> Option<BinaryParseFn> {
         match self.peek_token {
             Token::Assign
+            | Token::PlusAssign
+            | Token::MinusAssign
+            | Token::AsteriskAssign
+            | Token::SlashAssign
+            | Token::PercentAssign
             | Token::Plus
             | Token::Minus
             | Token::Slash
             | Token::Asterisk
+            | Token::Percent
+            | Token::Power
+            | Token::BitAnd
+            | Token::BitOr
+            | Token::BitXor
+            | Token::ShiftLeft
+            | Token::ShiftRight
             | Token::Eq
             | Token::NotEq
+            | Token::And
+            | Token::Or
             | Token::Lt
-            | Token::Gt => Some(Parser::parse_binary_expression),
+            | Token::Le
+            | Token::Gt
+            | Token::Ge
+            | Token::Pipeline => Some(Parser::parse_binary_expression),
             Token::Lparen => Some(Parser::parse_call_expression),
             Token::Lbracket => Some(Parser::parse_index_expression),
+            Token::Dot => Some(Parser::parse_field_expression),
+            Token::DotDot | Token::DotDotEq => Some(Parser::parse_range_expression),
             _ => None,
         }
     }
@@ -416,6 +1128,15 @@ impl Parser {
     fn binary_token(&self, token: &Token) -> (Precedence, Option<BinaryOperator>) {
         match token {
             Token::Assign => (Precedence::Assign, Some(BinaryOperator::Assign)),
+            Token::PlusAssign => (Precedence::Assign, Some(BinaryOperator::PlusAssign)),
+            Token::MinusAssign => (Precedence::Assign, Some(BinaryOperator::MinusAssign)),
+            Token::AsteriskAssign => (Precedence::Assign, Some(BinaryOperator::MulAssign)),
+            Token::SlashAssign => (Precedence::Assign, Some(BinaryOperator::DivAssign)),
+            Token::PercentAssign => (Precedence::Assign, Some(BinaryOperator::ModAssign)),
+            Token::Pipeline => (Precedence::Pipeline, None),
+            Token::DotDot | Token::DotDotEq => (Precedence::Range, None),
+            Token::Or => (Precedence::LogicalOr, Some(BinaryOperator::Or)),
+            Token::And => (Precedence::LogicalAnd, Some(BinaryOperator::And)),
             Token::Eq => (Precedence::Equals, Some(BinaryOperator::Eq)),
             Token::NotEq => (Precedence::Equals, Some(BinaryOperator::NotEq)),
             Token::Lt => (Precedence::LessGreater, Some(BinaryOperator::Lt)),
@@ -426,16 +1147,27 @@ impl Parser {
             Token::Minus => (Precedence::Sum, Some(BinaryOperator::Minus)),
             Token::Slash => (Precedence::Product, Some(BinaryOperator::Div)),
             Token::Asterisk => (Precedence::Product, Some(BinaryOperator::Mul)),
+            Token::Percent => (Precedence::Product, Some(BinaryOperator::Mod)),
+            Token::Power => (Precedence::Power, Some(BinaryOperator::Pow)),
+            Token::BitOr => (Precedence::BitOr, Some(BinaryOperator::BitOr)),
+            Token::BitXor => (Precedence::BitXor, Some(BinaryOperator::BitXor)),
+            Token::BitAnd => (Precedence::BitAnd, Some(BinaryOperator::BitAnd)),
+            Token::ShiftLeft => (Precedence::Shift, Some(BinaryOperator::Shl)),
+            Token::ShiftRight => (Precedence::Shift, Some(BinaryOperator::Shr)),
             Token::Lparen => (Precedence::Call, None),
-            Token::Lbracket => (Precedence::Index, None),
+            Token::Lbracket | Token::Dot => (Precedence::Index, None),
             _ => (Precedence::Lowest, None),
         }
     }
 
     /*基本解析*/
-    /// 解析标识符
+    /// 解析标识符, 或者 x => x * 2 形式的箭头函数
     fn parse_identifier(&mut self) -> ParseResult {
-        self.parse_identifier_string().map(Expression::Identifier)
+        let name = self.parse_identifier_string()?;
+        if self.peek_token == Token::FatArrow && !self.in_guard {
+            return self.parse_arrow_function(vec![Parameter::new(name)]);
+        }
+        Ok(Expression::Identifier(name))
     }
     /// 解析标识符字符串
     fn parse_identifier_string(&mut self) -> ParseResult<String> {
@@ -449,7 +1181,15 @@ impl Parser {
     /// 解析整型字面量
     fn parse_integer_literal(&mut self) -> ParseResult {
         if let Token::Int(int) = &self.token {
-            match int.parse() {
+            //格式已由词法分析检查, 去掉下划线和进制前缀
+            let digits = int.replace('_', "");
+            let (digits, radix) = match digits.get(..2) {
+                Some("0x" | "0X") => (&digits[2..], 16),
+                Some("0o" | "0O") => (&digits[2..], 8),
+                Some("0b" | "0B") => (&digits[2..], 2),
+                _ => (&digits[..], 10),
+            };
+            match i64::from_str_radix(digits, radix) {
                 Ok(val) => Ok(Expression::IntLiteral(val)),
                 Err(_) => Err(ParserError::ParseInt(int.to_string())),
             }
@@ -460,9 +1200,9 @@ impl Parser {
     /// 解析浮点数字面量
     fn parse_float_literal(&mut self) -> ParseResult {
         if let Token::Float(float) = &self.token {
-            match float.parse() {
-                Ok(val) => Ok(Expression::FloatLiteral(val)),
-                Err(_) => Err(ParserError::ParseInt(float.to_string())),
+            match float.replace('_', "").parse::<f64>() {
+                Ok(val) if val.is_finite() => Ok(Expression::FloatLiteral(val)),
+                _ => Err(ParserError::ParseFloat(float.to_string())),
             }
         } else {
             Err(ParserError::ExpectedFloat(self.token.clone()))
@@ -476,6 +1216,31 @@ impl Parser {
             Err(ParserError::ExpectedString(self.token.clone()))
         }
     }
+    /// 解析模板字符串, ${}中的表达式单独解析
+    fn parse_template_literal(&mut self) -> ParseResult {
+        let parts = match &self.token {
+            Token::Template(parts) => parts.clone(),
+            token => return Err(ParserError::ExpectedString(token.clone())),
+        };
+        let parts = parts
+            .into_iter()
+            .map(|part| match part {
+                TemplatePart::Str(string) => Ok(Expression::StringLiteral(string)),
+                TemplatePart::Expr(source) => {
+                    let mut parser = Parser::from(&source);
+                    let expr = parser.parse_expression(Precedence::Lowest)?;
+                    if let Some(err) = parser.errors.into_iter().next() {
+                        return Err(err);
+                    }
+                    match parser.peek_token {
+                        Token::Eof => Ok(expr),
+                        token => Err(ParserError::Expected(Token::Rbrace, token)),
+                    }
+                }
+            })
+            .collect::<ParseResult<Vec<_>>>()?;
+        Ok(Expression::Template(parts))
+    }
     ///解析布尔值
     fn parse_boolean(&mut self) -> ParseResult {
         match &self.token {
@@ -5,7 +5,7 @@ mod tests {
 
     use crate::parser::ast::*;
     use crate::parser::ast::{BlockStatement, Expression};
-    use crate::parser::{lexer::*, Parser};
+    use crate::parser::{lexer::*, Parser, ParserError, Token};
 
     #[test]
     fn function_call() {
@@ -23,7 +23,10 @@ mod tests {
     fn name_function() {
         let function = Statement::Function(
             "add".to_string(),
-            vec!["x".to_string(), "y".to_string()],
+            vec![
+                Parameter::new("x".to_string()),
+                Parameter::new("y".to_string()),
+            ],
             BlockStatement {
                 statements: vec![Statement::Expression(Expression::Binary(
                     BinaryOperator::Plus,
@@ -42,7 +45,7 @@ mod tests {
     fn for_statement() {
         let inputs = &[
             (
-                r#"for (let i = 1; i < 2; i = i + 1){};"#,
+                r#"for (let i = 1; i < 2; i = i + 1){}"#,
                 Statement::For(
                     Some(Box::new(Statement::Let(
                         "i".to_string(),
@@ -107,6 +110,141 @@ mod tests {
         test_parse_statement_str(inputs);
     }
     #[test]
+    fn while_statement() {
+        let inputs = &[
+            (
+                "while (i < 10) { i = i + 1 }",
+                Statement::While(
+                    Expression::Binary(
+                        BinaryOperator::Lt,
+                        Box::new(Expression::Identifier("i".to_string())),
+                        Box::new(Expression::IntLiteral(10)),
+                    ),
+                    BlockStatement {
+                        statements: vec![Statement::Expression(Expression::Binary(
+                            BinaryOperator::Assign,
+                            Box::new(Expression::Identifier("i".to_string())),
+                            Box::new(Expression::Binary(
+                                BinaryOperator::Plus,
+                                Box::new(Expression::Identifier("i".to_string())),
+                                Box::new(Expression::IntLiteral(1)),
+                            )),
+                        ))],
+                    },
+                ),
+            ),
+            (
+                "outer: while true { if x { break outer } continue; }",
+                Statement::Labeled(
+                    "outer".to_string(),
+                    Box::new(Statement::While(
+                        Expression::BoolLiteral(true),
+                        BlockStatement {
+                            statements: vec![
+                                Statement::Expression(Expression::If(
+                                    Box::new(Expression::Identifier("x".to_string())),
+                                    BlockStatement {
+                                        statements: vec![Statement::Break(Some(
+                                            "outer".to_string(),
+                                        ))],
+                                    },
+                                    None,
+                                )),
+                                Statement::Continue(None),
+                            ],
+                        },
+                    )),
+                ),
+            ),
+            ("break typo", Statement::Break(Some("typo".to_string()))),
+            //换行后的标识符不是标签
+            (
+                "while true { break\n x }",
+                Statement::While(
+                    Expression::BoolLiteral(true),
+                    BlockStatement {
+                        statements: vec![
+                            Statement::Break(None),
+                            Statement::Expression(Expression::Identifier("x".to_string())),
+                        ],
+                    },
+                ),
+            ),
+        ];
+        test_parse_statement_str(inputs);
+    }
+    #[test]
+    fn for_in_statement() {
+        let inputs = &[
+            (
+                "for x in arr { x }",
+                Statement::ForIn(
+                    vec!["x".to_string()],
+                    Expression::Identifier("arr".to_string()),
+                    BlockStatement {
+                        statements: vec![Statement::Expression(Expression::Identifier(
+                            "x".to_string(),
+                        ))],
+                    },
+                ),
+            ),
+            (
+                "for (k, v) in {1: 2} { k };",
+                Statement::ForIn(
+                    vec!["k".to_string(), "v".to_string()],
+                    Expression::HashLiteral(vec![(
+                        Expression::IntLiteral(1),
+                        Expression::IntLiteral(2),
+                    )]),
+                    BlockStatement {
+                        statements: vec![Statement::Expression(Expression::Identifier(
+                            "k".to_string(),
+                        ))],
+                    },
+                ),
+            ),
+        ];
+        test_parse_statement_str(inputs);
+    }
+    #[test]
+    fn struct_statement() {
+        let inputs = &[
+            (
+                "struct Point { x, y }",
+                Statement::Struct(
+                    "Point".to_string(),
+                    vec!["x".to_string(), "y".to_string()],
+                    vec![],
+                ),
+            ),
+            (
+                "struct Point { x; y; fn add(dx, dy) { this.x + dx } };",
+                Statement::Struct(
+                    "Point".to_string(),
+                    vec!["x".to_string(), "y".to_string()],
+                    vec![(
+                        "add".to_string(),
+                        vec![
+                            Parameter::new("dx".to_string()),
+                            Parameter::new("dy".to_string()),
+                        ],
+                        BlockStatement {
+                            statements: vec![Statement::Expression(Expression::Binary(
+                                BinaryOperator::Plus,
+                                Box::new(Expression::Field(
+                                    Box::new(Expression::Identifier("this".to_string())),
+                                    "x".to_string(),
+                                )),
+                                Box::new(Expression::Identifier("dx".to_string())),
+                            ))],
+                        },
+                    )],
+                ),
+            ),
+        ];
+        test_parse_statement_str(inputs);
+    }
+    #[test]
     fn test_something() {
         let inputs = "let add = fn(a,b) { a + b; }; add(2,4);";
         let mut parser = Parser::from(inputs);
@@ -154,6 +292,377 @@ mod tests {
         test_parse(&tests);
     }
 
+    #[test]
+    fn field_and_method_call() {
+        let tests = [
+            (
+                "p.name",
+                Expression::Field(
+                    Box::new(Expression::Identifier("p".to_string())),
+                    "name".to_string(),
+                ),
+            ),
+            (
+                "arr.push(1, 2)",
+                Expression::MethodCall(
+                    Box::new(Expression::Identifier("arr".to_string())),
+                    "push".to_string(),
+                    vec![Expression::IntLiteral(1), Expression::IntLiteral(2)],
+                ),
+            ),
+        ];
+        test_parse_str(&tests);
+    }
+
+    #[test]
+    fn match_expression() {
+        let arm = |pattern, guard, value| MatchArm {
+            pattern,
+            guard,
+            body: BlockStatement {
+                statements: vec![Statement::Expression(value)],
+            },
+        };
+        let tests = [(
+            "match x { 1 | -2 => a, [y, _] if y > 0 => { y } {\"k\": z} => z }",
+            Expression::Match(
+                Box::new(Expression::Identifier("x".to_string())),
+                vec![
+                    arm(
+                        Pattern::Or(vec![
+                            Pattern::Literal(Expression::IntLiteral(1)),
+                            Pattern::Literal(Expression::Unary(
+                                UnaryOperator::Neg,
+                                Box::new(Expression::IntLiteral(2)),
+                            )),
+                        ]),
+                        None,
+                        Expression::Identifier("a".to_string()),
+                    ),
+                    arm(
+                        Pattern::Array(vec![Pattern::Binding("y".to_string()), Pattern::Wildcard]),
+                        Some(Expression::Binary(
+                            Gt,
+                            Box::new(Expression::Identifier("y".to_string())),
+                            Box::new(Expression::IntLiteral(0)),
+                        )),
+                        Expression::Identifier("y".to_string()),
+                    ),
+                    arm(
+                        Pattern::Hash(vec![(
+                            Expression::StringLiteral("k".to_string()),
+                            Pattern::Binding("z".to_string()),
+                        )]),
+                        None,
+                        Expression::Identifier("z".to_string()),
+                    ),
+                ],
+            ),
+        )];
+        test_parse_str(&tests);
+    }
+
+    #[test]
+    fn match_pattern_error() {
+        let tests = [
+            (
+                "match x { [a, a] => a }",
+                ParserError::DuplicateBinding("a".to_string()),
+            ),
+            (
+                "match x { [a] | b => a }",
+                ParserError::OrPatternBindings("a".to_string()),
+            ),
+        ];
+        for (input, expected) in tests {
+            let mut parser = Parser::from(input);
+            parser.parse_program();
+            let errors = parser.errors();
+            assert_eq!(format!("{:?}", errors[0]), format!("{:?}", expected));
+        }
+    }
+
+    #[test]
+    fn destructure_statement() {
+        let tests = [
+            (
+                "let [a, [b], c = 1, ...d] = x;",
+                Statement::Destructure(
+                    LetPattern::Array(
+                        vec![
+                            (LetPattern::Name("a".to_string()), None),
+                            (
+                                LetPattern::Array(
+                                    vec![(LetPattern::Name("b".to_string()), None)],
+                                    None,
+                                ),
+                                None,
+                            ),
+                            (
+                                LetPattern::Name("c".to_string()),
+                                Some(Expression::IntLiteral(1)),
+                            ),
+                        ],
+                        Some("d".to_string()),
+                    ),
+                    Expression::Identifier("x".to_string()),
+                ),
+            ),
+            (
+                "let {name, \"age\": a = 0, pos: [x, y]} = p",
+                Statement::Destructure(
+                    LetPattern::Hash(vec![
+                        (
+                            "name".to_string(),
+                            LetPattern::Name("name".to_string()),
+                            None,
+                        ),
+                        (
+                            "age".to_string(),
+                            LetPattern::Name("a".to_string()),
+                            Some(Expression::IntLiteral(0)),
+                        ),
+                        (
+                            "pos".to_string(),
+                            LetPattern::Array(
+                                vec![
+                                    (LetPattern::Name("x".to_string()), None),
+                                    (LetPattern::Name("y".to_string()), None),
+                                ],
+                                None,
+                            ),
+                            None,
+                        ),
+                    ]),
+                    Expression::Identifier("p".to_string()),
+                ),
+            ),
+        ];
+        test_parse_statement_str(&tests);
+    }
+
+    #[test]
+    fn try_statement() {
+        let block = |name: &str| BlockStatement {
+            statements: vec![Statement::Expression(Expression::Identifier(
+                name.to_string(),
+            ))],
+        };
+        let tests = [
+            (
+                "try { a } catch (e) { e } finally { c }",
+                Statement::Try(
+                    block("a"),
+                    Some((Some("e".to_string()), block("e"))),
+                    Some(block("c")),
+                ),
+            ),
+            (
+                "try { a } catch { b }",
+                Statement::Try(block("a"), Some((None, block("b"))), None),
+            ),
+            (
+                "try { a } finally { c }",
+                Statement::Try(block("a"), None, Some(block("c"))),
+            ),
+            (
+                "throw a + 1;",
+                Statement::Throw(Expression::Binary(
+                    Plus,
+                    Box::new(Expression::Identifier("a".to_string())),
+                    Box::new(Expression::IntLiteral(1)),
+                )),
+            ),
+        ];
+        test_parse_statement_str(&tests);
+    }
+
+    #[test]
+    fn block_statement() {
+        let tests = [
+            (
+                "{ let x = 5; x }",
+                Statement::Block(BlockStatement {
+                    statements: vec![
+                        Statement::Let("x".to_string(), Expression::IntLiteral(5)),
+                        Statement::Expression(Expression::Identifier("x".to_string())),
+                    ],
+                }),
+            ),
+            //能解析为Hash字面量时仍是表达式
+            (
+                "{1: 2}",
+                Statement::Expression(Expression::HashLiteral(vec![(
+                    Expression::IntLiteral(1),
+                    Expression::IntLiteral(2),
+                )])),
+            ),
+        ];
+        test_parse_statement_str(&tests);
+    }
+
+    #[test]
+    fn import_statement() {
+        let tests = [
+            (
+                "import \"lib/math.my\" as math;",
+                Statement::Import(
+                    "lib/math.my".to_string(),
+                    Import::Module("math".to_string()),
+                ),
+            ),
+            (
+                "import { add, sub as minus } from \"math.my\"",
+                Statement::Import(
+                    "math.my".to_string(),
+                    Import::Names(vec![
+                        ("add".to_string(), "add".to_string()),
+                        ("sub".to_string(), "minus".to_string()),
+                    ]),
+                ),
+            ),
+            (
+                "export let pi = 3",
+                Statement::Export(Box::new(Statement::Let(
+                    "pi".to_string(),
+                    Expression::IntLiteral(3),
+                ))),
+            ),
+        ];
+        test_parse_statement_str(&tests);
+        let program = Program::_new("export let a = 1; export let [b, c] = x; let d = 2");
+        assert_eq!(program.exports(), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn const_statement() {
+        let tests = [
+            (
+                "const half = 0.5;",
+                Statement::Const("half".to_string(), Expression::FloatLiteral(0.5)),
+            ),
+            (
+                "export const e = 2",
+                Statement::Export(Box::new(Statement::Const(
+                    "e".to_string(),
+                    Expression::IntLiteral(2),
+                ))),
+            ),
+        ];
+        test_parse_statement_str(&tests);
+    }
+
+    #[test]
+    fn template_literal() {
+        let tests = [
+            (
+                "`a ${b + 1}`",
+                Template(vec![
+                    StringLiteral("a ".to_string()),
+                    Binary(
+                        Plus,
+                        Box::new(Identifier("b".to_string())),
+                        Box::new(IntLiteral(1)),
+                    ),
+                ]),
+            ),
+            (
+                "`${len(xs)} items`",
+                Template(vec![
+                    Call(
+                        Box::new(Identifier("len".to_string())),
+                        vec![Identifier("xs".to_string())],
+                    ),
+                    StringLiteral(" items".to_string()),
+                ]),
+            ),
+            ("`plain`", StringLiteral("plain".to_string())),
+        ];
+        test_parse_str(&tests);
+    }
+
+    #[test]
+    fn function_parameters() {
+        let tests = [
+            (
+                "fn(a, b = a * 2, ...c) { c }",
+                Expression::FunctionLiteral(
+                    vec![
+                        Parameter::new("a".to_string()),
+                        Parameter {
+                            default: Some(Expression::Binary(
+                                Mul,
+                                Box::new(Expression::Identifier("a".to_string())),
+                                Box::new(Expression::IntLiteral(2)),
+                            )),
+                            ..Parameter::new("b".to_string())
+                        },
+                        Parameter {
+                            rest: true,
+                            ..Parameter::new("c".to_string())
+                        },
+                    ],
+                    BlockStatement {
+                        statements: vec![Statement::Expression(Expression::Identifier(
+                            "c".to_string(),
+                        ))],
+                    },
+                ),
+            ),
+            (
+                "f(1, ...xs, [...ys])",
+                Expression::Call(
+                    Box::new(Expression::Identifier("f".to_string())),
+                    vec![
+                        Expression::IntLiteral(1),
+                        Expression::Spread(Box::new(Expression::Identifier("xs".to_string()))),
+                        Expression::ArrayLiteral(vec![Expression::Spread(Box::new(
+                            Expression::Identifier("ys".to_string()),
+                        ))]),
+                    ],
+                ),
+            ),
+        ];
+        test_parse_str(&tests);
+    }
+
+    #[test]
+    fn parser_errors() {
+        let tests = [
+            (
+                "try { a } b",
+                ParserError::Expected(Token::Catch, Token::Ident("b".to_string())),
+            ),
+            ("const a;", ParserError::ExpectedAssign(Token::Semicolon)),
+            ("`${}`", ParserError::ExpectedUnaryOp(Token::Eof)),
+            (
+                r#"let s = "\q""#,
+                ParserError::Lex(LexError::InvalidEscape("\\q".to_string())),
+            ),
+            ("1 + /* 2", ParserError::Lex(LexError::UnterminatedComment)),
+            (
+                "9223372036854775808",
+                ParserError::ParseInt("9223372036854775808".to_string()),
+            ),
+            ("0x1_0000_0000_0000_0000", ParserError::ParseInt("0x1_0000_0000_0000_0000".to_string())),
+            ("1e400", ParserError::ParseFloat("1e400".to_string())),
+            (
+                "let a = 1.2.3",
+                ParserError::Lex(LexError::InvalidNumber("1.2.3".to_string(), 1, 9)),
+            ),
+            (
+                "`${a b}`",
+                ParserError::Expected(Token::Rbrace, Token::Ident("b".to_string())),
+            ),
+        ];
+        for (input, expected) in tests {
+            let mut parser = Parser::from(input);
+            parser.parse_program();
+            let errors = parser.errors();
+            assert_eq!(format!("{:?}", errors[0]), format!("{:?}", expected));
+        }
+    }
+
     #[test]
     fn test_array_literal() {
         let tests = [(
@@ -213,7 +722,10 @@ mod tests {
             (
                 "fn(x, y) { x=1; x + y; }".to_string(),
                 Expression::FunctionLiteral(
-                    vec!["x".to_string(), "y".to_string()],
+                    vec![
+                        Parameter::new("x".to_string()),
+                        Parameter::new("y".to_string()),
+                    ],
                     BlockStatement {
                         statements: vec![
                             Statement::Expression(Expression::Binary(
@@ -268,9 +780,35 @@ mod tests {
         );
         let mut parser = Parser::from(input);
         let program = parser.parse_program();
-        println!("{}", program.to_string());
+        println!("{}", program);
         check_parser_error(parser);
-        assert_eq!(expected.to_string(), program.to_string());
+        assert_eq!(format!("{};", expected), program.to_string());
+    }
+
+    #[test]
+    fn test_else_if_expression() {
+        let input = "if a { 1 } else if b { 2 } else { 3 }";
+        let expected = Expression::If(
+            Box::new(Expression::Identifier("a".to_string())),
+            BlockStatement {
+                statements: vec![Statement::Expression(Expression::IntLiteral(1))],
+            },
+            Some(BlockStatement {
+                statements: vec![Statement::Expression(Expression::If(
+                    Box::new(Expression::Identifier("b".to_string())),
+                    BlockStatement {
+                        statements: vec![Statement::Expression(Expression::IntLiteral(2))],
+                    },
+                    Some(BlockStatement {
+                        statements: vec![Statement::Expression(Expression::IntLiteral(3))],
+                    }),
+                ))],
+            }),
+        );
+        let mut parser = Parser::from(input);
+        let program = parser.parse_program();
+        check_parser_error(parser);
+        assert_eq!(program.statements, vec![Statement::Expression(expected)]);
     }
 
     #[test]
@@ -293,15 +831,53 @@ mod tests {
             ("-a * b", "((-a) * b)"),
             ("! -a", "(!(-a))"),
             ("a+b+c", "((a + b) + c)"),
+            ("1..n + 1", "(1..(n + 1))"),
+            ("a += b * c", "(a += (b * c))"),
+            ("a[i] -= 1", "((a[i]) -= 1)"),
+            ("a % b * c", "((a % b) * c)"),
+            ("-2 ** 2", "(-(2 ** 2))"),
+            ("2 ** 3 ** 2", "(2 ** (3 ** 2))"),
+            ("a * b ** c", "(a * (b ** c))"),
+            ("a & b == c", "((a & b) == c)"),
+            ("a | b ^ c & d", "(a | (b ^ (c & d)))"),
+            ("1 << 2 + 3", "(1 << (2 + 3))"),
+            ("a >> 1 & 1", "((a >> 1) & 1)"),
+            ("~a & b", "((~a) & b)"),
+            ("a >= b == c <= d", "((a >= b) == (c <= d))"),
+            ("a + 1 <= b", "((a + 1) <= b)"),
+            ("a..=b", "(a..=b)"),
+            ("..5", "(..5)"),
+            ("a[1..]", "(a[(1..)])"),
+            ("a[..len(a) - 1]", "(a[(..(len(a) - 1))])"),
             ("a+b-c", "((a + b) - c)"),
             ("a*b*c", "((a * b) * c)"),
             ("a*b/c", "((a * b) / c)"),
             ("a+b/c", "(a + (b / c))"),
             ("a+b*c+d/e-f", "(((a + (b * c)) + (d / e)) - f)"),
-            ("3+4;-5*5", "(3 + 4)((-5) * 5)"),
+            ("3+4;-5*5", "(3 + 4);((-5) * 5)"),
             ("5>4 == 3<4", "((5 > 4) == (3 < 4))"),
             ("5<4 != 3>4", "((5 < 4) != (3 > 4))"),
             ("3+4*5 == 3*1+4*5", "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))"),
+            ("a || b && c", "(a || (b && c))"),
+            ("a && b || c", "((a && b) || c)"),
+            ("a < b && c == d", "((a < b) && (c == d))"),
+            ("x = a || b", "(x = (a || b))"),
+            ("-a.b * c", "((-a.b) * c)"),
+            ("a.b[1].c", "(a.b[1]).c"),
+            ("a.b.c += 1", "(a.b.c += 1)"),
+            ("arr.push(x + 1).len()", "arr.push((x + 1)).len()"),
+            ("f().x", "f().x"),
+            ("x |> f(y) |> g", "g(f(x, y))"),
+            ("a + 1 |> f |> s.m(b)", "s.m(f((a + 1)), b)"),
+            ("x => x * 2", "fn(x) { (x * 2) }"),
+            ("(a, b) => { a + b }", "fn(a, b) { (a + b) }"),
+            ("() => 1", "fn() { 1 }"),
+            ("xs |> map(x => x + 1)", "map(xs, fn(x) { (x + 1) })"),
+            ("(a + b) * c", "((a + b) * c)"),
+            (
+                "match x { a if f(y => y) => a, _ => 0 }",
+                "match x { a if f(fn(y) { y }) => { a }, _ => { 0 } }",
+            ),
             (
                 "add(a + b + c * d / f + g)",
                 "add((((a + b) + ((c * d) / f)) + g))",
@@ -311,7 +887,7 @@ mod tests {
             let mut parser = Parser::from(input);
             let program = parser.parse_program();
             check_parser_error(parser);
-            assert_eq!(program.to_string(), expect);
+            assert_eq!(program.to_string(), format!("{};", expect));
         }
     }
 
@@ -438,7 +1014,7 @@ mod tests {
             // println!("{:#?}", program.statements[0]);
             // println!("{:#?}", expected);
             check_parser_error(parser);
-            assert_eq!(program.to_string(), expected.to_string());
+            assert_eq!(program.to_string(), format!("{};", expected));
         }
     }
 
@@ -447,7 +1023,7 @@ mod tests {
             let mut parser = Parser::from(input);
             let program = parser.parse_program();
             check_parser_error(parser);
-            assert_eq!(program.to_string(), expected.to_string());
+            assert_eq!(program.to_string(), format!("{};", expected));
         }
     }
 
@@ -456,7 +1032,7 @@ mod tests {
             let mut parser = Parser::from(input);
             let program = parser.parse_program();
             check_parser_error(parser);
-            assert_eq!(program.to_string(), expected.to_string());
+            assert_eq!(program.to_string(), format!("{};", expected));
         }
     }
     /// 辅助函数 测试let
@@ -4,6 +4,7 @@ use std::io::Write;
 use crate::{create_rc_ref_cell, Engine, exe_with_eval, exe_with_vm, parse_file};
 use crate::compiler::symbol_table::SymbolTable;
 use crate::eval::Environment;
+use crate::module;
 use crate::parser::Parser;
 
 const PROMPT: &str = ">> ";
@@ -28,6 +29,7 @@ pub fn start(engine: Engine) {
         Some(file_path) => {
             input =
                 std::fs::read_to_string(&file_path).unwrap_or_else(|_| panic!("文件 {} 不存在", &file_path));
+            module::set_main_file(&file_path);
             true
         }
     };
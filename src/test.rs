@@ -0,0 +1,1456 @@
+/// 求值器和虚拟机共用的测试: 同一组输入在两种执行方式下的结果应相同
+///
+/// 只有一种执行方式才有的情况(字节码、编译错误等)放在各自模块的测试中
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use crate::compiler::Compiler;
+    use crate::eval::evaluator;
+    use crate::eval::evaluator::EvalResult;
+    use crate::eval::Environment;
+    use crate::object::{Container, HashKey, Object, Range, RuntimeError, StructType};
+    use crate::parser::ast::{BinaryOperator, Program};
+    use crate::vm::Vm;
+
+    #[test]
+    fn while_statement() {
+        let inputs = [
+            (
+                r"
+            let i = 1;
+            let sum = 0;
+            while (i <= 10) {
+                sum += i;
+                i += 1;
+            }
+            let f = 0.5;
+            while (f <= 2) {
+                f += 0.5
+            }
+            [sum, f]
+            ",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(55),
+                    Object::Float(2.5),
+                ]))),
+            ),
+            (
+                r"
+            let i = 0;
+            let sum = 0;
+            while (i < 10) {
+                sum = sum + i;
+                i = i + 1;
+            }
+            sum
+            ",
+                Object::Integer(45),
+            ),
+            (
+                r"
+            let i = 0;
+            while (true) {
+                if i == 5 { break }
+                i = i + 1
+            }
+            i
+            ",
+                Object::Integer(5),
+            ),
+            (
+                r"
+            let find = fn(target) {
+                let i = 0;
+                while (true) {
+                    if i == target { return i }
+                    i = i + 1
+                }
+            }
+            find(3)
+            ",
+                Object::Integer(3),
+            ),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn break_and_continue() {
+        let inputs = [
+            (
+                r"
+            let sum = 0;
+            for (let i = 0; i < 10; i = i + 1) {
+                if i == 3 { continue }
+                if i == 6 { break }
+                sum = sum + i
+            }
+            sum
+            ",
+                Object::Integer(12),
+            ),
+            (
+                r"
+            let count = 0;
+            outer: for (let i = 0; i < 3; i = i + 1) {
+                for (let j = 0; j < 3; j = j + 1) {
+                    if j == 1 { continue outer }
+                    if i == 2 { break outer }
+                    count = count + 1
+                }
+            }
+            count
+            ",
+                Object::Integer(2),
+            ),
+            (
+                r"
+            let n = 0;
+            outer: while (true) {
+                while (true) {
+                    n = n + 1;
+                    if n == 3 { break outer }
+                }
+            }
+            n
+            ",
+                Object::Integer(3),
+            ),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn for_in_statement() {
+        let inputs = [
+            (
+                r"
+            let sum = 0;
+            for x in [1, 2, 3] {
+                sum = sum + x
+            }
+            sum
+            ",
+                Object::Integer(6),
+            ),
+            (
+                r"
+            let sum = 0;
+            for (i, x) in [10, 20, 30] {
+                sum = sum + i * x
+            }
+            sum
+            ",
+                Object::Integer(80),
+            ),
+            (
+                r#"
+            let sum = 0;
+            for (k, v) in {1: 10, 2: 20} {
+                sum = sum + k * v
+            }
+            let keys = 0;
+            for k in {1: 10, 2: 20} {
+                keys = keys + k
+            }
+            sum + keys
+            "#,
+                Object::Integer(53),
+            ),
+            (
+                r#"
+            let s = "";
+            for ch in "abc" {
+                s = ch + s
+            }
+            s
+            "#,
+                Object::String("cba".to_string()),
+            ),
+            (
+                r#"
+            let s = "";
+            for k in {"c": 1, "e": 2, "a": 3, "d": 4, "b": 5, 2: 0, 1: 0} {
+                s = s + k
+            }
+            s
+            "#,
+                Object::String("12abcde".to_string()),
+            ),
+            (
+                r"
+            let count = fn() {
+                let n = 0;
+                outer: for x in [1, 2, 3] {
+                    for y in [1, 2, 3] {
+                        if y == 2 { continue outer }
+                        if x == 3 { break outer }
+                        n = n + 1
+                    }
+                }
+                n
+            }
+            count()
+            ",
+                Object::Integer(2),
+            ),
+        ];
+        check_input(&inputs);
+        check_error(&[(
+            "for x in 1 { x }",
+            RuntimeError::NotIterable(Object::Integer(1)),
+        )]);
+    }
+    #[test]
+    fn range_expression() {
+        let inputs = [
+            (
+                r"
+            let sum = 0;
+            for i in 0..5 { sum = sum + i }
+            for i in 1..=5 { sum = sum + i }
+            for (i, x) in 10..13 { sum = sum + i * x }
+            sum
+            ",
+                Object::Integer(60),
+            ),
+            (
+                r"
+            let sum = 0;
+            for i in 0.. {
+                if i == 5 { break }
+                sum = sum + i
+            }
+            sum
+            ",
+                Object::Integer(10),
+            ),
+            ("let n = 3; len(0..n + 1)", Object::Integer(4)),
+            ("len(1..=3)", Object::Integer(3)),
+            ("len(5..1)", Object::Integer(0)),
+            ("first(3..6)", Object::Integer(3)),
+            ("last(3..6)", Object::Integer(5)),
+            ("last(3..=6)", Object::Integer(6)),
+            ("first(5..5)", Object::Null),
+            (
+                "1..4",
+                Object::Range(Range {
+                    start: Some(1),
+                    end: Some(4),
+                    inclusive: false,
+                }),
+            ),
+            (
+                "[1, 2, 3, 4, 5][1..3]",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(2),
+                    Object::Integer(3),
+                ]))),
+            ),
+            (
+                "[1, 2, 3][1..]",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(2),
+                    Object::Integer(3),
+                ]))),
+            ),
+            (
+                "[1, 2, 3][..=1]",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                ]))),
+            ),
+            (
+                "[1, 2][5..9]",
+                Object::Array(Rc::new(Container::new(vec![]))),
+            ),
+            (r#""hello world"[..5]"#, Object::String("hello".to_string())),
+        ];
+        check_input(&inputs);
+        check_error(&[(
+            "1.5..2",
+            RuntimeError::InvalidRangeBound(Object::Float(1.5)),
+        )]);
+    }
+    #[test]
+    fn mutable_closures() {
+        let inputs = [
+            (
+                r"
+            let make_counter = fn() {
+                let count = 0;
+                fn() { count += 1; count }
+            };
+            let c = make_counter();
+            c();
+            c();
+            c()
+            ",
+                Object::Integer(3),
+            ),
+            (
+                r"
+            let f = fn() {
+                let x = 1;
+                let set = fn(v) { x = v };
+                set(5);
+                x
+            };
+            f()
+            ",
+                Object::Integer(5),
+            ),
+            (
+                r"
+            let pair = fn() {
+                let n = 0;
+                let inc = fn() { n += 1 };
+                let get = fn() { n };
+                inc();
+                inc();
+                get()
+            };
+            pair()
+            ",
+                Object::Integer(2),
+            ),
+            (
+                r"
+            let outer = fn() {
+                let total = 0;
+                let add = fn(xs) {
+                    for x in xs {
+                        let apply = fn() { total += x };
+                        apply()
+                    }
+                };
+                add([1, 2, 3]);
+                total
+            };
+            outer()
+            ",
+                Object::Integer(6),
+            ),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn field_and_method() {
+        let inputs = [
+            (
+                r#"let p = {"name": "ann"}; p.name"#,
+                Object::String("ann".to_string()),
+            ),
+            (r#"let p = {"name": "ann"}; p.age"#, Object::Null),
+            (
+                r#"let p = {}; p.age = 3; p.age += 1; p["age"]"#,
+                Object::Integer(4),
+            ),
+            (
+                r#"let cfg = {"db": {"ports": [80]}}; cfg.db.ports[0] += 1; cfg["db"].ports[0]"#,
+                Object::Integer(81),
+            ),
+            (
+                "let arr = [1, 2]; arr.push(3).len() + arr.len()",
+                Object::Integer(6),
+            ),
+            (
+                r#""abc".first() + "abc".rest()"#,
+                Object::String("abc".to_string()),
+            ),
+            (r#"{"a": 1, "b": 2}.values().len()"#, Object::Integer(2)),
+            (
+                r#"let counter = {"n": 1, "inc": fn(k) { k + 1 }}; counter.inc(counter.n)"#,
+                Object::Integer(2),
+            ),
+        ];
+        check_input(&inputs);
+        check_error(&[
+            (
+                "1.len()",
+                RuntimeError::NoSuchMember(Object::Integer(1), "len".to_string()),
+            ),
+            (
+                "let a = [1]; a.x",
+                RuntimeError::NoSuchMember(
+                    Object::Array(Rc::new(Container::new(vec![Object::Integer(1)]))),
+                    "x".to_string(),
+                ),
+            ),
+        ]);
+    }
+    #[test]
+    fn struct_types() {
+        let inputs = [
+            (
+                "struct Point { x, y }; let p = Point(1, 2); p.x + p[\"y\"]",
+                Object::Integer(3),
+            ),
+            (
+                "struct Point { x, y }; let p = Point(1, 2); p.x = 5; p.y *= 3; p.x * 10 + p.y",
+                Object::Integer(56),
+            ),
+            (
+                r"
+            struct Point {
+                x, y
+                fn add(other) { Point(this.x + other.x, this.y + other.y) }
+                fn scale(k) { this.x *= k; this.y = this.y * k; this }
+            }
+            let p = Point(1, 2).add(Point(2, 3));
+            p.scale(2).x + p.y
+            ",
+                Object::Integer(16),
+            ),
+            (
+                "struct P { a; fn get() { P(this.a + 1) } }; let q = P(1); q.get().get().a",
+                Object::Integer(3),
+            ),
+            (
+                "let f = fn() { struct V { a; fn next() { V(this.a * 2) } }; V(3).next() }; f().a",
+                Object::Integer(6),
+            ),
+            (
+                "struct P { f; fn call() { this.f(2) } }; P(fn(n) { n * 5 }).call()",
+                Object::Integer(10),
+            ),
+            (
+                "struct P { x }; let a = P([1]); let b = a; b.x.push(2); a == P([1, 2])",
+                Object::Boolean(true),
+            ),
+            (
+                "struct Point { x }; type(Point(1)) + type(Point) + type(1)",
+                Object::String("PointSTRUCTINTEGER".to_string()),
+            ),
+            //块内声明的this遮蔽接收者, 可以赋值
+            (
+                "struct P { x; fn m() { if (true) { let this = 1; this = 2; this } } }; P(1).m()",
+                Object::Integer(2),
+            ),
+            //const变量和构造调用的字段按槽位访问
+            (
+                "struct P { x, y }; const p = P(1, 2); p.y = 5; p.x += 10; [p.x, p.y, P(3, 4).y]",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(11),
+                    Object::Integer(5),
+                    Object::Integer(4),
+                ]))),
+            ),
+            (
+                r#"struct P { x, y }; { let P = fn(a, b) { {"y": a} }; const q = P(7, 8); q.y }"#,
+                Object::Integer(7),
+            ),
+            (
+                "let f = fn() { struct V { a, b }; const v = V(1, 2); fn() { v.b += 1 } }; f()()",
+                Object::Integer(3),
+            ),
+        ];
+        check_input(&inputs);
+        check_error(&[
+            (
+                "struct P { x }; P(1, 2)",
+                RuntimeError::WrongArgumentCount(1, 2),
+            ),
+            (
+                "struct P { x }; P(1).y",
+                RuntimeError::NoSuchMember(
+                    Rc::new(StructType::new(
+                        "P".to_string(),
+                        vec!["x".to_string()],
+                        vec![],
+                    ))
+                    .instantiate(vec![Object::Integer(1)])
+                    .unwrap(),
+                    "y".to_string(),
+                ),
+            ),
+        ]);
+    }
+    #[test]
+    fn match_expression() {
+        let inputs = [
+            (
+                r#"
+            fn describe(v) {
+                match v {
+                    0 => "zero",
+                    1 | 2 | -3 => "small",
+                    "a" | "b" => "letter",
+                    [x, y] if x == y => "pair",
+                    [x, _] => x,
+                    {"dx": dx, "dy": 0} => { let d = dx * 2; d },
+                    n if n > 100 => "big",
+                    _ => "other"
+                }
+            }
+            [describe(0), describe(-3), describe("b"), describe([3, 3]), describe([4, 5]),
+             describe({"dx": 4, "dy": 0}), describe(500), describe(50)]
+            "#,
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::String("zero".to_string()),
+                    Object::String("small".to_string()),
+                    Object::String("letter".to_string()),
+                    Object::String("pair".to_string()),
+                    Object::Integer(4),
+                    Object::Integer(8),
+                    Object::String("big".to_string()),
+                    Object::String("other".to_string()),
+                ]))),
+            ),
+            ("match 5 { 1 => 1 }", Object::Null),
+            ("let x = 1; match 5 { x => x } + x", Object::Integer(6)),
+            (
+                "match [1, [2, 3]] { [a, [b, c]] => a + b + c }",
+                Object::Integer(6),
+            ),
+            (
+                "let f = fn(v) { match v { [[a], {\"k\": b}] | [b, a, _] => a * 10 + b } }; f([[1], {\"k\": 2}]) + f([3, 4, 0])",
+                Object::Integer(55),
+            ),
+            (
+                "match {\"k\": 1} { {\"j\": j} => j, {\"k\": 2} => 2, _ => 3 }",
+                Object::Integer(3),
+            ),
+            (
+                "let fs = []; for i in 0..3 { match i { k => fs.push(fn() { k * 2 }) } }; fs[0]() + fs[1]() + fs[2]()",
+                Object::Integer(6),
+            ),
+            (
+                "let r = 0; for i in 0..5 { r += match i % 2 { 0 => i, _ => 0 } }; r",
+                Object::Integer(6),
+            ),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn destructuring_let() {
+        let inputs = [
+            (
+                "let [a, b, ...others] = [1, 2, 3, 4]; a * 100 + b * 10 + len(others)",
+                Object::Integer(122),
+            ),
+            (
+                "let arr = [1, 2, 3]; let [a, b, ...rest] = arr; [a, b, rest]",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                    Object::Array(Rc::new(Container::new(vec![Object::Integer(3)]))),
+                ]))),
+            ),
+            (
+                r#"let person = {"name": "ann", "age": 3}; let {name, age} = person; [name, age]"#,
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::String("ann".to_string()),
+                    Object::Integer(3),
+                ]))),
+            ),
+            (
+                "let f = fn(arr) { let [first, ...rest] = arr; [first, len(rest)] }; f([7, 8, 9])",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(7),
+                    Object::Integer(2),
+                ]))),
+            ),
+            (
+                "let {name, age} = {\"name\": \"ann\", \"age\": 3}; name + \"/\" + type(age)",
+                Object::String("ann/INTEGER".to_string()),
+            ),
+            (
+                "let [x, [y, z = 9], w = x * 10] = [1, [2]]; [x, y, z, w]",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                    Object::Integer(9),
+                    Object::Integer(10),
+                ]))),
+            ),
+            (
+                "let {pos: [px, py], \"tag\": t = 7} = {\"pos\": [5, 6]}; px + py + t",
+                Object::Integer(18),
+            ),
+            (
+                "struct P { x, y }; let {x: sx, y} = P(7, 8); sx * y",
+                Object::Integer(56),
+            ),
+            //实例没有的成员与Hash一样使用默认值
+            (
+                "struct P { x, y }; let {x, z = 7} = P(1, 2); x + z",
+                Object::Integer(8),
+            ),
+            (
+                "let [...all] = []; len(all)",
+                Object::Integer(0),
+            ),
+            (
+                "let f = fn(pair) { let [p, q] = pair; p - q }; f([10, 4])",
+                Object::Integer(6),
+            ),
+            (
+                "let fs = []; for i in 0..3 { let [k] = [i]; fs.push(fn() { k }) }; fs[0]() + fs[1]() + fs[2]()",
+                Object::Integer(3),
+            ),
+        ];
+        check_input(&inputs);
+        check_error(&[
+            (
+                "let [a, b] = [1]",
+                RuntimeError::ArrayOutOfBound { len: 1, index: 1 },
+            ),
+            (
+                "let [a] = 5",
+                RuntimeError::CannotDestructure("ARRAY".to_string(), Object::Integer(5)),
+            ),
+            (
+                "let {a = 1} = 5",
+                RuntimeError::CannotDestructure("HASH".to_string(), Object::Integer(5)),
+            ),
+            (
+                "let {a} = {\"b\": 1}",
+                RuntimeError::NoSuchMember(
+                    Object::Hash(Rc::new(Container::new(HashMap::from([(
+                        HashKey::String("b".to_string()),
+                        Object::Integer(1),
+                    )])))),
+                    "a".to_string(),
+                ),
+            ),
+        ]);
+    }
+    #[test]
+    fn function_parameters() {
+        let inputs = [
+            (
+                "let f = fn(a, b = 2) { a * 10 + b }; f(1) * 100 + f(1, 5)",
+                Object::Integer(1215),
+            ),
+            (
+                "let g = fn(a, b = a * 3, c = b + 1) { [a, b, c] }; [...g(1), ...g(1, 1)]",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(1),
+                    Object::Integer(3),
+                    Object::Integer(4),
+                    Object::Integer(1),
+                    Object::Integer(1),
+                    Object::Integer(2),
+                ]))),
+            ),
+            (
+                "let h = fn(a, ...others) { a + len(others) }; h(1) * 10 + h(1, 2, 3)",
+                Object::Integer(13),
+            ),
+            (
+                "let add = fn(a, b, c) { a * 100 + b * 10 + c }; let xs = [2, 3]; add(...xs, 4) + add(1, ...xs)",
+                Object::Integer(357),
+            ),
+            (
+                "let xs = [2, 3]; [0, ...xs, ...[], 4]",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(0),
+                    Object::Integer(2),
+                    Object::Integer(3),
+                    Object::Integer(4),
+                ]))),
+            ),
+            (
+                "let count = fn(...all) { len(all) }; count() + count(...[1, 2], 3)",
+                Object::Integer(3),
+            ),
+            (
+                "struct P { x; fn add(a, b = 10) { this.x + a + b } fn sum(...ns) { this.x + len(ns) } }; let p = P(1); p.add(1) + p.add(1, 1) + p.sum(...[1, 2])",
+                Object::Integer(18),
+            ),
+            (
+                "let h = {\"f\": fn(a, b = 1) { a - b }}; h.f(...[5]) * 10 + h.f(5, 2)",
+                Object::Integer(43),
+            ),
+            (
+                "let c = fn() { let x = 1; fn(a = x) { a } }; c()() + c()(7)",
+                Object::Integer(8),
+            ),
+            ("len(...[[1, 2]])", Object::Integer(2)),
+            //参数名与内置函数同名时遮蔽内置函数
+            (
+                "fn f(a, ...rest) { rest }; f(1, 2, 3)",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(2),
+                    Object::Integer(3),
+                ]))),
+            ),
+            ("let g = fn(len) { len }; g(5)", Object::Integer(5)),
+            ("let s = 0; for first in 1..4 { s += first }; s", Object::Integer(6)),
+        ];
+        check_input(&inputs);
+        check_error(&[
+            (
+                "let f = fn(a, b) { a }; f(1)",
+                RuntimeError::WrongArgumentCount(2, 1),
+            ),
+            (
+                "let f = fn(a, b = 1) { a }; f(1, 2, 3)",
+                RuntimeError::WrongArgumentCount(2, 3),
+            ),
+            (
+                "let f = fn(a, ...r) { a }; f()",
+                RuntimeError::WrongArgumentCount(1, 0),
+            ),
+            (
+                "struct P { x; fn m(a) { a } }; P(1).m()",
+                RuntimeError::WrongArgumentCount(2, 1),
+            ),
+            (
+                "let f = fn(a) { a }; f(...5)",
+                RuntimeError::CannotSpread(Object::Integer(5)),
+            ),
+        ]);
+    }
+    #[test]
+    fn exceptions() {
+        let inputs = [
+            (
+                "let e = 0; try { 1 / 0 } catch err { e = err }; [e.kind, e.message, type(e)]",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::String("ByZero".to_string()),
+                    Object::String("by zero: 1 / 0".to_string()),
+                    Object::String("ERROR".to_string()),
+                ]))),
+            ),
+            (
+                "let r = 0; try { let [a, b] = [1] } catch e { r = e.kind }; r",
+                Object::String("ArrayOutOfBound".to_string()),
+            ),
+            (
+                "let r = 0; try { throw {\"code\": 7} } catch (e) { r = e[\"code\"] }; r",
+                Object::Integer(7),
+            ),
+            (
+                "let log = []; try { log.push(1) } catch { log.push(2) } finally { log.push(3) }; log",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(1),
+                    Object::Integer(3),
+                ]))),
+            ),
+            (
+                "let r = 0; try { try { throw 1 } catch e { throw e + 1 } } catch e { r = e }; r",
+                Object::Integer(2),
+            ),
+            (
+                "let log = []; try { try { throw 1 } finally { log.push(\"inner\") } } catch e { log.push(e) }; log",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::String("inner".to_string()),
+                    Object::Integer(1),
+                ]))),
+            ),
+            (
+                "let g = fn(x) { if x == 0 { throw \"deep\" } 1 + g(x - 1) }; let r = 0; try { r = 10 + g(5) } catch e { r = e }; r",
+                Object::String("deep".to_string()),
+            ),
+            (
+                "let log = []; let f = fn() { try { return 1 } finally { log.push(2) } }; log.push(f()); log",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(2),
+                    Object::Integer(1),
+                ]))),
+            ),
+            (
+                "let f = fn() { try { throw 1 } catch e { return e + 1 } finally { 10 } }; f()",
+                Object::Integer(2),
+            ),
+            (
+                "let log = []; for i in 0..4 { try { if i == 1 { continue } if i == 3 { break } log.push(i) } finally { log.push(-i) } }; log",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(0),
+                    Object::Integer(0),
+                    Object::Integer(-1),
+                    Object::Integer(2),
+                    Object::Integer(-2),
+                    Object::Integer(-3),
+                ]))),
+            ),
+            (
+                "let sum = 0; for x in [1, 0, 2] { try { sum += 4 / x } catch { sum += 100 } }; sum",
+                Object::Integer(106),
+            ),
+            ("try { 1 } finally { 2 }", Object::Null),
+        ];
+        check_input(&inputs);
+        check_error(&[
+            (
+                "throw \"boom\"",
+                RuntimeError::Thrown(Object::String("boom".to_string())),
+            ),
+            (
+                "let r = 0; try { throw 1 } finally { r = 2 }; r",
+                RuntimeError::Thrown(Object::Integer(1)),
+            ),
+            (
+                "try { 1 / 0 } catch e { throw e }",
+                RuntimeError::Thrown(Object::Error(
+                    "ByZero".to_string(),
+                    "by zero: 1 / 0".to_string(),
+                )),
+            ),
+        ]);
+    }
+    #[test]
+    fn modules() {
+        let inputs = [
+            (
+                "import \"res/modules/math.my\" as m; m.square(3) + m.pi",
+                Object::Integer(12),
+            ),
+            (
+                "import { square as sq, one } from \"res/modules/math.my\"; sq(one + 2)",
+                Object::Integer(9),
+            ),
+            (
+                "import { Point, origin } from \"res/modules/geometry.my\"; Point(1, 2).norm2() + origin.x",
+                Object::Integer(5),
+            ),
+            (
+                r#"
+            import "res/modules/math.my" as m
+            import { count, square } from "res/modules/math.my"
+            let before = count()
+            m.square(1)
+            square(2);
+            [count() - before, type(m), m.two]
+            "#,
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(2),
+                    Object::String("MODULE".to_string()),
+                    Object::Integer(2),
+                ]))),
+            ),
+            //读取成员得到模块内变量的当前值(模块只执行一次, 所以比较前后的差)
+            (
+                r#"
+            import "res/modules/counter.my" as c
+            let before = [c.n, c.state["n"]]
+            c.inc()
+            c.bump(...[]);
+            [c.n - before[0], c.state["n"] - before[1]]
+            "#,
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(1),
+                    Object::Integer(1),
+                ]))),
+            ),
+            (
+                r#"
+            import { n, inc } from "res/modules/counter.my"
+            let before = n
+            inc()
+            inc(...[]);
+            n - before
+            "#,
+                Object::Integer(2),
+            ),
+            (
+                "import \"res/modules/math.my\" as m; let r = 0; try { m.calls } catch e { r = e.kind }; r",
+                Object::String("NoSuchMember".to_string()),
+            ),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn number_literals() {
+        let inputs = [
+            ("0xFF + 0b1010 + 0o7 + 1_000", Object::Integer(1272)),
+            ("2.5e-3 * 1e3", Object::Float(2.5)),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn arrow_and_pipeline() {
+        let inputs = [
+            ("let double = x => x * 2; double(21)", Object::Integer(42)),
+            ("let add = (a, b) => a + b; 1 |> add(2)", Object::Integer(3)),
+            ("5 |> (x => x * 2) |> (x => x + 1)", Object::Integer(11)),
+            ("[1, 2, 3] |> len", Object::Integer(3)),
+            (
+                "let f = (n) => { let m = n + 1; m * m }; f(2)",
+                Object::Integer(9),
+            ),
+            ("let k = 10; let g = () => k; g()", Object::Integer(10)),
+            ("let inc = x => y => x + y; inc(1)(2)", Object::Integer(3)),
+            ("[1, 2] |> push(3) |> len", Object::Integer(3)),
+            (
+                "match 3 { n if n > 2 => n * 10, _ => 0 }",
+                Object::Integer(30),
+            ),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn template_literal() {
+        let inputs = [
+            (
+                r#"let name = "alice"; let items = [1, 2]; `hello ${name}, you have ${len(items)} items`"#,
+                Object::String("hello alice, you have 2 items".to_string()),
+            ),
+            (
+                r#"`${1.5} ${true} ${[1, "a"]}`"#,
+                Object::String("1.5 true [1, a]".to_string()),
+            ),
+            (
+                r#"let h = {"k": 1}; `${h["k"] + 1}${`-${h["k"]}`}`"#,
+                Object::String("2-1".to_string()),
+            ),
+            (r"`a\${b}\``", Object::String("a${b}`".to_string())),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn constants() {
+        let inputs = [
+            ("const a = 1; const b = a + 1; a + b", Object::Integer(3)),
+            (
+                "const greet = \"hi\"; let f = fn() { const mark = \"!\"; fn() { greet + mark } }; f()()",
+                Object::String("hi!".to_string()),
+            ),
+            (
+                "const fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(10)",
+                Object::Integer(55),
+            ),
+            ("const a = 1; if (true) { let a = 2; a = 3 }; a", Object::Integer(1)),
+            (
+                "const arr = [1, 2]; arr[0] = 3; arr.push(4); arr",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(3),
+                    Object::Integer(2),
+                    Object::Integer(4),
+                ]))),
+            ),
+            (
+                "let a = freeze([1, [2]]); a[1][0] = 3; a[1][0]",
+                Object::Integer(3),
+            ),
+            (
+                "let h = {\"a\": 1}.freeze(); let r = 0; try { h[\"a\"] = 2 } catch e { r = e.kind }; r",
+                Object::String("FrozenValue".to_string()),
+            ),
+        ];
+        check_input(&inputs);
+        check_error(&[
+            (
+                "let a = freeze([1]); a[0] = 2",
+                RuntimeError::FrozenValue(Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(1),
+                ])))),
+            ),
+            (
+                "let a = [1]; freeze(a); push(a, 2)",
+                RuntimeError::FrozenValue(Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(1),
+                ])))),
+            ),
+        ]);
+    }
+    #[test]
+    fn block_scopes() {
+        let inputs = [
+            (
+                "let a = 1; let f = fn(a) { a * 10 }; f(2) + a",
+                Object::Integer(21),
+            ),
+            (
+                "let x = 1; if (true) { let x = 2; x = 3 }; x",
+                Object::Integer(1),
+            ),
+            (
+                r"
+            let r = 0;
+            if (true) { let x = 1; r += x }
+            if (true) { let x = 2; r += x }
+            r
+            ",
+                Object::Integer(3),
+            ),
+            (
+                r"
+            let s = 0;
+            for i in 0..3 { let t = i * 2; s += t }
+            for (let i = 0; i < 3; i += 1) { let t = i; s += t }
+            s
+            ",
+                Object::Integer(9),
+            ),
+            (
+                r"
+            let fs = [];
+            for i in 0..3 { let j = i; push(fs, fn() { j }) }
+            fs[0]() * 100 + fs[1]() * 10 + fs[2]()
+            ",
+                Object::Integer(12),
+            ),
+            (
+                r"
+            let f = fn() {
+                let fs = [];
+                let n = 10;
+                while (n < 13) { let m = n; push(fs, fn() { m }); n += 1 }
+                fs[0]() + fs[2]()
+            };
+            f()
+            ",
+                Object::Integer(22),
+            ),
+            //单独的语句块有自己的作用域
+            ("{ let x = 5; x }", Object::Integer(5)),
+            ("let x = 1; { let x = 2; x = 3 }; x", Object::Integer(1)),
+            ("let x = 1; { x = 2; { x += 1 } }; x", Object::Integer(3)),
+            (r#"{"a": 1}["a"]"#, Object::Integer(1)),
+            ("let f = fn() { { return 4 }; 5 }; f()", Object::Integer(4)),
+            //内置函数名也可以被遮蔽, 离开块后恢复
+            ("let f = fn() { let len = 1; len }; f()", Object::Integer(1)),
+            ("{ let len = 2; len }", Object::Integer(2)),
+            ("{ let len = 2 }; len([1, 2, 3])", Object::Integer(3)),
+            ("let first = 7; first", Object::Integer(7)),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn reference_semantics() {
+        let inputs = [
+            (
+                "let a = [[1, 2]]; let b = a[0]; b[1] = 9; a[0][1]",
+                Object::Integer(9),
+            ),
+            (
+                "let a = [1]; let set = fn(arr) { arr[0] = 5; arr }; set(a); a[0]",
+                Object::Integer(5),
+            ),
+            (
+                "let a = [1]; push(a, 2); a",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                ]))),
+            ),
+            (
+                "let items = []; let add = fn(x) { push(items, x) }; add(1); add(2); len(items)",
+                Object::Integer(2),
+            ),
+            (
+                r#"let h = {"n": 1}; let g = h; g["n"] = 2; h["n"]"#,
+                Object::Integer(2),
+            ),
+            (
+                "let row = [0]; let grid = [row, row]; grid[0][0] = 7; grid[1][0]",
+                Object::Integer(7),
+            ),
+            //切片得到副本
+            (
+                "let a = [1]; let b = a[..]; b[0] = 2; a[0]",
+                Object::Integer(1),
+            ),
+            //按值比较相等
+            ("[1, [2]] == [1, [2]]", Object::Boolean(true)),
+            (r#"{"a": [1]} != {"a": [2]}"#, Object::Boolean(true)),
+            ("let a = [1]; let b = a; a == b", Object::Boolean(true)),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn cyclic_references() {
+        let inputs = [
+            //再次遇到正在比较的数据视为相等, 输出为省略的形式
+            ("let a = [1]; push(a, a); a == a", Object::Boolean(true)),
+            (
+                "let a = [1]; push(a, a); let b = [1]; push(b, b); a == b",
+                Object::Boolean(true),
+            ),
+            (
+                "let a = [1]; push(a, a); let b = [2]; push(b, b); a != b",
+                Object::Boolean(true),
+            ),
+            (
+                "let a = [1]; push(a, a); `${a}`",
+                Object::String("[1, [...]]".to_string()),
+            ),
+            (
+                r#"let h = {"n": 1}; h["h"] = h; `${h == h} ${h["h"]["h"]["n"]}`"#,
+                Object::String("true 1".to_string()),
+            ),
+            (
+                r#"let h = {}; h["h"] = h; `${h}`"#,
+                Object::String(r#"{"h": {...}}"#.to_string()),
+            ),
+            (
+                "struct N { v, next }; let n = N(1, 0); n.next = n; `${n == n} ${n}`",
+                Object::String("true N { v: 1, next: {...} }".to_string()),
+            ),
+            ("let f = fn() { 1 }; f == f", Object::Boolean(true)),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn nested_assign() {
+        let inputs = [
+            (
+                "let grid = [[1, 2], [3, 4]]; grid[1][0] = 9; grid",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Array(Rc::new(Container::new(vec![
+                        Object::Integer(1),
+                        Object::Integer(2),
+                    ]))),
+                    Object::Array(Rc::new(Container::new(vec![
+                        Object::Integer(9),
+                        Object::Integer(4),
+                    ]))),
+                ]))),
+            ),
+            (
+                r#"let cfg = {"db": {"port": 1}}; cfg["db"]["port"] = 5432; cfg["db"]["port"]"#,
+                Object::Integer(5432),
+            ),
+            (
+                "let grid = [[1, 2], [3, 4]]; grid[1][0] += 10; grid[1][0]",
+                Object::Integer(13),
+            ),
+            (
+                "let f = fn() { let a = [1, [2, 3]]; a[1][1] = 7; a[0] = 0; a }; f()",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(0),
+                    Object::Array(Rc::new(Container::new(vec![
+                        Object::Integer(2),
+                        Object::Integer(7),
+                    ]))),
+                ]))),
+            ),
+            (
+                "let make = fn() { [1, 2] }; make()[0] = 5; make()[0]",
+                Object::Integer(1),
+            ),
+            (
+                "let a = [1]; a[0] = 2; a = [3, 4]; a",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(3),
+                    Object::Integer(4),
+                ]))),
+            ),
+            (
+                r"
+            let total = 0;
+            for row in [[1, 2], [3]] {
+                for x in row { total += x }
+            }
+            total
+            ",
+                Object::Integer(6),
+            ),
+            (
+                "let a = [1]; let v = [7, 8, a[0] = 5]; [v, a]",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Array(Rc::new(Container::new(vec![
+                        Object::Integer(7),
+                        Object::Integer(8),
+                        Object::Integer(5),
+                    ]))),
+                    Object::Array(Rc::new(Container::new(vec![Object::Integer(5)]))),
+                ]))),
+            ),
+            ("let a = [[1]]; a[0][0] = 2", Object::Integer(2)),
+            (
+                "let a = [[1]]; let f = fn(x) { x * 2 }; f(a[0][0] += 4)",
+                Object::Integer(10),
+            ),
+            (
+                r#"let h = {"k": {"n": 1}}; if ((h.k.n += 4) == 5) { h.k.n } else { 0 }"#,
+                Object::Integer(5),
+            ),
+            (
+                "struct P { x; fn set(v) { this.x = v } }; let p = P(1); [p.set(9), p.x]",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(9),
+                    Object::Integer(9),
+                ]))),
+            ),
+        ];
+        check_input(&inputs);
+        check_error(&[
+            (
+                "let a = [1]; a[5] = 1",
+                RuntimeError::ArrayOutOfBound { len: 1, index: 5 },
+            ),
+            (
+                "let a = [1]; a[0][0] = 1",
+                RuntimeError::UnSupportedIndexOperation(Object::Integer(1), Object::Integer(0)),
+            ),
+        ]);
+    }
+    #[test]
+    fn compound_assign() {
+        let inputs = [
+            (
+                "let a = 1; a += 2; a -= 1; a *= 10; a /= 4; a %= 3; a",
+                Object::Integer(2),
+            ),
+            (
+                r#"let s = "a"; s += "b"; s"#,
+                Object::String("ab".to_string()),
+            ),
+            (
+                "let arr = [1, 2, 3]; arr[1] += 10; arr[2] *= 2; arr",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(1),
+                    Object::Integer(12),
+                    Object::Integer(6),
+                ]))),
+            ),
+            (
+                r#"let m = {"k": 1}; m["k"] += 1; m["k"]"#,
+                Object::Integer(2),
+            ),
+            (
+                r"
+            let calls = [0];
+            let idx = fn() { calls[0] += 1; 1 };
+            let arr = [5, 5];
+            arr[idx()] += 1;
+            [arr[1], calls[0]]
+            ",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(6),
+                    Object::Integer(1),
+                ]))),
+            ),
+            (
+                "let x = 1; let a = [5, 6, (x += 2)]; [a, x]",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Array(Rc::new(Container::new(vec![
+                        Object::Integer(5),
+                        Object::Integer(6),
+                        Object::Integer(3),
+                    ]))),
+                    Object::Integer(3),
+                ]))),
+            ),
+            (
+                "let x = 1; let f = fn(a, b) { a * 10 + b }; f(9, x += 1)",
+                Object::Integer(92),
+            ),
+            (
+                r#"let x = 0; if (x += 1) { "t" } else { "f" }"#,
+                Object::String("t".to_string()),
+            ),
+            (
+                "let x = 0; let n = 0; while ((x += 1) < 3) { n += 1 }; [x, n]",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(3),
+                    Object::Integer(2),
+                ]))),
+            ),
+            (
+                "let f = fn() { let a = 1; let g = fn() { a *= 5 }; [g(), a] }; f()",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::Integer(5),
+                    Object::Integer(5),
+                ]))),
+            ),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn logical_expression() {
+        let inputs = [
+            ("true && true", Object::Boolean(true)),
+            ("true && false", Object::Boolean(false)),
+            ("false || true", Object::Boolean(true)),
+            ("false || false", Object::Boolean(false)),
+            ("1 < 2 && 3 > 2", Object::Boolean(true)),
+            ("2 < 1 && 3 > 2", Object::Boolean(false)),
+            ("1 && \"\"", Object::Boolean(false)),
+            ("false || true && false", Object::Boolean(false)),
+            ("if 1 > 2 || 2 > 1 { 10 } else { 20 }", Object::Integer(10)),
+            (
+                "let c = true; (if (c) { true } else { 1 < 2 }) && true",
+                Object::Boolean(true),
+            ),
+            (
+                "let c = false; (if (c) { false } else { 1 < 2 }) && 2 <= 1",
+                Object::Boolean(false),
+            ),
+            (
+                r"
+            let count = 0;
+            let inc = fn() { count = count + 1; true };
+            false && inc();
+            true || inc();
+            count
+            ",
+                Object::Integer(0),
+            ),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn condition_expression() {
+        let inputs = [
+            (
+                r#"let c = false; if (if (c) { true } else { 1 <= 2 }) { "yes" } else { "no" }"#,
+                Object::String("yes".to_string()),
+            ),
+            (
+                r#"let c = false; if (if (c) { true } else { 2 < 1 }) { "yes" } else { "no" }"#,
+                Object::String("no".to_string()),
+            ),
+            (
+                "let n = 0; while (if (n > 5) { false } else { n <= 3 }) { n += 1 }; n",
+                Object::Integer(4),
+            ),
+            (
+                r#"if (1 <= 1) { "le" } else { "gt" }"#,
+                Object::String("le".to_string()),
+            ),
+            (
+                r#"if (2 < 1) { "lt" } else { "ge" }"#,
+                Object::String("ge".to_string()),
+            ),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn float_arithmetic() {
+        let inputs = [
+            ("1.5", Object::Float(1.5)),
+            ("1.5 + 2.25", Object::Float(3.75)),
+            ("1 + 0.5", Object::Float(1.5)),
+            ("0.5 - 1", Object::Float(-0.5)),
+            ("3 * 0.5", Object::Float(1.5)),
+            ("7 / 2.0", Object::Float(3.5)),
+            ("-2.5", Object::Float(-2.5)),
+            ("1.5 < 2", Object::Boolean(true)),
+            ("2.5 > 2", Object::Boolean(true)),
+            ("1 == 1.0", Object::Boolean(true)),
+            ("0.1 != 0.1", Object::Boolean(false)),
+            ("if 0.5 < 1 { 10 } else { 20 }", Object::Integer(10)),
+            (r#""avg: " + 2.5"#, Object::String("avg: 2.5".to_string())),
+            (r#"0.5 + "s""#, Object::String("0.5s".to_string())),
+            ("-1.5", Object::Float(-1.5)),
+        ];
+        check_input(&inputs);
+    }
+    #[test]
+    fn integer_operators() {
+        let inputs = [
+            ("7 % 3", Object::Integer(1)),
+            ("-7 % 3", Object::Integer(-1)),
+            ("1 + 7 % 3 * 2", Object::Integer(3)),
+            ("7.5 % 2", Object::Float(1.5)),
+            ("2 ** 10", Object::Integer(1024)),
+            ("-2 ** 2", Object::Integer(-4)),
+            ("2 ** 3 ** 2", Object::Integer(512)),
+            ("2 ** -1", Object::Float(0.5)),
+            ("1.5 ** 2", Object::Float(2.25)),
+            ("6 & 3", Object::Integer(2)),
+            ("6 | 3", Object::Integer(7)),
+            ("6 ^ 3", Object::Integer(5)),
+            ("~5", Object::Integer(-6)),
+            ("1 << 4", Object::Integer(16)),
+            ("-16 >> 2", Object::Integer(-4)),
+            ("(7 >> 1) & 1 == 1", Object::Boolean(true)),
+            ("1 + 2 << 1", Object::Integer(6)),
+        ];
+        check_input(&inputs);
+        check_error(&[
+            (
+                "5 % 0",
+                RuntimeError::ByZero(BinaryOperator::Mod, Object::Integer(5), Object::Integer(0)),
+            ),
+            (
+                "1 << 64",
+                RuntimeError::ShiftOverflow(Object::Integer(1), Object::Integer(64)),
+            ),
+            (
+                "8 >> -1",
+                RuntimeError::ShiftOverflow(Object::Integer(8), Object::Integer(-1)),
+            ),
+        ]);
+    }
+    #[test]
+    fn arithmetic_errors() {
+        let inputs = [
+            ("2 ** 62", Object::Integer(1 << 62)),
+            ("(-2) ** 63", Object::Integer(i64::MIN)),
+            (
+                "let m = 0; try { 1 % 0 } catch e { m = e.message }; m",
+                Object::String("by zero: 1 % 0".to_string()),
+            ),
+            (
+                "let m = 0; try { 10 ** 100 } catch e { m = [e.kind, e.message] }; m",
+                Object::Array(Rc::new(Container::new(vec![
+                    Object::String("Overflow".to_string()),
+                    Object::String("integer overflow: 10 ** 100".to_string()),
+                ]))),
+            ),
+        ];
+        check_input(&inputs);
+        check_error(&[
+            (
+                "7 / 0",
+                RuntimeError::ByZero(BinaryOperator::Div, Object::Integer(7), Object::Integer(0)),
+            ),
+            (
+                "10 ** 100",
+                RuntimeError::Overflow(
+                    BinaryOperator::Pow,
+                    Object::Integer(10),
+                    Object::Integer(100),
+                ),
+            ),
+            (
+                "2 ** 63",
+                RuntimeError::Overflow(
+                    BinaryOperator::Pow,
+                    Object::Integer(2),
+                    Object::Integer(63),
+                ),
+            ),
+        ]);
+    }
+
+    /// 分别用求值器和虚拟机执行, 结果都应与期望相同
+    fn check_input(inputs: &[(&str, Object)]) {
+        for (input, expected) in inputs {
+            match run(input) {
+                (Ok(evaluated), Ok(top)) => {
+                    assert_eq!(&evaluated, expected, "eval input: {}", input);
+                    assert_eq!(&top, expected, "vm input: {}", input);
+                }
+                (evaluated, top) => {
+                    panic!("input: {}\neval: {:?}\nvm: {:?}", input, evaluated, top)
+                }
+            }
+        }
+    }
+
+    /// 分别用求值器和虚拟机执行, 都应产生期望的运行时错误
+    fn check_error(inputs: &[(&str, RuntimeError)]) {
+        for (input, expected) in inputs {
+            match run(input) {
+                (Err(evaluated), Err(top)) => {
+                    assert_eq!(&evaluated, expected, "eval input: {}", input);
+                    assert_eq!(&top, expected, "vm input: {}", input);
+                }
+                (evaluated, top) => {
+                    panic!("input: {}\neval: {:?}\nvm: {:?}", input, evaluated, top)
+                }
+            }
+        }
+    }
+
+    /// 求值器和虚拟机的执行结果
+    fn run(input: &str) -> (EvalResult, EvalResult) {
+        let program = Program::_new(input);
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let evaluated = evaluator::eval(&program, env);
+        let byte_code = Compiler::new()
+            .compile(&program)
+            .unwrap_or_else(|err| panic!("input: {}\ncompile error: {:?}", input, err));
+        let top = Vm::new(byte_code).run().map(|top| Object::clone(&top));
+        (evaluated, top)
+    }
+}
@@ -8,6 +8,15 @@ pub struct Frame {
     pub closure: Rc<Object>,
     pub ip: usize,
     pub base_pointer: usize,
+    // 当前帧内登记的异常处理器, 内层在后
+    pub handlers: Vec<Handler>,
+}
+
+/// 异常处理器, 出错时跳到catch_ip并把栈恢复到sp
+#[derive(Debug, Clone)]
+pub struct Handler {
+    pub catch_ip: usize,
+    pub sp: usize,
 }
 
 impl Frame {
@@ -16,6 +25,7 @@ impl Frame {
             closure,
             ip: 0,
             base_pointer,
+            handlers: vec![],
         }
     }
     pub fn instructions(&self) -> Rc<Instructions> {
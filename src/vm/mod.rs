@@ -3,12 +3,13 @@ use std::collections::HashMap;
 use std::rc::Rc;
 
 use crate::compiler::code::{read_operands, Instructions, OPS};
-use crate::object::builtins::BUILTINS;
-use crate::object::HashKey;
+use crate::object::builtins::{lookup_method, BUILTINS};
+use crate::object::{check_argument_count, Container, HashKey, Iter, Module, Range, StructType};
+use crate::parser::ast::BinaryOperator;
 use crate::{
     compiler::{code::Opcode, ByteCode, Constants},
     object::{Closure, CompiledFunction, Object, RuntimeError},
-    vm::frame::Frame,
+    vm::frame::{Frame, Handler},
 };
 
 mod frame;
@@ -66,15 +67,17 @@ impl Vm {
             int_cache.push(Rc::new(Object::Integer(i as i64)))
         }
         //
-        let main_fn = CompiledFunction::new(Rc::new(byte_code.instructions), 0, 0);
-        let main_closure = Closure::new(main_fn, vec![]);
+        //顶层语句块中的变量作为主帧的局部变量, 预留栈上的槽位
+        let num_locals = byte_code.num_locals;
+        let main_fn = CompiledFunction::new(Rc::new(byte_code.instructions), num_locals, 0);
+        let main_closure = Closure::new(Rc::new(main_fn), vec![]);
         let main_frame = Frame::new(Rc::new(Object::Closure(main_closure)), 0);
         let mut frames = Vec::with_capacity(MAX_FRAMES);
         frames.push(main_frame);
         Self {
             constants: byte_code.constants,
             stack,
-            sp: 0,
+            sp: num_locals,
             globals,
             frames,
             int_cache,
@@ -84,6 +87,30 @@ impl Vm {
         }
     }
     pub fn run(&mut self) -> VmResult {
+        loop {
+            match self.execute() {
+                Err(err) => self.unwind(err)?,
+                result => return result,
+            }
+        }
+    }
+    /// 出错时由内向外查找异常处理器, 没有处理器的函数帧直接退出
+    fn unwind(&mut self, err: RuntimeError) -> VmResult<()> {
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            if let Some(handler) = frame.handlers.pop() {
+                frame.ip = handler.catch_ip;
+                self.sp = handler.sp;
+                self.push_stack(Rc::new(err.into_object()));
+                return Ok(());
+            }
+            if self.frames.len() == 1 {
+                return Err(err);
+            }
+            self.pop_frame();
+        }
+    }
+    fn execute(&mut self) -> VmResult {
         // let mut _time_recorder = crate::TimeRecorder::_new();
         // ip means instruction_pointer
         while self.current_frame().ip < self.current_frame().instructions().len() {
@@ -134,6 +161,12 @@ impl Vm {
                     self.current_frame_ip_inc(n);
                 }
 
+                Opcode::BuildString => {
+                    let (len, n) = self.read_usize(op_code, ip);
+                    self.build_string(len);
+                    self.current_frame_ip_inc(n);
+                }
+
                 Opcode::Hash => {
                     let (hash_len, n) = self.read_usize(op_code, ip);
                     self.build_hash(hash_len)?;
@@ -147,10 +180,163 @@ impl Vm {
                     self.push_stack(result);
                 }
 
+                Opcode::Range => {
+                    let inclusive = ins[ip] == 1;
+                    self.current_frame_ip_inc(1);
+                    let end = self.pop_stack();
+                    let start = self.pop_stack();
+                    let range = Range::new(&start, &end, inclusive)?;
+                    self.push_stack(Rc::new(Object::Range(range)));
+                }
+
                 Opcode::Add => {
                     self.execute_add_operation()?;
                 }
-                Opcode::Sub | Opcode::Mul | Opcode::Div => {
+                Opcode::SetIndex => {
+                    let depth = ins[ip] as usize;
+                    self.current_frame_ip_inc(1);
+                    let value = self.pop_stack();
+                    let keys = self.stack[self.sp - depth..self.sp].to_vec();
+                    self.sp -= depth;
+                    let obj = self.pop_stack();
+                    obj.set_index_path(&keys, Object::clone(&value))?;
+                    self.push_stack(value);
+                }
+                Opcode::IndexPeek => {
+                    let depth = ins[ip] as usize;
+                    self.current_frame_ip_inc(1);
+                    let mut value = self.stack[self.sp - depth - 1].clone();
+                    for i in self.sp - depth..self.sp {
+                        value = self.execute_index_operation(&value, &self.stack[i])?;
+                    }
+                    self.push_stack(value);
+                }
+                Opcode::GetField => {
+                    let name = self.get_field_name(&ins, ip)?;
+                    self.current_frame_ip_inc(2);
+                    let obj = self.pop_stack();
+                    self.push_stack(Rc::new(obj.get_field(&name)?));
+                }
+                Opcode::SetField => {
+                    let name = self.get_field_name(&ins, ip)?;
+                    let depth = ins[ip + 2] as usize;
+                    self.current_frame_ip_inc(3);
+                    let value = self.pop_stack();
+                    let mut keys = self.stack[self.sp - depth..self.sp].to_vec();
+                    keys.push(Rc::new(Object::String(name)));
+                    self.sp -= depth;
+                    let obj = self.pop_stack();
+                    obj.set_index_path(&keys, Object::clone(&value))?;
+                    self.push_stack(value);
+                }
+                Opcode::Invoke => {
+                    let name = self.get_field_name(&ins, ip)?;
+                    let arg_nums = ins[ip + 2] as usize;
+                    self.current_frame_ip_inc(3);
+                    self.invoke_method(&name, arg_nums)?;
+                }
+                Opcode::GetSlot => {
+                    let slot = ins[ip] as usize;
+                    self.current_frame_ip_inc(1);
+                    let obj = self.pop_stack();
+                    let value = match obj.as_ref() {
+                        Object::Instance(instance) => instance.fields.borrow().get(slot).cloned(),
+                        _ => None,
+                    };
+                    match value {
+                        Some(value) => self.push_stack(Rc::new(value)),
+                        None => {
+                            return Err(RuntimeError::UnSupportedIndexOperation(
+                                Object::clone(&obj),
+                                Object::Integer(slot as i64),
+                            ));
+                        }
+                    }
+                }
+                Opcode::SetSlot => {
+                    let slot = ins[ip] as usize;
+                    self.current_frame_ip_inc(1);
+                    let value = self.pop_stack();
+                    let obj = self.pop_stack();
+                    match obj.as_ref() {
+                        Object::Instance(instance) if slot < instance.fields.borrow().len() => {
+                            instance.fields.borrow_mut()[slot] = Object::clone(&value);
+                            self.push_stack(value);
+                        }
+                        _ => {
+                            return Err(RuntimeError::UnSupportedIndexOperation(
+                                Object::clone(&obj),
+                                Object::Integer(slot as i64),
+                            ));
+                        }
+                    }
+                }
+                Opcode::MatchArray => {
+                    let len = ins[ip] as usize;
+                    self.current_frame_ip_inc(1);
+                    let value = self.pop_stack();
+                    let matched = match value.as_ref() {
+                        Object::Array(items) => items.borrow().len() == len,
+                        _ => false,
+                    };
+                    self.push_stack(self.get_bool_from_cache(matched));
+                }
+                Opcode::MatchHash => {
+                    let key_count = ins[ip] as usize;
+                    self.current_frame_ip_inc(1);
+                    let keys = self.stack[self.sp - key_count..self.sp].to_vec();
+                    self.sp -= key_count;
+                    let value = self.pop_stack();
+                    let matched = match value.as_ref() {
+                        Object::Hash(pairs) => keys.iter().all(|key| {
+                            HashKey::from_object(key)
+                                .is_ok_and(|key| pairs.borrow().contains_key(&key))
+                        }),
+                        _ => false,
+                    };
+                    self.push_stack(self.get_bool_from_cache(matched));
+                }
+                Opcode::DestructArray => {
+                    let len = ins[ip] as usize;
+                    self.current_frame_ip_inc(1);
+                    self.pop_stack().expect_array(len)?;
+                }
+                Opcode::DestructHash => {
+                    let key_count = ins[ip] as usize;
+                    self.current_frame_ip_inc(1);
+                    let keys = self.stack[self.sp - key_count..self.sp].to_vec();
+                    self.sp -= key_count;
+                    self.pop_stack().expect_members(&keys)?;
+                }
+                Opcode::DestructField => {
+                    let name = self.get_field_name(&ins, ip)?;
+                    self.current_frame_ip_inc(2);
+                    let obj = self.pop_stack();
+                    self.push_stack(Rc::new(obj.destructure_field(&name)?));
+                }
+                Opcode::Struct => {
+                    let const_index = self.read_u16(&ins, ip);
+                    let method_count = ins[ip + 2] as usize;
+                    self.current_frame_ip_inc(3);
+                    self.build_struct(const_index, method_count)?;
+                }
+                Opcode::Module => {
+                    let const_index = self.read_u16(&ins, ip);
+                    let member_count = ins[ip + 2] as usize;
+                    self.current_frame_ip_inc(3);
+                    self.build_module(const_index, member_count)?;
+                }
+
+                Opcode::Sub
+                | Opcode::Mul
+                | Opcode::Div
+                | Opcode::Mod
+                | Opcode::Pow
+                | Opcode::BitAnd
+                | Opcode::BitOr
+                | Opcode::BitXor
+                | Opcode::Shl
+                | Opcode::Shr => {
                     self.execute_binary_operation(&op_code)?;
                 }
 
@@ -160,6 +346,10 @@ impl Vm {
                     }
                 }
 
+                Opcode::Dup => {
+                    self.push_stack(self.stack[self.sp - 1].clone());
+                }
+
                 Opcode::True => {
                     self.push_stack(self.bool_cache_true.clone());
                 }
@@ -179,68 +369,94 @@ impl Vm {
 
                 Opcode::Neg => {
                     let value = self.pop_stack();
-                    if let Object::Integer(val) = *value {
-                        self.push_stack(Rc::new(Object::Integer(-val)));
-                    } else {
-                        return Err(RuntimeError::UnSupportedUnOperation(
-                            op_code,
-                            (*value).clone(),
-                        ));
+                    match *value {
+                        Object::Integer(val) => self.push_stack(Rc::new(Object::Integer(-val))),
+                        Object::Float(val) => self.push_stack(Rc::new(Object::Float(-val))),
+                        _ => {
+                            return Err(RuntimeError::UnSupportedUnOperation(
+                                op_code,
+                                (*value).clone(),
+                            ));
+                        }
                     }
                 }
                 Opcode::Not => {
                     let value = self.pop_stack();
                     self.execute_not_expression(&value)?;
                 }
+                Opcode::BitNot => {
+                    let value = self.pop_stack();
+                    match *value {
+                        Object::Integer(val) => self.push_stack(Rc::new(Object::Integer(!val))),
+                        _ => {
+                            return Err(RuntimeError::UnSupportedUnOperation(
+                                op_code,
+                                (*value).clone(),
+                            ));
+                        }
+                    }
+                }
 
                 Opcode::JumpAlways => {
                     self.frames.last_mut().unwrap().ip = self.read_u16(&ins, ip);
                 }
-                Opcode::JumpIfNotLess => {
+                Opcode::JumpIfNotLess | Opcode::JumpIfNotLessEq => {
                     let right = self.pop_stack();
                     let left = self.pop_stack();
+                    let or_eq = op_code == Opcode::JumpIfNotLessEq;
                     match (&*left, &*right) {
                         (Object::Integer(l), Object::Integer(r)) => {
-                            self.jump_if(l < r, &ins, ip);
-                        }
-                        _ => {
-                            return Err(RuntimeError::CustomErrMsg(
-                                "unsupported compare".to_string(),
-                            ));
+                            self.jump_if(l < r || (or_eq && l == r), &ins, ip);
                         }
+                        (l, r) => match Self::as_float_pair(l, r) {
+                            Some((l, r)) => self.jump_if(l < r || (or_eq && l == r), &ins, ip),
+                            None => {
+                                return Err(RuntimeError::CustomErrMsg(
+                                    "unsupported compare".to_string(),
+                                ));
+                            }
+                        },
                     }
                 }
                 Opcode::JumpIfNotTruthy => {
-                    let is_truthy = self.pop_stack();
-                    if let Object::Boolean(truthy) = *is_truthy {
-                        self.jump_if(truthy, &ins, ip);
+                    let condition = self.pop_stack();
+                    self.jump_if(condition.is_truthy(), &ins, ip);
+                }
+                Opcode::JumpIfTruthy => {
+                    let condition = self.pop_stack();
+                    self.jump_if(!condition.is_truthy(), &ins, ip);
+                }
+                Opcode::JumpIfNotNull => {
+                    let is_null = *self.stack[self.sp - 1] == Object::Null;
+                    if is_null {
+                        self.pop_stack();
                     }
+                    self.jump_if(is_null, &ins, ip);
                 }
 
                 Opcode::Null => {
                     self.push_stack(self.null_cache.clone());
-                    self.sp -= 1;
                 }
                 // set global
                 Opcode::SetGlobal => {
                     let global_index = self.read_u16(&ins, ip);
-                    self.execute_assign_operation_or_pop_and_set_global(global_index, false)?;
+                    self.pop_and_set_global(global_index);
                     self.frames.last_mut().unwrap().ip += 2;
                 }
                 Opcode::SetGlobal0 => {
-                    self.execute_assign_operation_or_pop_and_set_global(0, false)?;
+                    self.pop_and_set_global(0);
                 }
                 Opcode::SetGlobal1 => {
-                    self.execute_assign_operation_or_pop_and_set_global(1, false)?;
+                    self.pop_and_set_global(1);
                 }
                 Opcode::SetGlobal2 => {
-                    self.execute_assign_operation_or_pop_and_set_global(2, false)?;
+                    self.pop_and_set_global(2);
                 }
                 Opcode::SetGlobal3 => {
-                    self.execute_assign_operation_or_pop_and_set_global(3, false)?;
+                    self.pop_and_set_global(3);
                 }
                 Opcode::SetGlobal4 => {
-                    self.execute_assign_operation_or_pop_and_set_global(4, false)?;
+                    self.pop_and_set_global(4);
                 }
                 // get global
                 Opcode::GetGlobal => {
@@ -267,7 +483,7 @@ impl Vm {
                 }
                 // set local
                 Opcode::SetLocal => {
-                    frame.ip += 2;
+                    frame.ip += 1;
                     self.pop_and_set_local(ins[ip] as usize);
                 }
                 Opcode::SetLocal0 => self.pop_and_set_local(0),
@@ -275,6 +491,15 @@ impl Vm {
                 Opcode::SetLocal2 => self.pop_and_set_local(2),
                 Opcode::SetLocal3 => self.pop_and_set_local(3),
                 Opcode::SetLocal4 => self.pop_and_set_local(4),
+                Opcode::AssignLocal => {
+                    frame.ip += 1;
+                    let popped = self.pop_stack();
+                    let slot = self.frames.last().unwrap().base_pointer + ins[ip] as usize;
+                    match self.stack[slot].as_ref() {
+                        Object::Cell(cell) => *cell.borrow_mut() = popped,
+                        _ => self.stack[slot] = popped,
+                    }
+                }
                 // get local
                 Opcode::GetLocal => {
                     frame.ip += 1;
@@ -282,9 +507,7 @@ impl Vm {
                     self.get_local_and_push(local_index);
                 }
                 Opcode::GetLocal0 => {
-                    let frame = self.frames.last().unwrap();
-                    let object = self.stack[frame.base_pointer].clone();
-                    self.push_stack(object)
+                    self.get_local_and_push(0);
                 }
                 Opcode::GetLocal1 => {
                     self.get_local_and_push(1);
@@ -314,6 +537,7 @@ impl Vm {
                     if let Object::CompiledFunction(compiled_function) = func_object.as_ref() {
                         //往前free_num个都是free_variable
                         let frees = self.stack[self.sp - free_num..self.sp].to_vec();
+                        self.sp -= free_num;
                         let closure =
                             Object::Closure(Closure::new(compiled_function.clone(), frees));
                         self.push_stack(Rc::new(closure));
@@ -323,6 +547,45 @@ impl Vm {
                     self.current_frame_ip_inc(3);
                 }
                 Opcode::GetFree => {
+                    let free_index = ins[ip] as usize;
+                    let free = self.current_frame().get_free(free_index);
+                    match free.as_ref() {
+                        Object::Cell(cell) => self.push_stack(cell.borrow().clone()),
+                        _ => self.push_stack(free),
+                    }
+                    self.current_frame_ip_inc(1);
+                }
+                Opcode::SetFree => {
+                    let free_index = ins[ip] as usize;
+                    let popped = self.pop_stack();
+                    let free = self.current_frame().get_free(free_index);
+                    match free.as_ref() {
+                        Object::Cell(cell) => *cell.borrow_mut() = popped,
+                        _ => {
+                            return Err(RuntimeError::CustomErrMsg(format!(
+                                "can't assign to {}",
+                                free
+                            )))
+                        }
+                    }
+                    self.current_frame_ip_inc(1);
+                }
+                Opcode::CaptureLocal => {
+                    //局部变量首次被捕获时装入Cell, 之后读写都经过这个Cell
+                    let slot = self.frames.last().unwrap().base_pointer + ins[ip] as usize;
+                    let cell = match self.stack[slot].as_ref() {
+                        Object::Cell(_) => self.stack[slot].clone(),
+                        _ => {
+                            let value = self.stack[slot].clone();
+                            let cell = Rc::new(Object::Cell(RefCell::new(value)));
+                            self.stack[slot] = cell.clone();
+                            cell
+                        }
+                    };
+                    self.push_stack(cell);
+                    self.current_frame_ip_inc(1);
+                }
+                Opcode::CaptureFree => {
                     let free_index = ins[ip] as usize;
                     self.push_stack(self.current_frame().get_free(free_index));
                     self.current_frame_ip_inc(1);
@@ -337,6 +600,41 @@ impl Vm {
                     self.current_frame_ip_inc(1);
                     self.call_function(arg_nums)?;
                 }
+                Opcode::CallSpread => {
+                    let arg_nums = self.spread_arguments();
+                    self.call_function(arg_nums)?;
+                }
+                Opcode::InvokeSpread => {
+                    let name = self.get_field_name(&ins, ip)?;
+                    self.current_frame_ip_inc(2);
+                    let arg_nums = self.spread_arguments();
+                    self.invoke_method(&name, arg_nums)?;
+                }
+                Opcode::Concat => {
+                    let count = self.read_u16(&ins, ip);
+                    self.current_frame_ip_inc(2);
+                    let mut items = vec![];
+                    for part in &self.stack[self.sp - count..self.sp] {
+                        match part.as_ref() {
+                            Object::Array(part) => items.extend(part.borrow().iter().cloned()),
+                            obj => return Err(RuntimeError::CannotSpread(obj.clone())),
+                        }
+                    }
+                    self.sp -= count;
+                    self.push_stack(Rc::new(Object::Array(Rc::new(Container::new(items)))));
+                }
+
+                Opcode::IterNew => {
+                    let iterable = self.pop_stack();
+                    let iter = Iter::new(iterable)?;
+                    self.push_stack(Rc::new(Object::Iterator(Box::new(RefCell::new(iter)))));
+                }
+                Opcode::IterNext => {
+                    let end = self.read_u16(&ins, ip);
+                    let value_count = ins[ip + 2] as usize;
+                    self.current_frame_ip_inc(3);
+                    self.execute_iter_next(end, value_count)?;
+                }
 
                 Opcode::ReturnValue => {
                     let return_value = self.stack[self.sp - 1].clone();
@@ -351,6 +649,20 @@ impl Vm {
                     self.sp = base_pointer - 1;
                     self.push_stack(self.null_cache.clone());
                 }
+                Opcode::SetupTry => {
+                    let catch_ip = self.read_u16(&ins, ip);
+                    let sp = self.sp;
+                    let frame = self.frames.last_mut().unwrap();
+                    frame.handlers.push(Handler { catch_ip, sp });
+                    frame.ip += 2;
+                }
+                Opcode::PopTry => {
+                    self.frames.last_mut().unwrap().handlers.pop();
+                }
+                Opcode::Throw => {
+                    let value = self.pop_stack();
+                    return Err(RuntimeError::Thrown(Object::clone(&value)));
+                }
                 _ => return Err(RuntimeError::UnKnownOpCode(op_code)),
             }
             // _time_recorder._tick(op_code);
@@ -384,58 +696,26 @@ impl Vm {
         }
         Ok(())
     }
-    /// # 执行赋值操作
-    pub fn execute_assign_operation_or_pop_and_set_global(
-        &mut self,
-        index: usize,
-        is_local: bool,
-    ) -> VmResult<()> {
-        let opt = if is_local {
-            self.get_local(index)
-        } else {
-            self.get_global(index)
+    /// # 迭代器取下一个值压入栈顶, 迭代结束则跳转
+    pub fn execute_iter_next(&mut self, end: usize, value_count: usize) -> VmResult<()> {
+        let iter = self.stack[self.sp - 1].clone();
+        let iter = match iter.as_ref() {
+            Object::Iterator(iter) => iter,
+            obj => return Err(RuntimeError::NotIterable(obj.clone())),
         };
-        match opt {
-            None => {
-                //声明
-                if is_local {
-                    self.pop_and_set_local(index);
-                } else {
-                    self.pop_and_set_global(index);
-                }
-            }
-            Some(obj) => {
-                //赋值
-                match obj.as_ref() {
-                    //数组赋值
-                    Object::Array(items) => {
-                        let val = self.pop_stack();
-                        let index = self.pop_stack();
-                        if let Object::Integer(i) = index.as_ref() {
-                            items.borrow_mut()[*i as usize] = Object::clone(&val);
-                        } else {
-                            return Err(RuntimeError::UnSupportedIndexOperation(
-                                Object::clone(&obj),
-                                Object::clone(&index),
-                            ));
-                        }
-                    }
-                    // // Hash赋值
-                    Object::Hash(pairs) => {
-                        let val = self.pop_stack();
-                        let index = self.pop_stack();
-                        let key = HashKey::from_object(&index)?;
-                        pairs.borrow_mut().insert(key, Object::clone(&val));
-                    }
-                    //普通赋值
-                    _ => {
-                        if is_local {
-                            self.pop_and_set_local(index);
-                        } else {
-                            self.pop_and_set_global(index);
-                        }
-                    }
+        let mut iter = iter.borrow_mut();
+        if value_count == 1 {
+            match iter.next_value() {
+                Some(value) => self.push_stack(Rc::new(value)),
+                None => self.frames.last_mut().unwrap().ip = end,
+            }
+        } else {
+            match iter.next_pair() {
+                Some((key, value)) => {
+                    self.push_stack(Rc::new(key));
+                    self.push_stack(Rc::new(value));
                 }
+                None => self.frames.last_mut().unwrap().ip = end,
             }
         }
         Ok(())
@@ -448,7 +728,16 @@ impl Vm {
             arr.push(Object::clone(el));
         }
         self.sp -= arr_len;
-        self.push_stack(Rc::new(Object::Array(RefCell::new(arr))))
+        self.push_stack(Rc::new(Object::Array(Rc::new(Container::new(arr)))))
+    }
+    /// # 拼接模板字符串
+    pub fn build_string(&mut self, len: usize) {
+        let string = self.stack[self.sp - len..self.sp]
+            .iter()
+            .map(|part| part.to_string())
+            .collect::<String>();
+        self.sp -= len;
+        self.push_stack(Rc::new(Object::String(string)))
     }
     /// # 创建Hash
     pub fn build_hash(&mut self, hash_len: usize) -> VmResult<()> {
@@ -461,8 +750,53 @@ impl Vm {
             hash.insert(key, Object::clone(v));
             i += 2;
         }
-        self.sp -= hash_len;
-        self.push_stack(Rc::new(Object::Hash(RefCell::new(hash))));
+        self.sp -= 2 * hash_len;
+        self.push_stack(Rc::new(Object::Hash(Rc::new(Container::new(hash)))));
+        Ok(())
+    }
+    /// # 创建结构体类型, 用栈上的闭包填充类型常量中的方法
+    pub fn build_struct(&mut self, const_index: usize, method_count: usize) -> VmResult<()> {
+        let template = match self.get_const_object(const_index).as_ref() {
+            Object::Struct(template) => template.clone(),
+            obj => {
+                return Err(RuntimeError::CustomErrMsg(format!(
+                    "{} is not a struct",
+                    obj
+                )))
+            }
+        };
+        let closures = &self.stack[self.sp - method_count..self.sp];
+        let methods = template
+            .methods
+            .iter()
+            .zip(closures)
+            .map(|((name, _), closure)| (name.clone(), Object::clone(closure)))
+            .collect();
+        self.sp -= method_count;
+        let struct_type = StructType::new(template.name.clone(), template.fields.clone(), methods);
+        self.push_stack(Rc::new(Object::Struct(Rc::new(struct_type))));
+        Ok(())
+    }
+    pub fn build_module(&mut self, const_index: usize, member_count: usize) -> VmResult<()> {
+        let template = match self.get_const_object(const_index).as_ref() {
+            Object::Module(template) => template.clone(),
+            obj => {
+                return Err(RuntimeError::CustomErrMsg(format!(
+                    "{} is not a module",
+                    obj
+                )))
+            }
+        };
+        let values = &self.stack[self.sp - member_count..self.sp];
+        let members = template
+            .members
+            .iter()
+            .zip(values)
+            .map(|((name, _), value)| (name.clone(), Object::clone(value)))
+            .collect();
+        self.sp -= member_count;
+        let module = Module::new(template.name.clone(), members);
+        self.push_stack(Rc::new(Object::Module(Rc::new(module))));
         Ok(())
     }
     /// # 执行二元操作
@@ -478,6 +812,15 @@ impl Vm {
                     .cloned()
                     .unwrap_or_else(|| Rc::new(Object::Integer(r)))
             }
+            (Object::Float(left_val), Object::Float(right_val)) => {
+                Rc::new(Object::Float(left_val + right_val))
+            }
+            (Object::Integer(left_val), Object::Float(right_val)) => {
+                Rc::new(Object::Float(*left_val as f64 + right_val))
+            }
+            (Object::Float(left_val), Object::Integer(right_val)) => {
+                Rc::new(Object::Float(left_val + *right_val as f64))
+            }
             (Object::String(left_val), Object::String(right_val)) => {
                 Rc::new(Object::String(left_val.clone() + right_val))
             }
@@ -487,6 +830,12 @@ impl Vm {
             (Object::String(left_val), Object::Integer(right_val)) => {
                 Rc::new(Object::String(left_val.clone() + &right_val.to_string()))
             }
+            (Object::Float(_), Object::String(right_val)) => {
+                Rc::new(Object::String(left.to_string() + right_val))
+            }
+            (Object::String(left_val), Object::Float(_)) => {
+                Rc::new(Object::String(left_val.clone() + &right.to_string()))
+            }
             _ => {
                 return Err(RuntimeError::UnSupportedBinOperation(
                     Opcode::Add,
@@ -509,12 +858,66 @@ impl Vm {
                     Opcode::Div => {
                         if right_val == &0 {
                             return Err(RuntimeError::ByZero(
+                                BinaryOperator::Div,
                                 Object::clone(left),
                                 Object::clone(right),
                             ));
                         }
                         left_val / right_val
                     }
+                    Opcode::Mod => {
+                        if right_val == &0 {
+                            return Err(RuntimeError::ByZero(
+                                BinaryOperator::Mod,
+                                Object::clone(left),
+                                Object::clone(right),
+                            ));
+                        }
+                        left_val.wrapping_rem(*right_val)
+                    }
+                    Opcode::Pow => match u32::try_from(*right_val) {
+                        Ok(exp) => match left_val.checked_pow(exp) {
+                            Some(r) => r,
+                            None => {
+                                return Err(RuntimeError::Overflow(
+                                    BinaryOperator::Pow,
+                                    Object::clone(left),
+                                    Object::clone(right),
+                                ));
+                            }
+                        },
+                        //负指数的结果为浮点数
+                        Err(_) => {
+                            let r = Self::execute_float_binary_operation(
+                                op,
+                                *left_val as f64,
+                                *right_val as f64,
+                            )?;
+                            self.push_stack(r);
+                            return Ok(());
+                        }
+                    },
+                    Opcode::BitAnd => left_val & right_val,
+                    Opcode::BitOr => left_val | right_val,
+                    Opcode::BitXor => left_val ^ right_val,
+                    Opcode::Shl | Opcode::Shr => {
+                        let shifted = u32::try_from(*right_val).ok().and_then(|rhs| {
+                            if op == &Opcode::Shl {
+                                left_val.checked_shl(rhs)
+                            } else {
+                                left_val.checked_shr(rhs)
+                            }
+                        });
+                        match shifted {
+                            Some(r) => r,
+                            None => {
+                                return Err(RuntimeError::ShiftOverflow(
+                                    Object::clone(left),
+                                    Object::clone(right),
+                                ));
+                            }
+                        }
+                    }
                     _ => return Err(RuntimeError::UnSupportedBinOperator(*op)),
                 };
                 self.int_cache
@@ -522,6 +925,15 @@ impl Vm {
                     .cloned()
                     .unwrap_or_else(|| Rc::new(Object::Integer(r)))
             }
+            (Object::Float(left_val), Object::Float(right_val)) => {
+                Self::execute_float_binary_operation(op, *left_val, *right_val)?
+            }
+            (Object::Integer(left_val), Object::Float(right_val)) => {
+                Self::execute_float_binary_operation(op, *left_val as f64, *right_val)?
+            }
+            (Object::Float(left_val), Object::Integer(right_val)) => {
+                Self::execute_float_binary_operation(op, *left_val, *right_val as f64)?
+            }
             _ => {
                 return Err(RuntimeError::UnSupportedBinOperation(
                     *op,
@@ -533,9 +945,26 @@ impl Vm {
         self.push_stack(result);
         Ok(())
     }
+    /// # 执行浮点数二元操作
+    fn execute_float_binary_operation(op: &Opcode, left: f64, right: f64) -> VmResult {
+        let r = match op {
+            Opcode::Sub => left - right,
+            Opcode::Mul => left * right,
+            Opcode::Div => left / right,
+            Opcode::Mod => left % right,
+            Opcode::Pow => left.powf(right),
+            _ => return Err(RuntimeError::UnSupportedBinOperator(*op)),
+        };
+        Ok(Rc::new(Object::Float(r)))
+    }
     /// # 执行索引操作
     pub fn execute_index_operation(&self, obj: &Object, index: &Object) -> VmResult {
-        if let Object::Array(items) = obj {
+        if let Object::Range(range) = index {
+            //切片
+            if let Some(slice) = range.slice(obj) {
+                return Ok(Rc::new(slice));
+            }
+        } else if let Object::Array(items) = obj {
             if let Object::Integer(index) = index {
                 let value = items.borrow().get(*index as usize).cloned().unwrap_or(NULL);
                 return Ok(Rc::new(value));
@@ -544,6 +973,8 @@ impl Vm {
             let key = HashKey::from_object(index)?;
             let value = pairs.borrow().get(&key).cloned().unwrap_or(NULL);
             return Ok(Rc::new(value));
+        } else if let (Object::Instance(_), Object::String(field)) = (obj, index) {
+            return Ok(Rc::new(obj.get_field(field)?));
         }
         Err(RuntimeError::UnSupportedIndexOperation(
             obj.clone(),
@@ -573,6 +1004,17 @@ impl Vm {
                 Ok(self.bool_cache_false.clone())
             }
             // Ok(self.get_bool_from_cache(bool))
+        } else if let Some((left, right)) = Self::as_float_pair(&left, &right) {
+            let bool = match op {
+                Opcode::GreaterThan => left > right,
+                Opcode::GreaterEq => left >= right,
+                Opcode::LessThan => left < right,
+                Opcode::LessEq => left <= right,
+                Opcode::Equal => left == right,
+                Opcode::NotEqual => left != right,
+                _ => return Err(RuntimeError::UnSupportedBinOperator(*op)),
+            };
+            Ok(self.get_bool_from_cache(bool))
         } else {
             match op {
                 Opcode::Equal => Ok(self.get_bool_from_cache(left == right)),
@@ -585,6 +1027,15 @@ impl Vm {
             }
         }
     }
+    /// 两个操作数都是数字且至少有一个是浮点数时, 转换为浮点数对
+    fn as_float_pair(left: &Object, right: &Object) -> Option<(f64, f64)> {
+        match (left, right) {
+            (Object::Float(l), Object::Float(r)) => Some((*l, *r)),
+            (Object::Integer(l), Object::Float(r)) => Some((*l as f64, *r)),
+            (Object::Float(l), Object::Integer(r)) => Some((*l, *r as f64)),
+            _ => None,
+        }
+    }
     pub fn get_bool_from_cache(&self, bool: bool) -> Rc<Object> {
         if bool {
             self.bool_cache_true.clone()
@@ -596,18 +1047,29 @@ impl Vm {
     #[inline]
     pub fn call_function(&mut self, arg_nums: usize) -> VmResult<()> {
         self.sp -= arg_nums;
-        let callee = &self.stack[self.sp - 1]; //往回跳过参数个数位置, 当前位置是函数
+        let callee = self.stack[self.sp - 1].clone(); //往回跳过参数个数位置, 当前位置是函数
         match callee.as_ref() {
             Object::Closure(Closure {
                 compiled_function, ..
             }) => {
-                // if arg_nums != compiled_function.num_parameters {
-                //     return Err(RuntimeError::WrongArgumentCount(
-                //         compiled_function.num_parameters,
-                //         arg_nums,
-                //     ));
-                // }
-                // let num_locals = closure.compiled_function.num_locals;
+                let rest = compiled_function.rest;
+                let positional = compiled_function.num_parameters - rest as usize;
+                let required = positional - compiled_function.num_defaults;
+                check_argument_count(required, positional, rest, arg_nums)?;
+                let base = self.sp;
+                if rest {
+                    //多出的参数收集为数组, 放在剩余参数的位置
+                    let extra = self.stack[base + positional.min(arg_nums)..base + arg_nums]
+                        .iter()
+                        .map(|arg| Object::clone(arg))
+                        .collect();
+                    self.stack[base + positional] =
+                        Rc::new(Object::Array(Rc::new(Container::new(extra))));
+                }
+                //缺省的参数为null, 由函数开头的代码换成默认值
+                for slot in base + arg_nums..base + positional {
+                    self.stack[slot] = self.null_cache.clone();
+                }
                 let frame = Frame::new(callee.clone(), self.sp);
                 // Equivalent to
                 self.sp += compiled_function.num_locals;
@@ -625,6 +1087,16 @@ impl Vm {
                 self.sp -= 1;
                 self.push_stack(Rc::new(r));
             }
+            Object::Struct(struct_type) => {
+                //构造结构体实例
+                let args = self.stack[self.sp..self.sp + arg_nums]
+                    .iter()
+                    .map(|arg| Object::clone(arg))
+                    .collect();
+                let instance = struct_type.instantiate(args)?;
+                self.sp -= 1;
+                self.push_stack(Rc::new(instance));
+            }
             _ => {
                 return Err(RuntimeError::CustomErrMsg(
                     "calling non-function".to_string(),
@@ -634,6 +1106,66 @@ impl Vm {
         // self.pop_stack();
         Ok(())
     }
+    /// 方法调用
+    ///
+    /// 优先按接收者类型查找内置方法, 其次是结构体的方法, 最后调用Hash或实例中的同名函数成员
+    pub fn invoke_method(&mut self, name: &str, arg_nums: usize) -> VmResult<()> {
+        let receiver_pos = self.sp - arg_nums - 1;
+        let receiver = self.stack[receiver_pos].clone();
+        if let Some(Object::Builtin(method)) = lookup_method(&receiver, name) {
+            //接收者作为第一个参数
+            let args = self.stack[receiver_pos..self.sp]
+                .iter()
+                .map(|arg| Object::clone(arg))
+                .collect();
+            let result = method(args)?;
+            self.sp = receiver_pos;
+            self.push_stack(Rc::new(result));
+            return Ok(());
+        }
+        if let Object::Instance(instance) = receiver.as_ref() {
+            if let Some(method) = instance.struct_type.method(name) {
+                //方法放到接收者之前, 接收者作为第一个参数this
+                self.push_stack(Rc::new(method));
+                self.stack[receiver_pos..self.sp].rotate_right(1);
+                return self.call_function(arg_nums + 1);
+            }
+        }
+        match receiver.get_field(name) {
+            Ok(member) if member != NULL => {
+                //用成员函数替换接收者, 按普通函数调用
+                self.stack[receiver_pos] = Rc::new(member);
+                self.call_function(arg_nums)
+            }
+            _ => Err(RuntimeError::NoSuchMember(
+                Object::clone(&receiver),
+                name.to_string(),
+            )),
+        }
+    }
+    /// 弹出栈顶的参数数组, 将各个元素作为参数压栈, 返回参数个数
+    fn spread_arguments(&mut self) -> usize {
+        let args = self.pop_stack();
+        let args = match args.as_ref() {
+            Object::Array(args) => args.borrow().clone(),
+            _ => vec![],
+        };
+        let arg_nums = args.len();
+        for arg in args {
+            self.push_stack(Rc::new(arg));
+        }
+        arg_nums
+    }
+    /// 读取成员名常量
+    fn get_field_name(&self, ins: &[u8], ip: usize) -> VmResult<String> {
+        match self.constants[self.read_u16(ins, ip)].as_ref() {
+            Object::String(name) => Ok(name.clone()),
+            obj => Err(RuntimeError::CustomErrMsg(format!(
+                "member name must be string, got {}",
+                obj
+            ))),
+        }
+    }
     /// # 读取一个无符号整数，并返回字节长度
     pub fn read_usize(&self, op_code: Opcode, ip: usize) -> (usize, usize) {
         let (operands, n) = read_operands(
@@ -674,10 +1206,11 @@ impl Vm {
     pub fn pop_and_set_global(&mut self, global_index: usize) {
         let global = self.pop_stack();
         if global_index >= self.globals.len() {
-            self.globals.push(global);
-        } else {
-            self.globals[global_index] = global;
+            //变量不一定按定义顺序赋值(如for-in的倒序绑定)
+            self.globals
+                .resize(global_index + 1, self.null_cache.clone());
         }
+        self.globals[global_index] = global;
     }
     /// # 弹出栈顶元素并设置到指定位置
     pub fn pop_and_set_local(&mut self, local_index: usize) {
@@ -696,7 +1229,11 @@ impl Vm {
     /// # 取出局部变量并压入栈顶
     pub fn get_local_and_push(&mut self, local_index: usize) {
         let object = self.get_local(local_index).unwrap();
-        self.push_stack(object)
+        match object.as_ref() {
+            //被闭包捕获的变量
+            Object::Cell(cell) => self.push_stack(cell.borrow().clone()),
+            _ => self.push_stack(object),
+        }
     }
     /// # 取出全局变量并压入栈顶
     pub fn get_global_and_push(&mut self, global_index: usize) {
@@ -1,10 +1,10 @@
 #[cfg(test)]
 mod tests {
-    use std::cell::RefCell;
     use std::collections::HashMap;
+    use std::rc::Rc;
 
     use crate::compiler::Compiler;
-    use crate::object::{HashKey, Object, RuntimeError};
+    use crate::object::{Container, HashKey, Object, RuntimeError};
     use crate::parser::ast::Program;
     use crate::vm::Vm;
     use std::time::Instant;
@@ -355,7 +355,6 @@ mod tests {
         ];
         run_vm_test(tests);
     }
-
     #[test]
     fn test_index() {
         let tests = vec![
@@ -373,22 +372,22 @@ mod tests {
     #[test]
     fn test_hash_literal() {
         let tests = vec![
-            ("{}", Object::Hash(RefCell::new(hash! {}))),
+            ("{}", Object::Hash(Rc::new(Container::new(hash! {})))),
             (
                 r#"{"a":1, "b":2, "c":3}"#,
-                Object::Hash(RefCell::new(hash! {
+                Object::Hash(Rc::new(Container::new(hash! {
                 HashKey::String("a".to_string()) => Object::Integer(1),
                 HashKey::String("b".to_string()) => Object::Integer(2),
                 HashKey::String("c".to_string()) => Object::Integer(3),
-                })),
+                }))),
             ),
             (
                 r#"{1+1:2+2,"hello":5*3, 10:"yo"}"#,
-                Object::Hash(RefCell::new(hash! {
+                Object::Hash(Rc::new(Container::new(hash! {
                 HashKey::Integer(2) => Object::Integer(4),
                 HashKey::String("hello".to_string()) => Object::Integer(15),
                 HashKey::Integer(10) => Object::String("yo".to_string()),
-                })),
+                }))),
             ),
         ];
         run_vm_test(tests);
@@ -397,22 +396,22 @@ mod tests {
     #[test]
     fn test_array_literal() {
         let tests = vec![
-            ("[]", Object::Array(RefCell::new(vec![]))),
+            ("[]", Object::Array(Rc::new(Container::new(vec![])))),
             (
                 "[1,2,3]",
-                Object::Array(RefCell::new(vec![
+                Object::Array(Rc::new(Container::new(vec![
                     Object::Integer(1),
                     Object::Integer(2),
                     Object::Integer(3),
-                ])),
+                ]))),
             ),
             (
                 "[1+2, 2*3, 3-1]",
-                Object::Array(RefCell::new(vec![
+                Object::Array(Rc::new(Container::new(vec![
                     Object::Integer(3),
                     Object::Integer(6),
                     Object::Integer(2),
-                ])),
+                ]))),
             ),
         ];
         run_vm_test(tests);
@@ -469,6 +468,24 @@ mod tests {
             ("if true { 10 } else { 20 }", Object::Integer(10)),
             ("if 1<2 { 10 }", Object::Integer(10)),
             ("if 1>2 { 10 } else { 20 }", Object::Integer(20)),
+            (
+                "if 1>2 { 10 } else if 1<2 { 20 } else { 30 }",
+                Object::Integer(20),
+            ),
+            (
+                "if 1>2 { 10 } else if 2<1 { 20 } else { 30 }",
+                Object::Integer(30),
+            ),
+            ("if 1>2 { 10 } else if 2<1 { 20 }", Object::Null),
+            (
+                r"
+            let grade = fn(score) {
+                if score > 89 { 4 } else if score > 79 { 3 } else if score > 59 { 2 } else { 1 }
+            }
+            grade(95) * 1000 + grade(80) * 100 + grade(60) * 10 + grade(10)
+            ",
+                Object::Integer(4321),
+            ),
         ];
         run_vm_test(tests);
     }
@@ -482,6 +499,10 @@ mod tests {
             ("1>2", Object::Boolean(false)),
             ("1>1", Object::Boolean(false)),
             ("1>1", Object::Boolean(false)),
+            ("1>=1", Object::Boolean(true)),
+            ("1<=0", Object::Boolean(false)),
+            ("2 >= 1.5", Object::Boolean(true)),
+            ("1.5 <= 1.5", Object::Boolean(true)),
             ("1==1", Object::Boolean(true)),
             ("1!=1", Object::Boolean(false)),
             ("1==2", Object::Boolean(false)),
@@ -515,7 +536,7 @@ mod tests {
             ("15/2 +3", Object::Integer(10)),
             ("15/(2 +3)", Object::Integer(3)),
         ];
-        run_vm_test(tests)
+        run_vm_test(tests);
     }
 
     fn run_vm_test(tests: Vec<(&str, Object)>) {
@@ -559,8 +580,9 @@ mod tests {
             match compiler.compile(&program) {
                 Ok(byte_code) => {
                     let mut vm = Vm::new(byte_code);
-                    if let Err(err) = vm.run() {
-                        assert_eq!(err, expected, "error input:\n{}", input);
+                    match vm.run() {
+                        Err(err) => assert_eq!(err, expected, "error input:\n{}", input),
+                        Ok(_) => panic!("Input: {}\nexpected error: {:?}", input, expected),
                     }
                 }
                 Err(e) => panic!("Input: {}\nCompiler Error: {:?}", input, e),